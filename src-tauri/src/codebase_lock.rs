@@ -0,0 +1,426 @@
+//! Codebase Lock - Finds the process holding a UCM codebase lock
+//!
+//! UCM takes an exclusive lock on `<root>/.unison/v2/unison.lockfile`, so a second
+//! UCM on the same codebase fails with "Failed to obtain a file lock". This module provides:
+//! - `find_lock_holder`: pid and command line of the UCM holding the lock, found through
+//!   `/proc/locks` on Linux and otherwise by scanning running `ucm` processes
+//! - `kill_lock_holder`: stops the holder, but only when it is an orphan - a UCM spawned
+//!   by an editor that is no longer running
+//! - `parse_api_port`: the HTTP API port from the holder's `--port` argument, so the
+//!   editor can connect to it read-only instead
+
+use crate::ucm_session::UCMCodebase;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Set on every UCM the editor spawns, so a UCM left behind by a crashed editor
+/// can be recognised as ours
+pub const EDITOR_PID_ENV: &str = "UNISON_EDITOR_PID";
+
+/// How long the holder gets to exit after SIGTERM before it is sent SIGKILL
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// How a lock holder was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockHolderSource {
+    /// The kernel's lock table names the process locking the lock file
+    LockFile,
+    /// A running `ucm` opened on the same codebase (the lock table wasn't available)
+    ProcessScan,
+}
+
+/// The process holding a codebase lock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub pid: u32,
+    #[serde(rename = "parentPid")]
+    pub parent_pid: Option<u32>,
+    #[serde(rename = "commandLine")]
+    pub command_line: String,
+    /// HTTP API port from the holder's `--port` argument
+    #[serde(rename = "apiPort")]
+    pub api_port: Option<u16>,
+    /// Spawned by an editor that is no longer running - safe to kill
+    pub orphaned: bool,
+    pub source: LockHolderSource,
+    #[serde(rename = "lockFile")]
+    pub lock_file: Option<String>,
+}
+
+/// A running process, as far as it matters here
+#[derive(Debug, Clone)]
+struct ProcessInfo {
+    pid: u32,
+    parent_pid: Option<u32>,
+    args: Vec<String>,
+    /// `UNISON_EDITOR_PID` from the process environment, where it can be read
+    editor_pid: Option<u32>,
+}
+
+/// Directory a codebase is rooted at (`--codebase` path, or the home directory)
+pub fn codebase_root(codebase: Option<&UCMCodebase>) -> Option<PathBuf> {
+    match codebase {
+        Some(codebase) => Some(PathBuf::from(&codebase.path)),
+        None => dirs::home_dir(),
+    }
+}
+
+/// Lock files of the codebase at `root`
+fn lock_files(root: &Path) -> Vec<PathBuf> {
+    let dir = root.join(".unison").join("v2");
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with("lockfile") || name.ends_with(".lock"))
+        })
+        .collect()
+}
+
+/// Pids holding a lock on any of `inodes`, from the contents of `/proc/locks`, e.g.
+/// `1: FLOCK  ADVISORY  WRITE 4242 00:1a:1234567 0 EOF`.
+/// Waiters (`1: -> FLOCK ...`) and OFD locks (pid -1) are skipped.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_locks(contents: &str, inodes: &[u64]) -> Vec<u32> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.get(1) == Some(&"->") {
+                return None;
+            }
+            let pid: u32 = fields.get(4)?.parse().ok()?;
+            let inode: u64 = fields.get(5)?.rsplit(':').next()?.parse().ok()?;
+            inodes.contains(&inode).then_some(pid)
+        })
+        .collect()
+}
+
+/// Value following `flags` in `args`, as `--flag value` or `--flag=value`
+fn flag_value<'a>(args: &'a [String], flags: &[&str]) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(i, arg)| {
+        flags.iter().find_map(|flag| {
+            if arg == flag {
+                args.get(i + 1).map(String::as_str)
+            } else {
+                arg.strip_prefix(flag)?.strip_prefix('=')
+            }
+        })
+    })
+}
+
+/// HTTP API port a UCM was started with (`--port 5858`)
+pub fn parse_api_port(args: &[String]) -> Option<u16> {
+    flag_value(args, &["--port", "-p"])?.parse().ok()
+}
+
+/// Codebase root a UCM was started with (`--codebase`/`-c`/`--codebase-create`)
+fn parse_codebase_arg(args: &[String]) -> Option<&str> {
+    flag_value(args, &["--codebase-create", "--codebase", "-c"])
+}
+
+fn is_ucm(args: &[String]) -> bool {
+    args.first()
+        .and_then(|program| Path::new(program).file_name())
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name == "ucm" || name == "ucm.exe")
+}
+
+/// Whether a UCM with `args`, started in `cwd`, opens the codebase at `root`
+fn opens_codebase(args: &[String], cwd: Option<&Path>, root: &Path) -> bool {
+    match parse_codebase_arg(args) {
+        Some(path) => {
+            let path = Path::new(path);
+            let path = match cwd {
+                Some(cwd) if path.is_relative() => cwd.join(path),
+                _ => path.to_path_buf(),
+            };
+            same_path(&path, root)
+        }
+        None => dirs::home_dir().is_some_and(|home| same_path(&home, root)),
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists; EPERM means it does but isn't ours
+    let exists = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+fn process_info(pid: u32) -> Option<ProcessInfo> {
+    let proc_dir = PathBuf::from("/proc").join(pid.to_string());
+    let args: Vec<String> = std::fs::read(proc_dir.join("cmdline"))
+        .ok()?
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect();
+    // `pid (comm) state ppid ...` - comm may contain spaces, so parse after the last ')'
+    let stat = std::fs::read_to_string(proc_dir.join("stat")).ok()?;
+    let parent_pid = stat
+        .rsplit_once(')')
+        .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+        .and_then(|ppid| ppid.parse().ok());
+    // Only readable for our own processes, which are the only ones we'd kill anyway
+    let editor_pid = std::fs::read(proc_dir.join("environ")).ok().and_then(|environ| {
+        environ.split(|b| *b == 0).find_map(|var| {
+            let var = String::from_utf8_lossy(var);
+            var.strip_prefix(EDITOR_PID_ENV)?.strip_prefix('=')?.parse().ok()
+        })
+    });
+    Some(ProcessInfo {
+        pid,
+        parent_pid,
+        args,
+        editor_pid,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn list_processes() -> Vec<ProcessInfo> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .filter_map(process_info)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn process_cwd(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+/// Run `ps` and parse lines of `pid ppid command...` (`ps` can't report the environment
+/// of other processes portably, so `editor_pid` stays unknown)
+#[cfg(all(unix, not(target_os = "linux")))]
+fn ps_processes(args: &[&str]) -> Vec<ProcessInfo> {
+    let Ok(output) = std::process::Command::new("ps").args(args).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let parent_pid = fields.next()?.parse().ok();
+            Some(ProcessInfo {
+                pid,
+                parent_pid,
+                args: fields.map(str::to_string).collect(),
+                editor_pid: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_info(pid: u32) -> Option<ProcessInfo> {
+    ps_processes(&["-o", "pid=,ppid=,command=", "-p", &pid.to_string()])
+        .into_iter()
+        .next()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn list_processes() -> Vec<ProcessInfo> {
+    ps_processes(&["-axo", "pid=,ppid=,command="])
+}
+
+#[cfg(not(unix))]
+fn process_info(_pid: u32) -> Option<ProcessInfo> {
+    None
+}
+
+#[cfg(not(unix))]
+fn list_processes() -> Vec<ProcessInfo> {
+    Vec::new()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_cwd(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// A UCM spawned by an editor that has since gone away. Only a UCM whose
+/// `UNISON_EDITOR_PID` can be read counts: without it (outside Linux) any UCM the
+/// user started with `--port` could look like ours, so none is reported as orphaned.
+fn is_orphan(process: &ProcessInfo) -> bool {
+    process
+        .editor_pid
+        .is_some_and(|editor_pid| editor_pid != std::process::id() && !process_alive(editor_pid))
+}
+
+fn lock_holder(process: ProcessInfo, source: LockHolderSource, lock_file: Option<&Path>) -> LockHolder {
+    LockHolder {
+        pid: process.pid,
+        parent_pid: process.parent_pid,
+        command_line: process.args.join(" "),
+        api_port: parse_api_port(&process.args),
+        orphaned: is_orphan(&process),
+        source,
+        lock_file: lock_file.map(|path| path.display().to_string()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn holder_from_lock_table(lock_files: &[PathBuf]) -> Option<LockHolder> {
+    use std::os::unix::fs::MetadataExt;
+
+    let proc_locks = std::fs::read_to_string("/proc/locks").ok()?;
+    lock_files.iter().find_map(|lock_file| {
+        let inode = std::fs::metadata(lock_file).ok()?.ino();
+        parse_proc_locks(&proc_locks, &[inode])
+            .into_iter()
+            .filter(|pid| *pid != std::process::id())
+            .find_map(process_info)
+            .map(|process| lock_holder(process, LockHolderSource::LockFile, Some(lock_file)))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn holder_from_lock_table(_lock_files: &[PathBuf]) -> Option<LockHolder> {
+    None
+}
+
+/// Find the process holding the lock of the codebase rooted at `root`
+pub fn find_lock_holder(root: &Path) -> Option<LockHolder> {
+    let lock_files = lock_files(root);
+    if let Some(holder) = holder_from_lock_table(&lock_files) {
+        return Some(holder);
+    }
+
+    // Our own UCMs (PTY, MCP) are never the conflicting process
+    let own_pid = std::process::id();
+    list_processes()
+        .into_iter()
+        .filter(|process| process.pid != own_pid && process.parent_pid != Some(own_pid))
+        .filter(|process| is_ucm(&process.args) && !process.args.iter().any(|arg| arg == "mcp"))
+        .find(|process| opens_codebase(&process.args, process_cwd(process.pid).as_deref(), root))
+        .map(|process| lock_holder(process, LockHolderSource::ProcessScan, lock_files.first().map(PathBuf::as_path)))
+}
+
+/// Stop the orphaned UCM `pid` holding the lock of the codebase at `root`: SIGTERM,
+/// then SIGKILL if it hasn't exited within `KILL_GRACE`
+pub async fn kill_lock_holder(root: &Path, pid: u32) -> Result<(), String> {
+    let holder = find_lock_holder(root)
+        .filter(|holder| holder.pid == pid)
+        .ok_or_else(|| format!("Process {} no longer holds the codebase lock", pid))?;
+    if !holder.orphaned {
+        return Err(format!(
+            "Process {} was not started by a previous editor session; close it yourself ({})",
+            pid, holder.command_line
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        log::warn!("Killing orphaned UCM {} holding the codebase lock: {}", pid, holder.command_line);
+        for (signal, name) in [(libc::SIGTERM, "SIGTERM"), (libc::SIGKILL, "SIGKILL")] {
+            if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+                return Err(format!(
+                    "Failed to send {} to {}: {}",
+                    name,
+                    pid,
+                    std::io::Error::last_os_error()
+                ));
+            }
+            let deadline = tokio::time::Instant::now() + KILL_GRACE;
+            while tokio::time::Instant::now() < deadline {
+                if !process_alive(pid) {
+                    return Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+        Err(format!("Process {} did not exit", pid))
+    }
+
+    #[cfg(not(unix))]
+    {
+        Err("Killing the lock holder is not supported on this platform".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_orphan_requires_editor_pid() {
+        let process = ProcessInfo {
+            pid: 4242,
+            parent_pid: Some(1),
+            args: vec!["ucm".to_string(), "--port".to_string(), "5858".to_string()],
+            editor_pid: None,
+        };
+        assert!(!is_orphan(&process));
+        // Spawned by this (running) editor
+        let ours = ProcessInfo { editor_pid: Some(std::process::id()), ..process };
+        assert!(!is_orphan(&ours));
+    }
+
+    #[test]
+    fn test_parse_proc_locks() {
+        let contents = "\
+1: FLOCK  ADVISORY  WRITE 4242 00:1a:1234567 0 EOF
+1: -> FLOCK  ADVISORY  WRITE 5151 00:1a:1234567 0 EOF
+2: POSIX  ADVISORY  WRITE 777 08:01:99 0 EOF
+3: OFDLCK ADVISORY  READ  -1 00:1a:1234567 0 EOF";
+        assert_eq!(parse_proc_locks(contents, &[1234567]), vec![4242]);
+        assert_eq!(parse_proc_locks(contents, &[99]), vec![777]);
+        assert!(parse_proc_locks(contents, &[5]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_ucm_args() {
+        assert_eq!(parse_api_port(&args("ucm --port 5858")), Some(5858));
+        assert_eq!(parse_api_port(&args("/usr/local/bin/ucm -c ~/cb --port=6000")), Some(6000));
+        assert_eq!(parse_api_port(&args("ucm -p 5900")), Some(5900));
+        assert_eq!(parse_api_port(&args("ucm --port-file x")), None);
+        assert_eq!(parse_api_port(&args("ucm")), None);
+
+        assert_eq!(parse_codebase_arg(&args("ucm -c /tmp/cb")), Some("/tmp/cb"));
+        assert_eq!(parse_codebase_arg(&args("ucm --codebase-create /tmp/new")), Some("/tmp/new"));
+        assert_eq!(parse_codebase_arg(&args("ucm --codebase=/tmp/cb")), Some("/tmp/cb"));
+        assert_eq!(parse_codebase_arg(&args("ucm --port 5858")), None);
+
+        assert!(is_ucm(&args("/opt/homebrew/bin/ucm --port 5858")));
+        assert!(!is_ucm(&args("/usr/bin/ucmx")));
+    }
+
+    #[test]
+    fn test_opens_codebase() {
+        let root = Path::new("/tmp/codebase-lock-test");
+        assert!(opens_codebase(&args("ucm -c /tmp/codebase-lock-test"), None, root));
+        assert!(opens_codebase(&args("ucm -c codebase-lock-test"), Some(Path::new("/tmp")), root));
+        assert!(!opens_codebase(&args("ucm -c /tmp/other"), None, root));
+        assert!(!opens_codebase(&args("ucm"), None, root));
+    }
+}
@@ -1,55 +1,92 @@
-use crate::file_watcher::FileWatcherManager;
-use crate::lsp_proxy::LspProxy;
+use crate::codebase_lock::{codebase_root, find_lock_holder, kill_lock_holder, LockHolder};
+use crate::directory_listing::{list_page, list_tree, DirectoryPage, FileNode, ListOptions};
+use crate::file_history::{self, DiffLine, HistoryEntry, SnapshotLabel};
+use crate::file_operations::{FileOperation, OperationJournal};
+use crate::file_transfer::{self, ConflictPolicy, TransferEvent, TransferMode, TransferProgress, TransferSummary};
+use crate::file_io::{check_version, content_version, write_atomic, WriteFileError};
+use crate::file_watcher::{FileWatcherManager, WatcherConfig};
+use crate::lsp_inspector::LspTrafficEntry;
+use crate::lsp_middleware::CodebaseEnricher;
+use crate::lsp_proxy::{generate_proxy_token, LspProxy};
+use crate::path_guard::WorkspaceGuard;
 use crate::mcp_client::{MCPClient, RunFunctionResult, RunTestsResult, TypecheckResult, UpdateResult};
-use crate::port_utils::find_available_port;
+use crate::port_utils::find_available_port_excluding;
+use crate::problems::FileProblems;
+use crate::transcript::TranscriptResult;
+use crate::ucm_locator::{locate_ucm, UCMInfo};
 use crate::ucm_api::{
     Branch, CurrentContext, Definition, DefinitionSummary, NamespaceItem, Project, SearchResult,
     UCMApiClient,
 };
-use crate::ucm_pty::{UCMContext, UCMPtyManager};
+use crate::pty_session::{PtyAttachSnapshot, PtySessionInfo, PtySessionManager, INITIAL_COLS, INITIAL_ROWS};
+use crate::ucm_pty::{UCMContext, UCMExecResult, UCMPromptState, UCMPtyManager};
+use crate::workspace_search::{
+    self, ReplaceResult, SearchEvent, SearchQuery, SearchRegistry, SearchSummary,
+};
+use crate::ucm_session::{
+    resolve_session_id, SessionMode, SessionPorts, SessionRegistry, UCMCodebase, UCMSession,
+    UCMSessionInfo,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, State};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::DialogExt;
 use tokio::sync::Mutex as TokioMutex;
 
 pub struct AppState {
-    pub ucm_client: Mutex<Option<UCMApiClient>>,
-    pub mcp_client: Mutex<Option<MCPClient>>,
-    /// UCM PTY manager - uses tokio Mutex for async access
-    pub ucm_pty: TokioMutex<Option<UCMPtyManager>>,
-    /// UCM HTTP API port (dynamically allocated, default 5858)
-    pub api_port: Mutex<u16>,
-    /// UCM LSP server port (dynamically allocated, default 5757)
-    pub lsp_port: Mutex<u16>,
-    /// WebSocket proxy port for LSP (dynamically allocated, default 5758)
-    pub lsp_proxy_port: Mutex<u16>,
+    /// UCM sessions keyed by session id - each has its own PTY, API/MCP clients,
+    /// ports, LSP proxy, inspector and problems
+    pub sessions: SessionRegistry,
+    /// Per-launch secret required to connect to the LSP WebSocket proxies
+    pub lsp_proxy_token: String,
     /// File watcher for detecting external file changes
     pub file_watcher: FileWatcherManager,
+    /// `ucm` binary configured in the editor settings (None = auto-detect)
+    pub ucm_path: Mutex<Option<String>>,
+    /// Shell PTYs of the general terminal panel
+    pub pty_sessions: PtySessionManager,
+    /// Running workspace searches, so they can be cancelled
+    pub searches: SearchRegistry,
+    /// Create/rename/delete operations of the file explorer, for undo/redo
+    pub file_operations: OperationJournal,
+    /// Root of the open workspace, which file commands are confined to
+    pub workspace: WorkspaceGuard,
+}
+
+impl AppState {
+    /// Resolve the `ucm` binary to spawn, honoring the configured path
+    pub fn locate_ucm(&self) -> Result<PathBuf, String> {
+        let configured = self.ucm_path.lock().unwrap().clone();
+        locate_ucm(configured.as_deref()).map(|location| location.path)
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            // UCM client will be initialized when UCM is spawned with the actual port
-            ucm_client: Mutex::new(None),
-            mcp_client: Mutex::new(None),
-            ucm_pty: TokioMutex::new(None),
-            api_port: Mutex::new(5858),
-            lsp_port: Mutex::new(5757),
-            lsp_proxy_port: Mutex::new(5758),
+            sessions: SessionRegistry::new(),
+            lsp_proxy_token: generate_proxy_token(),
             file_watcher: FileWatcherManager::new(),
+            ucm_path: Mutex::new(None),
+            pty_sessions: PtySessionManager::new(),
+            searches: SearchRegistry::new(),
+            file_operations: OperationJournal::new(),
+            workspace: WorkspaceGuard::new(),
         }
     }
 }
 
 #[tauri::command]
-pub async fn get_projects(state: State<'_, AppState>) -> Result<Vec<Project>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+#[allow(non_snake_case)]
+pub async fn get_projects(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Project>, String> {
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .get_projects()
@@ -61,12 +98,10 @@ pub async fn get_projects(state: State<'_, AppState>) -> Result<Vec<Project>, St
 #[allow(non_snake_case)]
 pub async fn get_branches(
     projectName: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Vec<Branch>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .get_branches(&projectName)
@@ -75,13 +110,12 @@ pub async fn get_branches(
 }
 
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn get_current_context(
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<CurrentContext, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .get_current_context()
@@ -95,12 +129,10 @@ pub async fn list_namespace(
     projectName: String,
     branchName: String,
     namespace: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Vec<NamespaceItem>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .list_namespace(&projectName, &branchName, &namespace)
@@ -114,12 +146,10 @@ pub async fn get_definition(
     projectName: String,
     branchName: String,
     name: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Option<DefinitionSummary>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     // Use suffixifyBindings=true for display (shorter, more readable names)
     client
@@ -136,12 +166,10 @@ pub async fn get_definition_fqn(
     projectName: String,
     branchName: String,
     name: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Option<DefinitionSummary>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     // Use suffixifyBindings=false for FQN source (for scratch files)
     client
@@ -157,12 +185,10 @@ pub async fn find_definitions(
     branchName: String,
     query: String,
     limit: usize,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Vec<SearchResult>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .find_definitions(&projectName, &branchName, &query, limit)
@@ -176,12 +202,10 @@ pub async fn get_dependencies(
     projectName: String,
     branchName: String,
     name: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Vec<Definition>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .get_dependencies(&projectName, &branchName, &name)
@@ -195,12 +219,10 @@ pub async fn get_dependents(
     projectName: String,
     branchName: String,
     name: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Vec<Definition>, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .get_dependents(&projectName, &branchName, &name)
@@ -209,11 +231,12 @@ pub async fn get_dependents(
 }
 
 #[tauri::command]
-pub async fn check_ucm_connection(state: State<'_, AppState>) -> Result<bool, String> {
-    let client = {
-        let client_guard = state.ucm_client.lock().unwrap();
-        client_guard.as_ref().ok_or("UCM client not initialized")?.clone()
-    };
+#[allow(non_snake_case)]
+pub async fn check_ucm_connection(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let client = state.sessions.get(&resolve_session_id(sessionId))?.api_client()?;
 
     client
         .check_connection()
@@ -222,94 +245,108 @@ pub async fn check_ucm_connection(state: State<'_, AppState>) -> Result<bool, St
 }
 
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn configure_ucm(
     host: String,
     port: u16,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let mut client_guard = state.ucm_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let mut client_guard = session.ucm_client.lock().unwrap();
     *client_guard = Some(UCMApiClient::new(&host, port));
     Ok(())
 }
 
 // File System Commands
 
-/// Maximum recursion depth for directory listing to prevent infinite loops
-const MAX_DIRECTORY_DEPTH: usize = 50;
-
-/// Validate that a path is within the allowed workspace directory
-/// Returns the canonicalized path if valid, or an error if path traversal is detected
-fn validate_path(path: &str, workspace: Option<&str>) -> Result<PathBuf, String> {
-    let path_buf = PathBuf::from(path);
-
-    // Check for path traversal attempts in the raw path
-    if path.contains("..") {
-        return Err(format!("Path traversal not allowed: {}", path));
+/// Show the native folder dialog and trust the picked folder as a workspace, so
+/// `open_workspace` accepts it (see `path_guard`). The dialog is shown by the backend,
+/// so the webview can't trust a folder the user didn't pick. Returns the canonical
+/// folder, or None if the dialog was cancelled.
+#[tauri::command]
+pub async fn pick_workspace_folder(
+    title: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let mut dialog = app_handle.dialog().file();
+    if let Some(title) = title {
+        dialog = dialog.set_title(title);
     }
-
-    // If the path doesn't exist yet (e.g., for create operations), validate the parent
-    let canonical = if path_buf.exists() {
-        fs::canonicalize(&path_buf)
-            .map_err(|e| format!("Failed to resolve path '{}': {}", path, e))?
-    } else {
-        // For non-existent paths, canonicalize the parent and append the filename
-        if let Some(parent) = path_buf.parent() {
-            if parent.as_os_str().is_empty() || !parent.exists() {
-                // If parent doesn't exist or is empty, just return the original path
-                // This will be validated by the actual file operation
-                path_buf.clone()
-            } else {
-                let canonical_parent = fs::canonicalize(parent)
-                    .map_err(|e| format!("Failed to resolve parent path: {}", e))?;
-                if let Some(filename) = path_buf.file_name() {
-                    canonical_parent.join(filename)
-                } else {
-                    canonical_parent
-                }
-            }
-        } else {
-            path_buf.clone()
-        }
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    dialog.pick_folder(move |folder| {
+        let _ = tx.send(folder);
+    });
+    let Some(folder) = rx.await.map_err(|e| format!("Folder dialog failed: {}", e))? else {
+        return Ok(None);
     };
 
-    // If workspace is provided, ensure the path is within it
-    if let Some(ws) = workspace {
-        let ws_path = PathBuf::from(ws);
-        if ws_path.exists() {
-            let workspace_canonical = fs::canonicalize(&ws_path)
-                .map_err(|e| format!("Failed to resolve workspace '{}': {}", ws, e))?;
-
-            if !canonical.starts_with(&workspace_canonical) {
-                return Err(format!(
-                    "Path '{}' is outside the workspace directory",
-                    path
-                ));
-            }
-        }
+    let path = folder
+        .into_path()
+        .map_err(|e| format!("Invalid folder: {}", e))?;
+    let root = state.workspace.trust(&path)?;
+    Ok(Some(root.to_string_lossy().to_string()))
+}
+
+/// Confine file commands to the workspace at `path` (see `path_guard`). Returns the
+/// canonical root. Opening another workspace clears the undo history, whose
+/// operations belong to the previous one.
+#[tauri::command]
+pub fn open_workspace(path: String, state: State<'_, AppState>) -> Result<String, String> {
+    let previous = state.workspace.root();
+    let root = state.workspace.open(Path::new(&path))?;
+    if previous.as_ref() != Some(&root) {
+        state.file_operations.clear();
+        log::info!("Opened workspace '{}'", root.display());
     }
+    Ok(root.to_string_lossy().to_string())
+}
 
-    Ok(canonical)
+/// Close the workspace; file commands fail until one is opened again
+#[tauri::command]
+pub fn close_workspace(state: State<'_, AppState>) {
+    state.workspace.close();
+    state.file_operations.clear();
 }
 
+/// Content of a file with its version, to pass back as `write_file`'s `expected_version`
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FileNode {
-    pub name: String,
-    pub path: String,
-    #[serde(rename = "isDirectory")]
-    pub is_directory: bool,
-    pub children: Option<Vec<FileNode>>,
+pub struct FileContent {
+    pub content: String,
+    pub version: String,
 }
 
 #[tauri::command]
-pub async fn read_file(path: String, workspace: Option<String>) -> Result<String, String> {
-    let validated_path = validate_path(&path, workspace.as_deref())?;
-    fs::read_to_string(&validated_path)
-        .map_err(|e| format!("Failed to read file '{}': {}", path, e))
+pub async fn read_file(
+    path: String,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<FileContent, String> {
+    let validated_path = state.workspace.resolve(&path, workspace.as_deref())?;
+    let content = fs::read_to_string(&validated_path)
+        .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+    let version = content_version(content.as_bytes());
+    Ok(FileContent { content, version })
 }
 
+/// Write a file atomically (temp file + rename) and return its new version.
+/// With `expected_version` the write fails with `WriteFileError::Conflict` if the file
+/// changed since that version was read.
 #[tauri::command]
-pub async fn write_file(path: String, content: String, workspace: Option<String>) -> Result<(), String> {
-    let validated_path = validate_path(&path, workspace.as_deref())?;
+pub async fn write_file(
+    path: String,
+    content: String,
+    expected_version: Option<String>,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, WriteFileError> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_path = state.workspace.resolve_entry(&path, workspace.as_deref())?;
+
+    if let Some(expected) = expected_version.as_deref() {
+        check_version(&validated_path, expected)?;
+    }
 
     // Ensure parent directory exists
     if let Some(parent) = validated_path.parent() {
@@ -317,13 +354,55 @@ pub async fn write_file(path: String, content: String, workspace: Option<String>
             .map_err(|e| format!("Failed to create parent directory: {}", e))?;
     }
 
-    fs::write(&validated_path, content)
-        .map_err(|e| format!("Failed to write file '{}': {}", path, e))
+    // Tell the watcher first, so it doesn't report this save as an external change
+    let version = content_version(content.as_bytes());
+    state.file_watcher.record_write(&validated_path, &version);
+
+    write_atomic(&validated_path, content.as_bytes())
+        .map_err(|e| format!("Failed to write file '{}': {}", path, e))?;
+
+    // History is best effort - a failed snapshot must not fail the save
+    if let Err(e) = file_history::record_snapshot(&validated_path, Some(&root), &content, SnapshotLabel::Save) {
+        log::warn!("Failed to record history of '{}': {}", path, e);
+    }
+    Ok(version)
 }
 
 #[tauri::command]
-pub async fn list_directory(path: String, recursive: bool, workspace: Option<String>) -> Result<Vec<FileNode>, String> {
-    let validated_path = validate_path(&path, workspace.as_deref())?;
+pub async fn list_directory(
+    path: String,
+    recursive: bool,
+    workspace: Option<String>,
+    options: Option<ListOptions>,
+    state: State<'_, AppState>,
+) -> Result<Vec<FileNode>, String> {
+    let validated_path = validated_directory(&state, &path, workspace.as_deref())?;
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let options = options.unwrap_or_default();
+
+    if recursive {
+        list_tree(&validated_path, &root, &options)
+    } else {
+        Ok(list_page(&validated_path, &root, &options)?.entries)
+    }
+}
+
+/// List one page of a directory, with `hasChildren` on directories for lazy expansion
+#[tauri::command]
+pub async fn list_directory_page(
+    path: String,
+    options: Option<ListOptions>,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<DirectoryPage, String> {
+    let validated_path = validated_directory(&state, &path, workspace.as_deref())?;
+    let root = state.workspace.root_for(workspace.as_deref())?;
+
+    list_page(&validated_path, &root, &options.unwrap_or_default())
+}
+
+fn validated_directory(state: &AppState, path: &str, workspace: Option<&str>) -> Result<PathBuf, String> {
+    let validated_path = state.workspace.resolve(path, workspace)?;
 
     if !validated_path.exists() {
         return Err(format!("Path does not exist: {}", path));
@@ -333,77 +412,92 @@ pub async fn list_directory(path: String, recursive: bool, workspace: Option<Str
         return Err(format!("Path is not a directory: {}", path));
     }
 
-    list_directory_impl(&validated_path, recursive, 0)
+    Ok(validated_path)
 }
 
-fn list_directory_impl(path: &Path, recursive: bool, depth: usize) -> Result<Vec<FileNode>, String> {
-    // Prevent infinite recursion from symlinks or deeply nested directories
-    if depth > MAX_DIRECTORY_DEPTH {
-        return Err(format!(
-            "Maximum directory depth ({}) exceeded at '{}'",
-            MAX_DIRECTORY_DEPTH,
-            path.display()
-        ));
+/// Search the files under `path` for `query`. Matches are streamed file by file as
+/// `workspace-search-match` events tagged with `searchId`; the summary is returned
+/// once the search finishes or is cancelled.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn search_workspace(
+    path: String,
+    query: SearchQuery,
+    searchId: String,
+    workspace: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SearchSummary, String> {
+    let root = state.workspace.resolve(&path, workspace.as_deref())?;
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
     }
 
-    let entries = fs::read_dir(path)
-        .map_err(|e| format!("Failed to read directory '{}': {}", path.display(), e))?;
-
-    let mut nodes = Vec::new();
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let entry_path = entry.path();
-        let metadata = entry.metadata()
-            .map_err(|e| format!("Failed to read metadata: {}", e))?;
-
-        let name = entry.file_name()
-            .to_string_lossy()
-            .to_string();
-
-        // Skip hidden files (starting with .)
-        if name.starts_with('.') {
-            continue;
-        }
-
-        let is_directory = metadata.is_dir();
-
-        // Skip symlinks to prevent infinite loops
-        if metadata.file_type().is_symlink() {
-            continue;
-        }
+    let cancel = state.searches.start(&searchId);
+    let search_cancel = cancel.clone();
+    let search_id = searchId.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        workspace_search::search_workspace(&root, &query, &search_cancel, |matches| {
+            let event = SearchEvent {
+                search_id: search_id.clone(),
+                payload: matches,
+            };
+            if let Err(e) = app_handle.emit("workspace-search-match", event) {
+                log::error!("Failed to emit search matches for {}: {}", search_id, e);
+            }
+        })
+    })
+    .await;
+    // Unregister before propagating a failed task, so the cancel flag doesn't linger
+    state.searches.finish(&searchId, &cancel);
+    result.map_err(|e| format!("Search task failed: {}", e))?
+}
 
-        let path_str = entry_path.to_string_lossy().to_string();
+/// Cancel a running `search_workspace`. Returns false if it already finished.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn cancel_workspace_search(searchId: String, state: State<'_, AppState>) -> bool {
+    state.searches.cancel(&searchId)
+}
 
-        let children = if is_directory && recursive {
-            Some(list_directory_impl(&entry_path, recursive, depth + 1)?)
-        } else {
-            None
-        };
-
-        nodes.push(FileNode {
-            name,
-            path: path_str,
-            is_directory,
-            children,
-        });
+/// Replace the matches of `query` under `path` with `replacement`.
+/// With `preview` nothing is written. Otherwise `versions` (path -> version, from the
+/// preview) restricts the replacement to the previewed files and refuses any file that
+/// changed since; all files are written or none are.
+#[tauri::command]
+pub async fn replace_in_workspace(
+    path: String,
+    query: SearchQuery,
+    replacement: String,
+    preview: bool,
+    versions: Option<HashMap<String, String>>,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ReplaceResult, String> {
+    let root = state.workspace.resolve(&path, workspace.as_deref())?;
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
     }
 
-    // Sort: directories first, then alphabetically
-    nodes.sort_by(|a, b| {
-        match (a.is_directory, b.is_directory) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+    tokio::task::spawn_blocking(move || {
+        if preview {
+            workspace_search::preview_replace(&root, &query, &replacement)
+        } else {
+            workspace_search::apply_replace(&root, &query, &replacement, &versions.unwrap_or_default())
         }
-    });
-
-    Ok(nodes)
+    })
+    .await
+    .map_err(|e| format!("Replace task failed: {}", e))?
 }
 
 #[tauri::command]
-pub async fn create_file(path: String, is_directory: bool, workspace: Option<String>) -> Result<(), String> {
-    let validated_path = validate_path(&path, workspace.as_deref())?;
+pub async fn create_file(
+    path: String,
+    is_directory: bool,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let validated_path = state.workspace.resolve_entry(&path, workspace.as_deref())?;
 
     if validated_path.exists() {
         return Err(format!("Path already exists: {}", path));
@@ -423,33 +517,40 @@ pub async fn create_file(path: String, is_directory: bool, workspace: Option<Str
             .map_err(|e| format!("Failed to create file '{}': {}", path, e))?;
     }
 
+    state.file_operations.record(FileOperation::Create {
+        path: validated_path,
+        is_directory,
+        trashed: None,
+    });
     Ok(())
 }
 
+/// Move a file or directory to the trash (see `file_operations`); undoable with
+/// `undo_file_operation`
 #[tauri::command]
-pub async fn delete_file(path: String, workspace: Option<String>) -> Result<(), String> {
-    let validated_path = validate_path(&path, workspace.as_deref())?;
+pub async fn delete_file(path: String, workspace: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_path = state.workspace.resolve_entry(&path, workspace.as_deref())?;
 
     if !validated_path.exists() {
         return Err(format!("Path does not exist: {}", path));
     }
 
-    if validated_path.is_dir() {
-        fs::remove_dir_all(&validated_path)
-            .map_err(|e| format!("Failed to delete directory '{}': {}", path, e))?;
-    } else {
-        fs::remove_file(&validated_path)
-            .map_err(|e| format!("Failed to delete file '{}': {}", path, e))?;
-    }
-
-    Ok(())
+    state
+        .file_operations
+        .delete(&validated_path, Some(&root))
 }
 
 #[tauri::command]
-pub async fn rename_file(old_path: String, new_path: String, workspace: Option<String>) -> Result<(), String> {
+pub async fn rename_file(
+    old_path: String,
+    new_path: String,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
     // Validate both paths are within the workspace
-    let validated_old = validate_path(&old_path, workspace.as_deref())?;
-    let validated_new = validate_path(&new_path, workspace.as_deref())?;
+    let validated_old = state.workspace.resolve_entry(&old_path, workspace.as_deref())?;
+    let validated_new = state.workspace.resolve_entry(&new_path, workspace.as_deref())?;
 
     if !validated_old.exists() {
         return Err(format!("Source path does not exist: {}", old_path));
@@ -460,16 +561,233 @@ pub async fn rename_file(old_path: String, new_path: String, workspace: Option<S
     }
 
     fs::rename(&validated_old, &validated_new)
-        .map_err(|e| format!("Failed to rename '{}' to '{}': {}", old_path, new_path, e))
+        .map_err(|e| format!("Failed to rename '{}' to '{}': {}", old_path, new_path, e))?;
+
+    state.file_operations.record(FileOperation::Rename {
+        old_path: validated_old,
+        new_path: validated_new,
+    });
+    Ok(())
+}
+
+/// Emits `file-transfer-progress` events for the transfer `transfer_id`
+fn transfer_reporter<'a>(app_handle: &'a AppHandle, transfer_id: &'a str) -> impl FnMut(&TransferProgress) + 'a {
+    move |progress| {
+        let event = TransferEvent { transfer_id, progress };
+        if let Err(e) = app_handle.emit("file-transfer-progress", event) {
+            log::error!("Failed to emit transfer progress for {}: {}", transfer_id, e);
+        }
+    }
+}
+
+/// Run `file_transfer::transfer` on a blocking thread, as copying or moving large
+/// trees can take a while
+async fn run_transfer(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    mode: TransferMode,
+    on_conflict: ConflictPolicy,
+    root: PathBuf,
+    transfer_id: String,
+    app_handle: AppHandle,
+) -> Result<TransferSummary, String> {
+    tokio::task::spawn_blocking(move || {
+        let state = app_handle.state::<AppState>();
+        file_transfer::transfer(
+            &pairs,
+            mode,
+            on_conflict,
+            &state.file_operations,
+            Some(&root),
+            &mut transfer_reporter(&app_handle, &transfer_id),
+        )
+    })
+    .await
+    .map_err(|e| format!("Transfer task failed: {}", e))?
+}
+
+/// Copy a file or directory to `destination`, its new path. Progress is emitted as
+/// `file-transfer-progress` events tagged with `transfer_id`. Undoable as one
+/// operation with `undo_file_operation`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_path(
+    source: String,
+    destination: String,
+    on_conflict: ConflictPolicy,
+    transfer_id: String,
+    workspace: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<TransferSummary, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_source = state.workspace.resolve(&source, workspace.as_deref())?;
+    let validated_destination = state.workspace.resolve_entry(&destination, workspace.as_deref())?;
+
+    run_transfer(
+        vec![(validated_source, validated_destination)],
+        TransferMode::Copy,
+        on_conflict,
+        root,
+        transfer_id,
+        app_handle,
+    )
+    .await
 }
 
+/// Copy a file or directory next to itself as `name copy.ext` (or `name copy 2.ext`,
+/// ...). Returns the path of the copy.
 #[tauri::command]
-pub async fn file_exists(path: String, workspace: Option<String>) -> Result<bool, String> {
+pub async fn duplicate_path(
+    path: String,
+    transfer_id: String,
+    workspace: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_path = state.workspace.resolve_entry(&path, workspace.as_deref())?;
+    let target = file_transfer::duplicate_target(&validated_path)?;
+
+    let summary = run_transfer(
+        vec![(validated_path, target)],
+        TransferMode::Copy,
+        ConflictPolicy::Rename,
+        root,
+        transfer_id,
+        app_handle,
+    )
+    .await?;
+    summary
+        .targets
+        .first()
+        .map(|target| target.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Failed to duplicate '{}'", path))
+}
+
+/// Move files and directories into the directory `destination`. Entries already
+/// there are left in place. Undoable as one operation with `undo_file_operation`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn move_paths(
+    paths: Vec<String>,
+    destination: String,
+    on_conflict: ConflictPolicy,
+    transfer_id: String,
+    workspace: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<TransferSummary, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_destination = validated_directory(&state, &destination, workspace.as_deref())?;
+
+    let mut pairs = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let validated_path = state.workspace.resolve_entry(path, workspace.as_deref())?;
+        let name = validated_path
+            .file_name()
+            .ok_or_else(|| format!("Cannot move '{}'", path))?;
+        let target = validated_destination.join(name);
+        pairs.push((validated_path, target));
+    }
+
+    run_transfer(
+        pairs,
+        TransferMode::Move,
+        on_conflict,
+        root,
+        transfer_id,
+        app_handle,
+    )
+    .await
+}
+
+/// Undo the last create, rename or delete. Returns the undone operation, or None if
+/// there is nothing to undo.
+#[tauri::command]
+pub fn undo_file_operation(
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<FileOperation>, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    state.file_operations.undo(Some(&root))
+}
+
+/// Redo the last undone operation. Returns it, or None if there is nothing to redo.
+#[tauri::command]
+pub fn redo_file_operation(
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<FileOperation>, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    state.file_operations.redo(Some(&root))
+}
+
+#[tauri::command]
+pub async fn file_exists(path: String, workspace: Option<String>, state: State<'_, AppState>) -> Result<bool, String> {
     // Validate path even for existence check to prevent information disclosure
-    let validated_path = validate_path(&path, workspace.as_deref())?;
+    let validated_path = state.workspace.resolve(&path, workspace.as_deref())?;
     Ok(validated_path.exists())
 }
 
+// File History Commands - For local snapshots of saved files
+
+/// Versions of a file recorded on save, newest first
+#[tauri::command]
+pub async fn list_file_history(
+    path: String,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_path = state.workspace.resolve(&path, workspace.as_deref())?;
+    Ok(file_history::list_history(&validated_path, Some(&root)))
+}
+
+/// Line diff between two versions of a file. A missing id stands for the file's
+/// current content on disk.
+#[tauri::command]
+pub async fn diff_file_history(
+    path: String,
+    from_id: Option<u64>,
+    to_id: Option<u64>,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<DiffLine>, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_path = state.workspace.resolve(&path, workspace.as_deref())?;
+    let read = |id: Option<u64>| match id {
+        Some(id) => file_history::read_snapshot(&validated_path, Some(&root), id),
+        None => fs::read_to_string(&validated_path)
+            .map_err(|e| format!("Failed to read file '{}': {}", path, e)),
+    };
+    Ok(file_history::diff_lines(&read(from_id)?, &read(to_id)?))
+}
+
+/// Restore version `id` of a file. The restore is written like a save (atomically)
+/// and recorded in the history, so it can be undone the same way.
+#[tauri::command]
+pub async fn restore_file_history(
+    path: String,
+    id: u64,
+    workspace: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<FileContent, String> {
+    let root = state.workspace.root_for(workspace.as_deref())?;
+    let validated_path = state.workspace.resolve_entry(&path, workspace.as_deref())?;
+    let content = file_history::read_snapshot(&validated_path, Some(&root), id)?;
+    let version = content_version(content.as_bytes());
+    state.file_watcher.record_write(&validated_path, &version);
+
+    write_atomic(&validated_path, content.as_bytes())
+        .map_err(|e| format!("Failed to restore '{}': {}", path, e))?;
+    if let Err(e) = file_history::record_snapshot(&validated_path, Some(&root), &content, SnapshotLabel::Restore) {
+        log::warn!("Failed to record history of '{}': {}", path, e);
+    }
+    log::info!("Restored '{}' to version {}", path, id);
+
+    Ok(FileContent { content, version })
+}
+
 // UCM MCP Commands - For updating codebase definitions
 
 /// Switch UCM's project/branch context
@@ -479,13 +797,16 @@ pub async fn file_exists(path: String, workspace: Option<String>) -> Result<bool
 pub fn switch_project_branch(
     projectName: String,
     branchName: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let mut mcp_guard = state.mcp_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.ensure_writable()?;
+    let mut mcp_guard = session.mcp_client.lock().unwrap();
 
     // Spawn MCP client if not already running
     if mcp_guard.is_none() {
-        *mcp_guard = Some(MCPClient::spawn()?);
+        *mcp_guard = Some(MCPClient::spawn(&state.locate_ucm()?, session.codebase().as_ref())?);
     }
 
     let mcp_client = mcp_guard
@@ -501,13 +822,28 @@ pub fn ucm_update(
     code: String,
     projectName: String,
     branchName: String,
+    filePath: Option<String>,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<UpdateResult, String> {
-    let mut mcp_guard = state.mcp_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.ensure_writable()?;
+
+    // Keep the code being sent to UCM in the file's history, labelled as such
+    if let Some(file_path) = filePath.as_deref() {
+        let root = state.workspace.root();
+        let snapshot = state.workspace.resolve(file_path, None).and_then(|path| {
+            file_history::record_snapshot(&path, root.as_deref(), &code, SnapshotLabel::BeforeUpdate)
+        });
+        if let Err(e) = snapshot {
+            log::warn!("Failed to record history before update of '{}': {}", file_path, e);
+        }
+    }
+    let mut mcp_guard = session.mcp_client.lock().unwrap();
 
     // Spawn MCP client if not already running
     if mcp_guard.is_none() {
-        *mcp_guard = Some(MCPClient::spawn()?);
+        *mcp_guard = Some(MCPClient::spawn(&state.locate_ucm()?, session.codebase().as_ref())?);
     }
 
     let mcp_client = mcp_guard
@@ -524,15 +860,18 @@ pub fn ucm_typecheck(
     code: String,
     projectName: String,
     branchName: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<TypecheckResult, String> {
     let start_time = std::time::Instant::now();
-    let mut mcp_guard = state.mcp_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.ensure_writable()?;
+    let mut mcp_guard = session.mcp_client.lock().unwrap();
 
     // Spawn MCP client if not already running
     let spawned = if mcp_guard.is_none() {
         log::info!("MCP client not initialized, spawning new instance...");
-        *mcp_guard = Some(MCPClient::spawn()?);
+        *mcp_guard = Some(MCPClient::spawn(&state.locate_ucm()?, session.codebase().as_ref())?);
         log::info!("MCP client spawned in {:?}", start_time.elapsed());
         true
     } else {
@@ -562,13 +901,16 @@ pub fn ucm_run_tests(
     projectName: String,
     branchName: String,
     subnamespace: Option<String>,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<RunTestsResult, String> {
-    let mut mcp_guard = state.mcp_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.ensure_writable()?;
+    let mut mcp_guard = session.mcp_client.lock().unwrap();
 
     // Spawn MCP client if not already running
     if mcp_guard.is_none() {
-        *mcp_guard = Some(MCPClient::spawn()?);
+        *mcp_guard = Some(MCPClient::spawn(&state.locate_ucm()?, session.codebase().as_ref())?);
     }
 
     let mcp_client = mcp_guard
@@ -586,13 +928,16 @@ pub fn ucm_run(
     projectName: String,
     branchName: String,
     args: Vec<String>,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<RunFunctionResult, String> {
-    let mut mcp_guard = state.mcp_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.ensure_writable()?;
+    let mut mcp_guard = session.mcp_client.lock().unwrap();
 
     // Spawn MCP client if not already running
     if mcp_guard.is_none() {
-        *mcp_guard = Some(MCPClient::spawn()?);
+        *mcp_guard = Some(MCPClient::spawn(&state.locate_ucm()?, session.codebase().as_ref())?);
     }
 
     let mcp_client = mcp_guard
@@ -609,13 +954,16 @@ pub fn view_definitions(
     projectName: String,
     branchName: String,
     names: Vec<String>,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let mut mcp_guard = state.mcp_client.lock().unwrap();
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.ensure_writable()?;
+    let mut mcp_guard = session.mcp_client.lock().unwrap();
 
     // Spawn MCP client if not already running
     if mcp_guard.is_none() {
-        *mcp_guard = Some(MCPClient::spawn()?);
+        *mcp_guard = Some(MCPClient::spawn(&state.locate_ucm()?, session.codebase().as_ref())?);
     }
 
     let mcp_client = mcp_guard
@@ -741,61 +1089,162 @@ async fn read_lsp_message(stream: &mut TcpStream) -> Result<String, anyhow::Erro
     Ok(String::from_utf8(content)?)
 }
 
+// Transcript Commands - For running `.md` transcripts as tests
+
+/// Run a Unison transcript and report which stanzas passed
+/// Output is streamed as `transcript-output` events while UCM runs
+///
+/// # Arguments
+/// * `path` - The `.md` transcript
+/// * `fork` - Run with `transcript.fork` on a copy of the session's codebase instead
+///   of a fresh codebase
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn run_transcript(
+    path: String,
+    fork: Option<bool>,
+    sessionId: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<TranscriptResult, String> {
+    let transcript = state.workspace.resolve(&path, None)?;
+    if transcript.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err(format!("Not a transcript (expected a .md file): {}", path));
+    }
+
+    let session_id = resolve_session_id(sessionId);
+    let codebase = state.sessions.get(&session_id)?.codebase();
+    let ucm_path = state.locate_ucm()?;
+
+    crate::transcript::run_transcript(
+        &app_handle,
+        &session_id,
+        &ucm_path,
+        &transcript,
+        fork.unwrap_or(false),
+        codebase.as_ref(),
+    )
+    .await
+}
+
 // UCM PTY Commands - For integrated terminal
 
+/// Default time `ucm_pty_exec` waits for a command to finish
+const DEFAULT_EXEC_TIMEOUT_MS: u64 = 60_000;
+
 /// Spawn UCM with async PTY for interactive terminal
 ///
 /// # Arguments
 /// * `cwd` - Optional working directory for UCM (for file loading via `load` command)
+/// * `sessionId` - Session to spawn UCM for (defaults to the default session)
+/// * `codebase` - Codebase to open (defaults to UCM's `~/.unison`). Spawning a
+///   running session with a different codebase restarts it on the new codebase.
 ///
 /// # Returns
 /// The allocated service ports (API and LSP)
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn ucm_pty_spawn(
     cwd: Option<String>,
+    sessionId: Option<String>,
+    codebase: Option<UCMCodebase>,
     app_handle: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<ServicePorts, String> {
-    let mut pty_guard = state.ucm_pty.lock().await;
+    let session_id = resolve_session_id(sessionId);
+    let session = state.sessions.get_or_create(&session_id);
+    let codebase = codebase
+        .map(|codebase| codebase.resolve(cwd.as_deref()))
+        .transpose()?;
+    let mut pty_guard = session.ucm_pty.lock().await;
 
     // If already running, check if it's still actually running
     // (UCM might have crashed due to file lock or other errors)
-    if let Some(ref manager) = *pty_guard {
-        if manager.is_running() {
-            let ports = ServicePorts {
-                api_port: *state.api_port.lock().unwrap(),
-                lsp_port: *state.lsp_port.lock().unwrap(),
-                lsp_proxy_port: *state.lsp_proxy_port.lock().unwrap(),
-            };
-            return Ok(ports);
+    if let Some(manager) = pty_guard.clone() {
+        if manager.is_running() && session.codebase() == codebase {
+            return Ok(service_ports(&state, session.ports()));
+        } else if manager.is_running() {
+            log::info!("Switching UCM session {} to codebase {:?}", session_id, codebase);
+            manager.shutdown().await?;
+            *pty_guard = None;
         } else {
             // UCM exited - clear the old manager so we can try again
-            log::info!("Previous UCM PTY is no longer running, clearing state for respawn");
+            log::info!("Previous UCM PTY of session {} is no longer running, clearing state for respawn", session_id);
             *pty_guard = None;
         }
     }
 
+    // The old proxy points at the old UCM's LSP port
+    session.stop_lsp_proxy();
+
+    // Allocate ports while no other session can, so two sessions never share a port
+    let spawn_guard = state.sessions.lock_spawn().await;
+    let mut reserved_ports = state.sessions.reserved_ports(&session_id);
+
+    let ucm_path = state.locate_ucm()?;
+
+    // The MCP client must open the same codebase as the PTY (and its HTTP API)
+    if session.codebase() != codebase {
+        session.mcp_client.lock().unwrap().take();
+        *session.codebase.lock().unwrap() = codebase.clone();
+    }
+
     // Async spawn - no blocking!
-    let (manager, ucm_ports) = UCMPtyManager::spawn(app_handle.clone(), cwd).await?;
-    *pty_guard = Some(manager);
+    let (manager, ucm_ports) = UCMPtyManager::spawn(
+        app_handle.clone(),
+        session_id.clone(),
+        &ucm_path,
+        codebase.as_ref(),
+        cwd,
+        &reserved_ports,
+    )
+    .await?;
+    let ucm_context = manager.context_handle();
+    *pty_guard = Some(Arc::new(manager));
 
     // Find available port for LSP WebSocket proxy (starting at 5758)
-    let lsp_proxy_port = find_available_port(5758)
+    // UCM may not have bound its ports yet, so never hand them out again
+    reserved_ports.extend([ucm_ports.api_port, ucm_ports.lsp_port]);
+    let lsp_proxy_port = find_available_port_excluding(5758, &reserved_ports)
         .ok_or("Could not find available port for LSP WebSocket proxy")?;
 
-    // Store the allocated ports in AppState
-    *state.api_port.lock().unwrap() = ucm_ports.api_port;
-    *state.lsp_port.lock().unwrap() = ucm_ports.lsp_port;
-    *state.lsp_proxy_port.lock().unwrap() = lsp_proxy_port;
+    // Store the allocated ports in the session
+    *session.ports.lock().unwrap() = Some(SessionPorts {
+        api_port: ucm_ports.api_port,
+        lsp_port: ucm_ports.lsp_port,
+        lsp_proxy_port,
+    });
+    drop(spawn_guard);
+
+    // Diagnostics from a previous UCM of this session are stale
+    session.problems.clear();
+    session.problems.set_app_handle(app_handle.clone(), &session_id);
 
     // Update the UCM API client to use the new port
-    let mut client_guard = state.ucm_client.lock().unwrap();
-    *client_guard = Some(UCMApiClient::new("127.0.0.1", ucm_ports.api_port));
+    *session.ucm_client.lock().unwrap() = Some(UCMApiClient::new("127.0.0.1", ucm_ports.api_port));
+    *session.mode.lock().unwrap() = SessionMode::Full;
 
     // Start LSP WebSocket proxy now that we know the LSP port
     let lsp_port = ucm_ports.lsp_port;
-    tauri::async_runtime::spawn(async move {
-        let proxy = Arc::new(LspProxy::new(lsp_proxy_port, "127.0.0.1".to_string(), lsp_port));
+    let lsp_proxy_token = state.lsp_proxy_token.clone();
+    let lsp_inspector = session.lsp_inspector.clone();
+    let problems = session.problems.clone();
+    let enricher = CodebaseEnricher::new(
+        UCMApiClient::new("127.0.0.1", ucm_ports.api_port),
+        ucm_context,
+    );
+    let proxy_task = tauri::async_runtime::spawn(async move {
+        let proxy = Arc::new(
+            LspProxy::new(
+                lsp_proxy_port,
+                "127.0.0.1".to_string(),
+                lsp_port,
+                lsp_proxy_token,
+                lsp_inspector,
+            )
+            .with_middleware(Arc::new(enricher))
+            .with_notification_observer(problems),
+        );
         log::info!(
             "LSP WebSocket proxy starting on port {} -> UCM LSP port {}",
             lsp_proxy_port,
@@ -805,31 +1254,35 @@ pub async fn ucm_pty_spawn(
             log::error!("LSP proxy server error: {}", e);
         }
     });
+    *session.lsp_proxy_task.lock().unwrap() = Some(proxy_task);
 
     log::info!(
-        "UCM PTY spawned successfully on ports - API: {}, LSP: {}, LSP Proxy: {}",
+        "UCM PTY spawned for session {} on ports - API: {}, LSP: {}, LSP Proxy: {}",
+        session_id,
         ucm_ports.api_port,
         ucm_ports.lsp_port,
         lsp_proxy_port
     );
 
     // Return all allocated ports
-    Ok(ServicePorts {
-        api_port: ucm_ports.api_port,
-        lsp_port: ucm_ports.lsp_port,
-        lsp_proxy_port,
-    })
+    Ok(service_ports(&state, session.ports()))
 }
 
 /// Write data to UCM PTY (user input from terminal) - async, non-blocking
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn ucm_pty_write(
     data: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let pty_guard = state.ucm_pty.lock().await;
-    let manager = pty_guard
-        .as_ref()
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    // Release the PTY lock before writing: input waits behind a running exec
+    let manager = session
+        .ucm_pty
+        .lock()
+        .await
+        .clone()
         .ok_or("UCM PTY not spawned")?;
 
     manager.write(data.as_bytes()).await
@@ -837,12 +1290,15 @@ pub async fn ucm_pty_write(
 
 /// Resize UCM PTY (when terminal is resized) - async, non-blocking
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn ucm_pty_resize(
     rows: u16,
     cols: u16,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let pty_guard = state.ucm_pty.lock().await;
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let pty_guard = session.ucm_pty.lock().await;
     let manager = pty_guard
         .as_ref()
         .ok_or("UCM PTY not spawned")?;
@@ -850,12 +1306,59 @@ pub async fn ucm_pty_resize(
     manager.resize(rows, cols).await
 }
 
+/// Run a UCM command in the PTY and wait for UCM to return to its prompt
+/// The command shows up in the terminal like typed input; user typing is queued behind it
+///
+/// # Arguments
+/// * `command` - A single UCM command line, e.g. `move.term foo bar`
+/// * `timeoutMs` - How long to wait for the next prompt (default 60 seconds)
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn ucm_pty_exec(
+    command: String,
+    timeoutMs: Option<u64>,
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<UCMExecResult, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    // Release the PTY lock while the command runs, so it can still be interrupted or killed
+    let manager = session
+        .ucm_pty
+        .lock()
+        .await
+        .clone()
+        .ok_or("UCM PTY not spawned")?;
+
+    let timeout = Duration::from_millis(timeoutMs.unwrap_or(DEFAULT_EXEC_TIMEOUT_MS));
+    manager.exec(&command, timeout).await
+}
+
+/// Attach a (re)mounted terminal to a running UCM
+/// Returns the buffered output history and the current PTY size
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn ucm_pty_attach(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<PtyAttachSnapshot, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let pty_guard = session.ucm_pty.lock().await;
+    let manager = pty_guard
+        .as_ref()
+        .ok_or("UCM PTY not spawned")?;
+
+    Ok(manager.attach())
+}
+
 /// Get current UCM context (project/branch) detected from PTY output
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn ucm_pty_get_context(
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<UCMContext, String> {
-    let pty_guard = state.ucm_pty.lock().await;
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let pty_guard = session.ucm_pty.lock().await;
     let manager = pty_guard
         .as_ref()
         .ok_or("UCM PTY not spawned")?;
@@ -863,15 +1366,35 @@ pub async fn ucm_pty_get_context(
     Ok(manager.get_context())
 }
 
+/// Get UCM's current prompt state (idle, busy or waiting at a sub-prompt)
+/// Changes are also emitted as `ucm-prompt-state` events
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn ucm_pty_get_prompt_state(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<UCMPromptState, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let pty_guard = session.ucm_pty.lock().await;
+    let manager = pty_guard
+        .as_ref()
+        .ok_or("UCM PTY not spawned")?;
+
+    Ok(manager.prompt_state())
+}
+
 /// Send switch command to UCM via PTY - async
 /// This switches UCM's project/branch context in the integrated terminal
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn ucm_pty_switch_context(
     project: String,
     branch: String,
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let pty_guard = state.ucm_pty.lock().await;
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let pty_guard = session.ucm_pty.lock().await;
     let manager = pty_guard
         .as_ref()
         .ok_or("UCM PTY not spawned")?;
@@ -879,42 +1402,310 @@ pub async fn ucm_pty_switch_context(
     manager.switch_context(&project, &branch).await
 }
 
-/// Kill the UCM PTY process
-/// This should be called before spawning a new UCM PTY with a different working directory
+/// Kill the UCM PTY process of a session
+/// This should be called before spawning a new UCM PTY with a different working directory.
+/// UCM is asked to `exit` first, then terminated with SIGTERM/SIGKILL if needed. The PTY
+/// lock is held until the process is gone, so a respawn can't race the old codebase lock.
 #[tauri::command]
+#[allow(non_snake_case)]
 pub async fn ucm_pty_kill(
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let mut pty_guard = state.ucm_pty.lock().await;
+    let Ok(session) = state.sessions.get(&resolve_session_id(sessionId)) else {
+        return Ok(());
+    };
+    let mut pty_guard = session.ucm_pty.lock().await;
 
     if let Some(manager) = pty_guard.take() {
-        log::info!("Killing UCM PTY");
-        manager.stop();
-        // The manager will be dropped here, which also calls stop()
+        log::info!("Killing UCM PTY of session {}", session.id);
+        session.stop_lsp_proxy();
+        manager.shutdown().await?;
+    }
+
+    Ok(())
+}
+
+/// Send Ctrl-C to UCM to interrupt the running command
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn ucm_pty_interrupt(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let pty_guard = session.ucm_pty.lock().await;
+    let manager = pty_guard
+        .as_ref()
+        .ok_or("UCM PTY not spawned")?;
+
+    manager.interrupt().await
+}
+
+/// List all UCM sessions with their state and ports
+#[tauri::command]
+pub async fn list_ucm_sessions(
+    state: State<'_, AppState>,
+) -> Result<Vec<UCMSessionInfo>, String> {
+    let mut infos = Vec::new();
+    for session in state.sessions.sessions() {
+        infos.push(session.info().await);
     }
+    Ok(infos)
+}
 
+/// Close a UCM session: stop its UCM, LSP proxy and MCP client and forget it
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn close_ucm_session(
+    sessionId: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(session) = state.sessions.remove(&sessionId) {
+        log::info!("Closing UCM session {}", sessionId);
+        session.shutdown().await?;
+    }
     Ok(())
 }
 
+// Codebase Lock Commands - For recovering when another UCM holds the codebase
+
+fn session_lock_root(session: &UCMSession) -> Result<PathBuf, String> {
+    codebase_root(session.codebase().as_ref())
+        .ok_or_else(|| "Could not determine the codebase directory".to_string())
+}
+
+/// Find the process holding the lock of the session's codebase
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_codebase_lock_holder(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<LockHolder>, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    Ok(find_lock_holder(&session_lock_root(&session)?))
+}
+
+/// Kill the lock holder `pid`, if it is a UCM orphaned by a previous editor session.
+/// The frontend respawns UCM afterwards.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn kill_codebase_lock_holder(
+    pid: u32,
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    kill_lock_holder(&session_lock_root(&session)?, pid).await
+}
+
+/// Use the HTTP API of the UCM holding the lock (port taken from its `--port`
+/// argument) instead of spawning our own. The session becomes read-only.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn connect_to_lock_holder(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<SessionMode, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    let holder = find_lock_holder(&session_lock_root(&session)?)
+        .ok_or("No process holding the codebase lock was found")?;
+    let api_port = holder.api_port.ok_or_else(|| {
+        format!(
+            "UCM {} was not started with --port, so its API can't be reached ({})",
+            holder.pid, holder.command_line
+        )
+    })?;
+
+    let client = UCMApiClient::new("127.0.0.1", api_port);
+    if !client.check_connection().await.unwrap_or(false) {
+        return Err(format!("UCM {} is not answering on port {}", holder.pid, api_port));
+    }
+
+    log::info!("Connecting session {} read-only to UCM {} on port {}", session.id, holder.pid, api_port);
+    session.stop_lsp_proxy();
+    session.mcp_client.lock().unwrap().take();
+    *session.ports.lock().unwrap() = None;
+    *session.ucm_client.lock().unwrap() = Some(client);
+    let mode = SessionMode::ReadOnly {
+        holder_pid: holder.pid,
+        api_port,
+    };
+    *session.mode.lock().unwrap() = mode;
+    Ok(mode)
+}
+
+/// Open the session without UCM: file editing works, codebase features report
+/// the lock instead of failing
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn open_degraded_session(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<SessionMode, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    log::info!("Opening session {} in degraded mode", session.id);
+    session.stop_lsp_proxy();
+    session.mcp_client.lock().unwrap().take();
+    *session.ports.lock().unwrap() = None;
+    *session.ucm_client.lock().unwrap() = None;
+    *session.mode.lock().unwrap() = SessionMode::Degraded;
+    Ok(SessionMode::Degraded)
+}
+
+// UCM Binary Discovery - Configurable path and version check
+
+/// Configure the `ucm` binary to use (from the editor settings).
+/// `None` or an empty path restores auto-detection. Takes effect on the next spawn.
+#[tauri::command]
+pub fn set_ucm_path(path: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(ref p) = path {
+        locate_ucm(Some(p))?;
+    }
+    log::info!("UCM path set to {:?}", path);
+    *state.ucm_path.lock().unwrap() = path;
+    Ok(())
+}
+
+/// Locate UCM and report its path, version and whether that version is supported.
+/// `ucmPath` checks a candidate path without configuring it.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn get_ucm_info(
+    ucmPath: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<UCMInfo, String> {
+    let configured = ucmPath.or_else(|| state.ucm_path.lock().unwrap().clone());
+    Ok(crate::ucm_locator::get_ucm_info(configured.as_deref()).await)
+}
+
+// Shell PTY Commands - For the general terminal panel
+
+/// Spawn a shell PTY session, or return the running one with this id
+///
+/// # Arguments
+/// * `sessionId` - Terminal session id chosen by the frontend
+/// * `command` - Program to run (defaults to the user's login shell)
+/// * `args` - Arguments for `command`
+/// * `cwd` - Working directory inside the workspace (defaults to the workspace root,
+///   or the home directory while no workspace is open)
+/// * `rows`, `cols` - Initial terminal size
+#[tauri::command]
+#[allow(non_snake_case)]
+#[allow(clippy::too_many_arguments)]
+pub fn pty_spawn(
+    sessionId: String,
+    command: Option<String>,
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    rows: Option<u16>,
+    cols: Option<u16>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<PtySessionInfo, String> {
+    let cwd = match cwd {
+        Some(cwd) => Some(state.workspace.resolve(&cwd, None)?),
+        None => state.workspace.root(),
+    }
+    .map(|cwd| cwd.to_string_lossy().to_string());
+    state.pty_sessions.spawn(
+        app_handle,
+        &sessionId,
+        command,
+        args.unwrap_or_default(),
+        cwd,
+        rows.unwrap_or(INITIAL_ROWS),
+        cols.unwrap_or(INITIAL_COLS),
+    )
+}
+
+/// Write user input to a shell PTY
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn pty_write(
+    sessionId: String,
+    data: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.pty_sessions.write(&sessionId, data.as_bytes()).await
+}
+
+/// Resize a shell PTY
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn pty_resize(
+    sessionId: String,
+    rows: u16,
+    cols: u16,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.pty_sessions.resize(&sessionId, rows, cols).await
+}
+
+/// Buffered output of a shell PTY, for a terminal (re)attaching to it
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn pty_attach(sessionId: String, state: State<'_, AppState>) -> Result<PtyAttachSnapshot, String> {
+    state.pty_sessions.attach(&sessionId)
+}
+
+/// Stop a shell PTY session (SIGHUP, escalating to SIGTERM/SIGKILL)
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn pty_kill(sessionId: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.pty_sessions.kill(&sessionId).await
+}
+
+/// List shell PTY sessions
+#[tauri::command]
+pub fn pty_list(state: State<'_, AppState>) -> Vec<PtySessionInfo> {
+    state.pty_sessions.list()
+}
+
 /// Response struct for get_service_ports command
 #[derive(Serialize)]
 pub struct ServicePorts {
+    #[serde(rename = "apiPort")]
     pub api_port: u16,
+    #[serde(rename = "lspPort")]
     pub lsp_port: u16,
+    #[serde(rename = "lspProxyPort")]
     pub lsp_proxy_port: u16,
+    /// Token the WebSocket client must send to the LSP proxy (`?token=...`)
+    #[serde(rename = "lspProxyToken")]
+    pub lsp_proxy_token: String,
 }
 
-/// Get the current service ports (API, LSP, LSP proxy)
-/// These are dynamically allocated when UCM is spawned
+/// Service ports of a session, falling back to UCM's default ports before it was spawned
+fn service_ports(state: &AppState, ports: Option<SessionPorts>) -> ServicePorts {
+    let ports = ports.unwrap_or(SessionPorts {
+        api_port: 5858,
+        lsp_port: 5757,
+        lsp_proxy_port: 5758,
+    });
+    ServicePorts {
+        api_port: ports.api_port,
+        lsp_port: ports.lsp_port,
+        lsp_proxy_port: ports.lsp_proxy_port,
+        lsp_proxy_token: state.lsp_proxy_token.clone(),
+    }
+}
+
+/// Get the current service ports (API, LSP, LSP proxy) of a session and the LSP proxy token
+/// Ports are dynamically allocated when UCM is spawned
 #[tauri::command]
+#[allow(non_snake_case)]
 pub fn get_service_ports(
+    sessionId: Option<String>,
     state: State<'_, AppState>,
 ) -> ServicePorts {
-    ServicePorts {
-        api_port: *state.api_port.lock().unwrap(),
-        lsp_port: *state.lsp_port.lock().unwrap(),
-        lsp_proxy_port: *state.lsp_proxy_port.lock().unwrap(),
-    }
+    let ports = state
+        .sessions
+        .get(&resolve_session_id(sessionId))
+        .ok()
+        .and_then(|session| session.ports());
+    service_ports(&state, ports)
 }
 
 // File Watcher Commands - For detecting external file changes
@@ -928,13 +1719,23 @@ pub fn init_file_watcher(
     state.file_watcher.initialize(app_handle)
 }
 
+/// Set the watcher backend, poll interval and content comparison
+#[tauri::command]
+pub fn configure_file_watcher(
+    config: WatcherConfig,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.file_watcher.configure(config)
+}
+
 /// Start watching a file for external changes
 #[tauri::command]
 pub fn watch_file(
     path: String,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    state.file_watcher.watch_file(&path)
+    let validated_path = state.workspace.resolve(&path, None)?;
+    state.file_watcher.watch_file(&validated_path)
 }
 
 /// Stop watching a file
@@ -943,5 +1744,141 @@ pub fn unwatch_file(
     path: String,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    state.file_watcher.unwatch_file(&path)
+    // Watched files are keyed by their canonical path; one resolving elsewhere now
+    // (e.g. after the workspace closed) can't be watched anymore
+    match state.workspace.resolve(&path, None) {
+        Ok(validated_path) => state.file_watcher.unwatch_file(&validated_path),
+        Err(_) => state.file_watcher.unwatch_file(Path::new(&path)),
+    }
+}
+
+/// Watch the workspace tree; changes arrive as debounced `workspace-changed` events
+#[tauri::command]
+pub fn watch_workspace(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let validated_path = state.workspace.resolve(&path, None)?;
+    state.file_watcher.watch_workspace(&validated_path)
+}
+
+/// Stop watching the workspace tree
+#[tauri::command]
+pub fn unwatch_workspace(state: State<'_, AppState>) {
+    state.file_watcher.unwatch_workspace()
+}
+
+// LSP Inspector Commands - For debugging LSP proxy traffic
+
+/// Start capturing a session's LSP traffic into the inspector ring buffer
+/// Each captured message is also emitted as an `lsp-traffic` event
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn lsp_inspector_start(
+    capacity: Option<usize>,
+    sessionId: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let session_id = resolve_session_id(sessionId);
+    let session = state.sessions.get(&session_id)?;
+    session.lsp_inspector.set_app_handle(app_handle, &session_id);
+    session.lsp_inspector.start(capacity);
+    Ok(())
+}
+
+/// Stop capturing LSP traffic (captured entries are kept)
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn lsp_inspector_stop(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.lsp_inspector.stop();
+    Ok(())
+}
+
+/// Get all captured LSP traffic, oldest first
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn lsp_inspector_get_entries(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<LspTrafficEntry>, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    Ok(session.lsp_inspector.entries())
+}
+
+/// Clear captured LSP traffic
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn lsp_inspector_clear(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.lsp_inspector.clear();
+    Ok(())
+}
+
+/// Export captured LSP traffic to a `.jsonl` file inside the workspace
+/// Returns the number of entries written
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn lsp_inspector_export(
+    path: String,
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let path = state.workspace.resolve_entry(&path, None)?;
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    session.lsp_inspector.export_jsonl(&path)
+}
+
+// Problems Commands - Workspace-wide diagnostics
+
+/// Get diagnostics for every file a session's UCM has reported problems for
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_problems(
+    sessionId: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<FileProblems>, String> {
+    let session = state.sessions.get(&resolve_session_id(sessionId))?;
+    Ok(session.problems.get_problems())
+}
+
+/// Open every `.u` file in the workspace against the session's UCM LSP in the background
+/// Diagnostics arrive as `problems-changed` events while the scan runs
+#[tauri::command]
+#[allow(non_snake_case)]
+pub async fn scan_workspace_problems(
+    workspace: String,
+    sessionId: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let workspace_path = state.workspace.resolve(&workspace, None)?;
+    if !workspace_path.is_dir() {
+        return Err(format!("Workspace is not a directory: {}", workspace));
+    }
+
+    let session_id = resolve_session_id(sessionId);
+    let session = state.sessions.get(&session_id)?;
+    if session.ucm_pty.lock().await.is_none() {
+        return Err("UCM PTY not spawned".to_string());
+    }
+
+    let lsp_port = session.ports().ok_or("UCM ports not allocated")?.lsp_port;
+    let problems = session.problems.clone();
+    problems.set_app_handle(app_handle, &session_id);
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = problems.scan_workspace(workspace_path, lsp_port).await {
+            log::error!("Workspace problems scan failed: {}", e);
+        }
+    });
+
+    Ok(())
 }
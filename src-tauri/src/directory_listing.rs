@@ -0,0 +1,304 @@
+//! Directory Listing - File explorer listings of workspace directories
+//!
+//! This module provides:
+//! - `list_page`: one level of a directory with `hasChildren` flags, so the explorer
+//!   can expand lazily, paged with `offset`/`limit` for huge directories
+//! - `list_tree`: the recursive listing, which stops descending below
+//!   `MAX_DIRECTORY_DEPTH` (leaving `children` unset and `hasChildren` set) instead of
+//!   failing
+//! - `ListOptions`: the hidden-file policy and whether `.gitignore`/`.ignore` files are
+//!   honored (see `ignore_rules`)
+//!
+//! Entries are sorted directories first, then by name. Symlinks are skipped, so
+//! listings can't loop.
+
+use crate::ignore_rules::IgnoreRules;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Maximum recursion depth of `list_tree`, below which directories are left unexpanded
+const MAX_DIRECTORY_DEPTH: usize = 50;
+
+/// Which dot-entries are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HiddenPolicy {
+    /// No hidden files or directories
+    #[default]
+    Hide,
+    /// Hidden files (`.gitignore`, `.env`, ...) but no hidden directories
+    ShowFiles,
+    /// Everything but `.git`
+    Show,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListOptions {
+    #[serde(default)]
+    pub hidden: HiddenPolicy,
+    /// Leave out entries matched by `.gitignore`/`.ignore` files
+    #[serde(rename = "respectIgnore", default = "default_respect_ignore")]
+    pub respect_ignore: bool,
+    /// First entry of the page
+    #[serde(default)]
+    pub offset: usize,
+    /// Entries per page (None = all)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+fn default_respect_ignore() -> bool {
+    true
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            hidden: HiddenPolicy::Hide,
+            respect_ignore: default_respect_ignore(),
+            offset: 0,
+            limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileNode {
+    pub name: String,
+    pub path: String,
+    #[serde(rename = "isDirectory")]
+    pub is_directory: bool,
+    pub children: Option<Vec<FileNode>>,
+    /// Size in bytes (files only)
+    pub size: Option<u64>,
+    /// Modification time (milliseconds since epoch)
+    pub modified: Option<u64>,
+    /// Whether a directory has listed entries (None for files)
+    #[serde(rename = "hasChildren")]
+    pub has_children: Option<bool>,
+}
+
+/// One page of a directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryPage {
+    pub entries: Vec<FileNode>,
+    /// Listed entries in the whole directory
+    pub total: usize,
+    /// Offset of the next page, None on the last page
+    #[serde(rename = "nextOffset")]
+    pub next_offset: Option<usize>,
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+struct Listing<'a> {
+    root: &'a Path,
+    options: &'a ListOptions,
+}
+
+impl Listing<'_> {
+    /// Ignore rules that apply inside `dir`
+    fn rules_for(&self, dir: &Path) -> IgnoreRules {
+        if !self.options.respect_ignore {
+            return IgnoreRules::default();
+        }
+        let mut rules = IgnoreRules::load(self.root);
+        rules.load_nested(self.root, dir);
+        rules
+    }
+
+    /// Rules inside `dir`, given the `rules` of its parent
+    fn child_rules(&self, rules: &IgnoreRules, dir: &Path) -> IgnoreRules {
+        let mut rules = rules.clone();
+        if self.options.respect_ignore {
+            rules.add_directory(self.root, dir);
+        }
+        rules
+    }
+
+    fn is_listed(&self, rules: &IgnoreRules, name: &str, path: &Path, is_dir: bool) -> bool {
+        let hidden = name.starts_with('.');
+        let shown = match self.options.hidden {
+            HiddenPolicy::Hide => !hidden,
+            HiddenPolicy::ShowFiles => !hidden || !is_dir,
+            HiddenPolicy::Show => name != ".git",
+        };
+        shown
+            && !path
+                .strip_prefix(self.root)
+                .is_ok_and(|relative| rules.is_gitignored(relative, is_dir))
+    }
+
+    /// Listed entries of `dir`, sorted
+    fn entries(&self, dir: &Path, rules: &IgnoreRules) -> Result<Vec<Entry>, String> {
+        let read_dir = fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read directory '{}': {}", dir.display(), e))?;
+
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let file_type = entry
+                .file_type()
+                .map_err(|e| format!("Failed to read metadata: {}", e))?;
+            // Skip symlinks to prevent infinite loops
+            if file_type.is_symlink() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            let is_dir = file_type.is_dir();
+            if self.is_listed(rules, &name, &path, is_dir) {
+                entries.push(Entry { name, path, is_dir });
+            }
+        }
+
+        // Sort: directories first, then alphabetically
+        entries.sort_by(|a, b| {
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        Ok(entries)
+    }
+
+    fn has_children(&self, rules: &IgnoreRules, dir: &Path) -> bool {
+        let rules = self.child_rules(rules, dir);
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return false;
+        };
+        read_dir.flatten().any(|entry| {
+            entry.file_type().is_ok_and(|file_type| {
+                !file_type.is_symlink()
+                    && self.is_listed(&rules, &entry.file_name().to_string_lossy(), &entry.path(), file_type.is_dir())
+            })
+        })
+    }
+
+    fn node(&self, rules: &IgnoreRules, entry: Entry, children: Option<Vec<FileNode>>) -> FileNode {
+        let metadata = fs::metadata(&entry.path).ok();
+        let modified = metadata
+            .as_ref()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_millis() as u64);
+        let size = match (&metadata, entry.is_dir) {
+            (Some(metadata), false) => Some(metadata.len()),
+            _ => None,
+        };
+        let has_children = match (&children, entry.is_dir) {
+            (_, false) => None,
+            (Some(children), true) => Some(!children.is_empty()),
+            (None, true) => Some(self.has_children(rules, &entry.path)),
+        };
+        FileNode {
+            name: entry.name,
+            path: entry.path.to_string_lossy().to_string(),
+            is_directory: entry.is_dir,
+            children,
+            size,
+            modified,
+            has_children,
+        }
+    }
+
+    fn tree(&self, dir: &Path, rules: &IgnoreRules, depth: usize) -> Result<Vec<FileNode>, String> {
+        let mut nodes = Vec::new();
+        for entry in self.entries(dir, rules)? {
+            let children = if entry.is_dir && depth < MAX_DIRECTORY_DEPTH {
+                Some(self.tree(&entry.path, &self.child_rules(rules, &entry.path), depth + 1)?)
+            } else {
+                None
+            };
+            nodes.push(self.node(rules, entry, children));
+        }
+        Ok(nodes)
+    }
+}
+
+/// One page of the entries of `dir`, with `hasChildren` set on directories
+pub fn list_page(dir: &Path, root: &Path, options: &ListOptions) -> Result<DirectoryPage, String> {
+    let listing = Listing { root, options };
+    let rules = listing.rules_for(dir);
+    let entries = listing.entries(dir, &rules)?;
+    let total = entries.len();
+    let end = options
+        .limit
+        .map_or(total, |limit| options.offset.saturating_add(limit).min(total));
+
+    let entries: Vec<FileNode> = entries
+        .into_iter()
+        .skip(options.offset)
+        .take(end.saturating_sub(options.offset))
+        .map(|entry| listing.node(&rules, entry, None))
+        .collect();
+    Ok(DirectoryPage {
+        entries,
+        total,
+        next_offset: (end < total).then_some(end),
+    })
+}
+
+/// All entries below `dir`, nested in `children` down to `MAX_DIRECTORY_DEPTH`
+pub fn list_tree(dir: &Path, root: &Path, options: &ListOptions) -> Result<Vec<FileNode>, String> {
+    let listing = Listing { root, options };
+    listing.tree(dir, &listing.rules_for(dir), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(nodes: &[FileNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_listing_options_and_paging() {
+        let root = std::env::temp_dir().join(format!("dir-listing-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("lib/gen")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::create_dir_all(root.join("out")).unwrap();
+        fs::write(root.join(".gitignore"), "/out/\n").unwrap();
+        fs::write(root.join("lib/.ignore"), "gen/\n").unwrap();
+        fs::write(root.join("lib/gen/x.u"), "").unwrap();
+        fs::write(root.join("b.u"), "b = 1").unwrap();
+        fs::write(root.join("A.u"), "").unwrap();
+
+        let page = list_page(&root, &root, &ListOptions::default()).unwrap();
+        assert_eq!(names(&page.entries), vec!["empty", "lib", "A.u", "b.u"]);
+        assert_eq!(page.total, 4);
+        assert_eq!(page.next_offset, None);
+        let lib = &page.entries[1];
+        // lib only holds the ignored gen/ and the hidden .ignore
+        assert_eq!((lib.has_children, lib.size), (Some(false), None));
+        assert_eq!(page.entries[3].size, Some(5));
+        assert!(page.entries[3].modified.is_some());
+
+        let options = ListOptions { offset: 1, limit: Some(2), ..ListOptions::default() };
+        let page = list_page(&root, &root, &options).unwrap();
+        assert_eq!(names(&page.entries), vec!["lib", "A.u"]);
+        assert_eq!(page.next_offset, Some(3));
+
+        let options = ListOptions {
+            hidden: HiddenPolicy::ShowFiles,
+            respect_ignore: false,
+            ..ListOptions::default()
+        };
+        let page = list_page(&root, &root, &options).unwrap();
+        assert_eq!(names(&page.entries), vec!["empty", "lib", "out", ".gitignore", "A.u", "b.u"]);
+        assert_eq!(page.entries[1].has_children, Some(true));
+
+        let tree = list_tree(&root, &root, &ListOptions { hidden: HiddenPolicy::Show, ..ListOptions::default() }).unwrap();
+        assert_eq!(names(&tree), vec!["empty", "lib", ".gitignore", "A.u", "b.u"]);
+        assert_eq!(names(tree[1].children.as_ref().unwrap()), vec![".ignore"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,432 @@
+//! File History - Local snapshots of workspace files
+//!
+//! This module provides:
+//! - `record_snapshot`: stores a file's content under `.unison-editor/history/` of its
+//!   workspace on every save, deduplicated by content hash, labelled (save, before
+//!   `ucm update`, restore) and pruned by age, count and size limits
+//! - `list_history` / `read_snapshot`: a file's versions, newest first, and their content
+//! - `diff_lines`: a line diff between two versions
+//!
+//! Each file gets a directory named after the hash of its workspace-relative path,
+//! holding `index.json` and one `<version>.snapshot` blob per distinct content.
+//! The workspace is the nearest ancestor directory containing `.unison-editor`.
+
+use crate::file_io::{content_version, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Editor directory of a workspace (config, editor state, history)
+pub(crate) const CONFIG_DIR: &str = ".unison-editor";
+
+/// History directory, inside `CONFIG_DIR`
+const HISTORY_DIR: &str = "history";
+
+/// Serializes index updates, so concurrent saves don't lose entries
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// How much history is kept per file. The newest snapshot is always kept.
+#[derive(Debug, Clone, Copy)]
+struct HistoryLimits {
+    max_age: Duration,
+    max_entries: usize,
+    max_bytes: u64,
+}
+
+const DEFAULT_LIMITS: HistoryLimits = HistoryLimits {
+    max_age: Duration::from_secs(30 * 24 * 60 * 60),
+    max_entries: 200,
+    max_bytes: 20 * 1024 * 1024,
+};
+
+/// Why a snapshot was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SnapshotLabel {
+    Save,
+    /// The code sent to `ucm update`, taken right before it ran
+    BeforeUpdate,
+    /// The file was restored to an older version
+    Restore,
+}
+
+/// One version of a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// Milliseconds since the epoch
+    pub timestamp: u64,
+    /// Content hash (see `file_io::content_version`)
+    pub version: String,
+    pub size: u64,
+    pub label: SnapshotLabel,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryIndex {
+    /// Workspace-relative path of the file
+    path: String,
+    /// Oldest first
+    entries: Vec<HistoryEntry>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Workspace root of `file`: `workspace` if given, else the nearest ancestor with a
+/// `.unison-editor` directory
+pub(crate) fn workspace_root(file: &Path, workspace: Option<&Path>) -> Option<PathBuf> {
+    if let Some(workspace) = workspace {
+        return fs::canonicalize(workspace).ok();
+    }
+    file.ancestors()
+        .skip(1)
+        .find(|dir| dir.join(CONFIG_DIR).is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// History directory of `file` and its workspace-relative path. None for files
+/// outside a workspace and the editor's own files.
+fn history_location(file: &Path, workspace: Option<&Path>) -> Option<(PathBuf, String)> {
+    let root = workspace_root(file, workspace)?;
+    let relative = file.strip_prefix(&root).ok()?;
+    if relative.starts_with(CONFIG_DIR) {
+        return None;
+    }
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let dir = root
+        .join(CONFIG_DIR)
+        .join(HISTORY_DIR)
+        .join(content_version(relative.as_bytes()));
+    Some((dir, relative))
+}
+
+fn load_index(dir: &Path) -> HistoryIndex {
+    fs::read_to_string(dir.join("index.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_index(dir: &Path, index: &HistoryIndex) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(index)
+        .map_err(|e| format!("Failed to serialize history index: {}", e))?;
+    write_atomic(&dir.join("index.json"), json.as_bytes())
+        .map_err(|e| format!("Failed to write history index: {}", e))
+}
+
+fn blob_path(dir: &Path, version: &str) -> PathBuf {
+    dir.join(format!("{}.snapshot", version))
+}
+
+/// Drop entries beyond the limits (oldest first, keeping the newest), then blobs no
+/// entry refers to any more
+fn prune(dir: &Path, index: &mut HistoryIndex, now: u64, limits: HistoryLimits) {
+    let cutoff = now.saturating_sub(limits.max_age.as_millis() as u64);
+    let newest = index.entries.len().saturating_sub(1);
+    let mut keep_from = index
+        .entries
+        .iter()
+        .position(|entry| entry.timestamp >= cutoff)
+        .unwrap_or(newest)
+        .min(newest);
+    keep_from = keep_from.max(index.entries.len().saturating_sub(limits.max_entries.max(1)));
+
+    // Size counts each distinct blob once, newest first
+    let mut counted: Vec<&str> = Vec::new();
+    let mut bytes = 0u64;
+    for (i, entry) in index.entries.iter().enumerate().rev() {
+        if i < keep_from {
+            break;
+        }
+        if !counted.contains(&entry.version.as_str()) {
+            counted.push(&entry.version);
+            bytes += entry.size;
+        }
+        if bytes > limits.max_bytes && i < newest {
+            keep_from = i + 1;
+            break;
+        }
+    }
+
+    let removed: Vec<HistoryEntry> = index.entries.drain(..keep_from).collect();
+    for entry in removed {
+        if !index.entries.iter().any(|kept| kept.version == entry.version) {
+            let _ = fs::remove_file(blob_path(dir, &entry.version));
+        }
+    }
+}
+
+fn record_snapshot_at(
+    file: &Path,
+    workspace: Option<&Path>,
+    content: &str,
+    label: SnapshotLabel,
+    now: u64,
+    limits: HistoryLimits,
+) -> Result<Option<HistoryEntry>, String> {
+    let Some((dir, relative)) = history_location(file, workspace) else {
+        return Ok(None);
+    };
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create history directory: {}", e))?;
+
+    let mut index = load_index(&dir);
+    index.path = relative;
+    let version = content_version(content.as_bytes());
+
+    // Unchanged since the last snapshot - only a more specific label is worth keeping
+    if let Some(last) = index.entries.last_mut() {
+        if last.version == version {
+            if label != SnapshotLabel::Save && last.label != label {
+                last.label = label;
+                save_index(&dir, &index)?;
+            }
+            return Ok(None);
+        }
+    }
+
+    let blob = blob_path(&dir, &version);
+    if !blob.exists() {
+        write_atomic(&blob, content.as_bytes()).map_err(|e| format!("Failed to write snapshot: {}", e))?;
+    }
+    let id = index.entries.last().map_or(now, |last| now.max(last.id + 1));
+    let entry = HistoryEntry {
+        id,
+        timestamp: now,
+        version,
+        size: content.len() as u64,
+        label,
+    };
+    index.entries.push(entry.clone());
+    prune(&dir, &mut index, now, limits);
+    save_index(&dir, &index)?;
+    Ok(Some(entry))
+}
+
+/// Record `content` as the newest version of `file`. Returns the new entry, or None
+/// if nothing was recorded (unchanged content, or a file outside any workspace).
+pub fn record_snapshot(
+    file: &Path,
+    workspace: Option<&Path>,
+    content: &str,
+    label: SnapshotLabel,
+) -> Result<Option<HistoryEntry>, String> {
+    record_snapshot_at(file, workspace, content, label, now_ms(), DEFAULT_LIMITS)
+}
+
+/// Versions of `file`, newest first
+pub fn list_history(file: &Path, workspace: Option<&Path>) -> Vec<HistoryEntry> {
+    let Some((dir, _)) = history_location(file, workspace) else {
+        return Vec::new();
+    };
+    let mut entries = load_index(&dir).entries;
+    entries.reverse();
+    entries
+}
+
+/// Content of version `id` of `file`
+pub fn read_snapshot(file: &Path, workspace: Option<&Path>, id: u64) -> Result<String, String> {
+    let (dir, relative) = history_location(file, workspace)
+        .ok_or_else(|| format!("No history for '{}' (not in a workspace)", file.display()))?;
+    let entry = load_index(&dir)
+        .entries
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| format!("Version {} of '{}' not found", id, relative))?;
+    fs::read_to_string(blob_path(&dir, &entry.version))
+        .map_err(|e| format!("Failed to read version {} of '{}': {}", id, relative, e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffKind {
+    Equal,
+    Added,
+    Removed,
+}
+
+/// One line of a diff, with its 1-based line numbers in the old and new text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+    #[serde(rename = "oldLine")]
+    pub old_line: Option<usize>,
+    #[serde(rename = "newLine")]
+    pub new_line: Option<usize>,
+}
+
+/// Larger middles (after trimming the common prefix and suffix) are shown as
+/// removed-then-added instead of running the quadratic LCS
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Line diff of `old` and `new` (longest common subsequence)
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut diff = Vec::with_capacity(old.len().max(new.len()));
+    let mut push = |kind, text: &str, old_line: Option<usize>, new_line: Option<usize>| {
+        diff.push(DiffLine {
+            kind,
+            text: text.to_string(),
+            old_line: old_line.map(|i| i + 1),
+            new_line: new_line.map(|i| i + 1),
+        });
+    };
+
+    for (i, line) in old[..prefix].iter().enumerate() {
+        push(DiffKind::Equal, line, Some(i), Some(i));
+    }
+
+    let (n, m) = (old_mid.len(), new_mid.len());
+    if n * m > MAX_DIFF_CELLS {
+        for (i, line) in old_mid.iter().enumerate() {
+            push(DiffKind::Removed, line, Some(prefix + i), None);
+        }
+        for (j, line) in new_mid.iter().enumerate() {
+            push(DiffKind::Added, line, None, Some(prefix + j));
+        }
+    } else {
+        // lcs[i][j] = LCS length of old_mid[i..] and new_mid[j..]
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                push(DiffKind::Equal, old_mid[i], Some(prefix + i), Some(prefix + j));
+                i += 1;
+                j += 1;
+            } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                push(DiffKind::Added, new_mid[j], None, Some(prefix + j));
+                j += 1;
+            } else {
+                push(DiffKind::Removed, old_mid[i], Some(prefix + i), None);
+                i += 1;
+            }
+        }
+    }
+
+    let (old_start, new_start) = (old.len() - suffix, new.len() - suffix);
+    for k in 0..suffix {
+        push(DiffKind::Equal, old[old_start + k], Some(old_start + k), Some(new_start + k));
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    fn workspace(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("file-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(CONFIG_DIR)).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::canonicalize(root).unwrap()
+    }
+
+    #[test]
+    fn test_record_dedupes_and_labels() {
+        let root = workspace("record");
+        let file = root.join("src/scratch.u");
+        let record = |content: &str, label, now| record_snapshot_at(&file, None, content, label, now, DEFAULT_LIMITS).unwrap();
+
+        assert!(record("foo = 1", SnapshotLabel::Save, HOUR).is_some());
+        assert!(record("foo = 1", SnapshotLabel::Save, 2 * HOUR).is_none());
+        assert!(record("foo = 1", SnapshotLabel::BeforeUpdate, 3 * HOUR).is_none());
+        assert!(record("foo = 2", SnapshotLabel::Save, 4 * HOUR).is_some());
+
+        let history = list_history(&file, None);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].label, SnapshotLabel::BeforeUpdate);
+        assert_eq!(read_snapshot(&file, None, history[1].id).unwrap(), "foo = 1");
+        assert_eq!(read_snapshot(&file, None, history[0].id).unwrap(), "foo = 2");
+
+        // The editor's own files and files outside a workspace have no history
+        let config = root.join(CONFIG_DIR).join("config.json");
+        assert!(record_snapshot(&config, None, "{}", SnapshotLabel::Save).unwrap().is_none());
+        let outside = std::env::temp_dir().join("no-workspace.u");
+        assert!(record_snapshot(&outside, None, "x", SnapshotLabel::Save).unwrap().is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_prune_by_age_count_and_size() {
+        let root = workspace("prune");
+        let file = root.join("src/scratch.u");
+        let limits = HistoryLimits {
+            max_age: Duration::from_millis(10 * HOUR),
+            max_entries: 3,
+            max_bytes: 1024,
+        };
+        for i in 0..5u64 {
+            record_snapshot_at(&file, None, &format!("v{}", i), SnapshotLabel::Save, i * HOUR, limits).unwrap();
+        }
+        let history = list_history(&file, None);
+        assert_eq!(history.iter().map(|e| e.timestamp).collect::<Vec<_>>(), vec![4 * HOUR, 3 * HOUR, 2 * HOUR]);
+        let (dir, _) = history_location(&file, None).unwrap();
+        assert!(!blob_path(&dir, &content_version(b"v0")).exists());
+
+        // Old entries age out, but the newest is always kept
+        record_snapshot_at(&file, None, "v5", SnapshotLabel::Save, 100 * HOUR, limits).unwrap();
+        assert_eq!(list_history(&file, None).len(), 1);
+
+        // Size keeps the newest snapshots that fit
+        let big = "x".repeat(600);
+        record_snapshot_at(&file, None, &big, SnapshotLabel::Save, 101 * HOUR, limits).unwrap();
+        record_snapshot_at(&file, None, &format!("{}y", big), SnapshotLabel::Save, 102 * HOUR, limits).unwrap();
+        let history = list_history(&file, None);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].timestamp, 102 * HOUR);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc\nd\n", "a\nc\nx\nd\n");
+        let summary: Vec<(DiffKind, &str, Option<usize>, Option<usize>)> = diff
+            .iter()
+            .map(|line| (line.kind, line.text.as_str(), line.old_line, line.new_line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DiffKind::Equal, "a", Some(1), Some(1)),
+                (DiffKind::Removed, "b", Some(2), None),
+                (DiffKind::Equal, "c", Some(3), Some(2)),
+                (DiffKind::Added, "x", None, Some(3)),
+                (DiffKind::Equal, "d", Some(4), Some(4)),
+            ]
+        );
+        assert!(diff_lines("same", "same").iter().all(|line| line.kind == DiffKind::Equal));
+        assert_eq!(diff_lines("", "new").len(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! File IO - Atomic, version-checked file writes
+//!
+//! This module provides:
+//! - `content_version`: a stable hash of a file's content, returned by `read_file` and
+//!   checked by `write_file` to detect changes made outside the editor
+//! - `write_atomic`: writes through a temp file in the same directory and renames it
+//!   into place, so a crash mid-write never leaves a truncated file
+//! - `WriteFileError`: the typed error of `write_file`, telling a version conflict
+//!   (with the content now on disk) apart from other failures
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Numbers the temp files of this process, so concurrent writes never share one
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Version of a file's content (64-bit FNV-1a, hex). Stable across runs, so versions
+/// of restored tabs stay comparable.
+pub fn content_version(content: &[u8]) -> String {
+    let hash = content.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Temp file next to `path`, so the rename stays on one filesystem. Unique per call.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), n))
+}
+
+/// Flush the directory entry of `path` (a completed rename) to disk
+pub fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Write `contents` to `path` atomically: readers see the old or the new content,
+/// never a partial file. An existing file's permissions are kept.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path);
+    let result = (|| {
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, path)?;
+        sync_parent_dir(path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Error of `write_file`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WriteFileError {
+    /// The file changed (or was deleted) since the version the editor read
+    Conflict {
+        path: String,
+        #[serde(rename = "expectedVersion")]
+        expected_version: String,
+        /// Version on disk (None if the file was deleted)
+        #[serde(rename = "actualVersion")]
+        actual_version: Option<String>,
+        /// Content on disk (None if the file was deleted)
+        #[serde(rename = "diskContent")]
+        disk_content: Option<String>,
+    },
+    Failed { message: String },
+}
+
+impl From<String> for WriteFileError {
+    fn from(message: String) -> Self {
+        WriteFileError::Failed { message }
+    }
+}
+
+impl std::fmt::Display for WriteFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteFileError::Conflict { path, .. } => write!(f, "File changed on disk: {}", path),
+            WriteFileError::Failed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Fail with `WriteFileError::Conflict` unless `path` is still at `expected_version`
+pub fn check_version(path: &Path, expected_version: &str) -> Result<(), WriteFileError> {
+    let conflict = |actual_version, disk_content| WriteFileError::Conflict {
+        path: path.display().to_string(),
+        expected_version: expected_version.to_string(),
+        actual_version,
+        disk_content,
+    };
+    match fs::read(path) {
+        Ok(bytes) => {
+            let actual = content_version(&bytes);
+            if actual == expected_version {
+                Ok(())
+            } else {
+                Err(conflict(Some(actual), Some(String::from_utf8_lossy(&bytes).to_string())))
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(conflict(None, None)),
+        Err(e) => Err(format!("Failed to read '{}': {}", path.display(), e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_version_is_stable() {
+        assert_eq!(content_version(b""), "cbf29ce484222325");
+        assert_eq!(content_version(b"a"), "af63dc4c8601ec8c");
+        assert_ne!(content_version(b"foo = 1"), content_version(b"foo = 2"));
+    }
+
+    #[test]
+    fn test_write_atomic_and_check_version() {
+        let dir = std::env::temp_dir().join(format!("file-io-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scratch.u");
+
+        write_atomic(&path, b"foo = 1").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "foo = 1");
+        let leftovers = fs::read_dir(&dir).unwrap().filter(|entry| {
+            entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp")
+        });
+        assert_eq!(leftovers.count(), 0);
+        // Concurrent writes of one file each get their own temp file
+        assert_ne!(temp_path(&path), temp_path(&path));
+
+        let version = content_version(b"foo = 1");
+        assert!(check_version(&path, &version).is_ok());
+
+        write_atomic(&path, b"foo = 2").unwrap();
+        match check_version(&path, &version) {
+            Err(WriteFileError::Conflict { disk_content, actual_version, .. }) => {
+                assert_eq!(disk_content.as_deref(), Some("foo = 2"));
+                assert_eq!(actual_version, Some(content_version(b"foo = 2")));
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            check_version(&path, &version),
+            Err(WriteFileError::Conflict { actual_version: None, .. })
+        ));
+
+        let json = serde_json::to_value(WriteFileError::from("boom".to_string())).unwrap();
+        assert_eq!(json["kind"], "failed");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,509 @@
+//! File Operations - Recoverable delete and undo for explorer operations
+//!
+//! This module provides:
+//! - `move_to_trash`: moves a deleted file or directory to the trash instead of
+//!   removing it - the freedesktop.org home trash on Linux, falling back to
+//!   `.unison-editor/trash/` of the workspace (other platforms, other filesystems)
+//! - `restore_from_trash`: puts a trashed entry back where it was
+//! - `OperationJournal`: the create/rename/delete operations of this launch, with
+//!   `undo`/`redo` (the `undo_file_operation`/`redo_file_operation` commands); a
+//!   `Batch` of them (see `file_transfer`) is undone as one
+//!
+//! Undoing a create moves the created entry to the trash (it may have been edited
+//! since), so redoing it brings back the same content.
+
+use crate::file_history::{workspace_root, CONFIG_DIR};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Workspace trash directory, inside `CONFIG_DIR`
+const TRASH_DIR: &str = "trash";
+
+/// Entries of the workspace trash older than this are removed for good
+const WORKSPACE_TRASH_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Operations kept for undo
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+/// A file or directory moved to the trash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashedEntry {
+    #[serde(rename = "originalPath")]
+    pub original_path: PathBuf,
+    /// Where the entry is now
+    #[serde(rename = "trashPath")]
+    pub trash_path: PathBuf,
+    /// `.trashinfo` file of a freedesktop trash entry
+    #[serde(rename = "infoPath")]
+    pub info_path: Option<PathBuf>,
+}
+
+fn unique_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("{}-{}", now, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Move `path` to `.unison-editor/trash/<id>/` of its workspace
+fn move_to_workspace_trash(path: &Path, workspace: Option<&Path>) -> Result<TrashedEntry, String> {
+    let root = workspace_root(path, workspace)
+        .ok_or_else(|| format!("No workspace trash for '{}'", path.display()))?;
+    let trash = root.join(CONFIG_DIR).join(TRASH_DIR);
+    prune_workspace_trash(&trash);
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Cannot trash '{}'", path.display()))?;
+    let dir = trash.join(unique_id());
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create trash directory: {}", e))?;
+    let trash_path = dir.join(name);
+    if let Err(e) = fs::rename(path, &trash_path) {
+        let _ = fs::remove_dir(&dir);
+        return Err(format!("Failed to move '{}' to the trash: {}", path.display(), e));
+    }
+    Ok(TrashedEntry {
+        original_path: path.to_path_buf(),
+        trash_path,
+        info_path: None,
+    })
+}
+
+fn prune_workspace_trash(trash: &Path) {
+    let Ok(entries) = fs::read_dir(trash) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > WORKSPACE_TRASH_MAX_AGE);
+        if expired {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Percent-encode a path for the `Path=` key of a `.trashinfo` file
+#[cfg(target_os = "linux")]
+fn encode_trash_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Local time as `YYYY-MM-DDThh:mm:ss`, the `DeletionDate=` format
+#[cfg(target_os = "linux")]
+fn deletion_date() -> String {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// Move `path` to the freedesktop.org home trash (`$XDG_DATA_HOME/Trash`), so it
+/// shows up in the desktop's trash. Fails for paths on another filesystem.
+#[cfg(target_os = "linux")]
+fn move_to_system_trash(path: &Path) -> Result<TrashedEntry, String> {
+    let trash = dirs::data_dir()
+        .ok_or("No data directory for the trash")?
+        .join("Trash");
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files).map_err(|e| format!("Failed to create trash directory: {}", e))?;
+    fs::create_dir_all(&info).map_err(|e| format!("Failed to create trash directory: {}", e))?;
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Cannot trash '{}'", path.display()))?
+        .to_string_lossy()
+        .to_string();
+
+    // The spec reserves a name by creating its .trashinfo exclusively
+    for n in 1..1000 {
+        let candidate = if n == 1 { name.clone() } else { format!("{}.{}", name, n) };
+        let info_path = info.join(format!("{}.trashinfo", candidate));
+        let trash_path = files.join(&candidate);
+        let mut info_file = match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Failed to write trash info: {}", e)),
+        };
+        if trash_path.exists() {
+            let _ = fs::remove_file(&info_path);
+            continue;
+        }
+
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_trash_path(path),
+            deletion_date()
+        );
+        let moved = io::Write::write_all(&mut info_file, contents.as_bytes())
+            .and_then(|_| fs::rename(path, &trash_path));
+        if let Err(e) = moved {
+            let _ = fs::remove_file(&info_path);
+            return Err(format!("Failed to move '{}' to the trash: {}", path.display(), e));
+        }
+        return Ok(TrashedEntry {
+            original_path: path.to_path_buf(),
+            trash_path,
+            info_path: Some(info_path),
+        });
+    }
+    Err(format!("No free trash name for '{}'", path.display()))
+}
+
+/// Move `path` to the trash: the system trash if `system` is set and it can take
+/// the path, else the workspace trash
+pub fn move_to_trash(path: &Path, workspace: Option<&Path>, system: bool) -> Result<TrashedEntry, String> {
+    #[cfg(target_os = "linux")]
+    if system {
+        match move_to_system_trash(path) {
+            Ok(entry) => return Ok(entry),
+            Err(e) => log::debug!("System trash unavailable, using the workspace trash: {}", e),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = system;
+    move_to_workspace_trash(path, workspace)
+}
+
+/// Put a trashed entry back at its original path
+pub fn restore_from_trash(entry: &TrashedEntry) -> Result<(), String> {
+    let original = &entry.original_path;
+    if original.exists() {
+        return Err(format!("Cannot restore '{}': it already exists", original.display()));
+    }
+    if !entry.trash_path.exists() {
+        return Err(format!("'{}' is no longer in the trash", original.display()));
+    }
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create parent directory: {}", e))?;
+    }
+    fs::rename(&entry.trash_path, original)
+        .map_err(|e| format!("Failed to restore '{}': {}", original.display(), e))?;
+
+    match &entry.info_path {
+        Some(info_path) => {
+            let _ = fs::remove_file(info_path);
+        }
+        // The per-entry directory of the workspace trash
+        None => {
+            if let Some(dir) = entry.trash_path.parent() {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A journaled file operation, as it was done
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FileOperation {
+    Create {
+        path: PathBuf,
+        #[serde(rename = "isDirectory")]
+        is_directory: bool,
+        /// Where an undone create went, for redo
+        trashed: Option<TrashedEntry>,
+    },
+    Rename {
+        #[serde(rename = "oldPath")]
+        old_path: PathBuf,
+        #[serde(rename = "newPath")]
+        new_path: PathBuf,
+    },
+    Delete {
+        path: PathBuf,
+        trashed: TrashedEntry,
+    },
+    /// Operations done together (a multi-entry move or copy), undone as one
+    Batch { operations: Vec<FileOperation> },
+}
+
+fn rename_entry(from: &Path, to: &Path) -> Result<(), String> {
+    if to.exists() {
+        return Err(format!("'{}' already exists", to.display()));
+    }
+    fs::rename(from, to)
+        .map_err(|e| format!("Failed to rename '{}' to '{}': {}", from.display(), to.display(), e))
+}
+
+#[derive(Debug, Default)]
+struct Stacks {
+    undo: Vec<FileOperation>,
+    redo: Vec<FileOperation>,
+}
+
+/// Undo/redo history of file operations
+pub struct OperationJournal {
+    stacks: Mutex<Stacks>,
+    /// Prefer the system trash over the workspace trash
+    system_trash: bool,
+}
+
+impl OperationJournal {
+    pub fn new() -> Self {
+        Self::with_system_trash(cfg!(target_os = "linux"))
+    }
+
+    pub(crate) fn with_system_trash(system_trash: bool) -> Self {
+        Self {
+            stacks: Mutex::new(Stacks::default()),
+            system_trash,
+        }
+    }
+
+    /// Move `path` to the trash without recording it, for operations recorded as a whole
+    pub fn trash(&self, path: &Path, workspace: Option<&Path>) -> Result<TrashedEntry, String> {
+        move_to_trash(path, workspace, self.system_trash)
+    }
+
+    /// Move `path` to the trash and record the delete
+    pub fn delete(&self, path: &Path, workspace: Option<&Path>) -> Result<(), String> {
+        let trashed = self.trash(path, workspace)?;
+        self.record(FileOperation::Delete {
+            path: path.to_path_buf(),
+            trashed,
+        });
+        Ok(())
+    }
+
+    /// Record an operation that was just done. Clears the redo stack.
+    pub fn record(&self, operation: FileOperation) {
+        let mut stacks = self.stacks.lock().unwrap();
+        stacks.redo.clear();
+        stacks.undo.push(operation);
+        if stacks.undo.len() > MAX_JOURNAL_ENTRIES {
+            stacks.undo.remove(0);
+        }
+    }
+
+    /// Forget all operations, e.g. when another workspace is opened
+    pub fn clear(&self) {
+        let mut stacks = self.stacks.lock().unwrap();
+        stacks.undo.clear();
+        stacks.redo.clear();
+    }
+
+    /// Undo the last operation. Returns it, or None if there is nothing to undo.
+    /// A failed undo stays on the undo stack.
+    pub fn undo(&self, workspace: Option<&Path>) -> Result<Option<FileOperation>, String> {
+        let mut stacks = self.stacks.lock().unwrap();
+        let Some(operation) = stacks.undo.pop() else {
+            return Ok(None);
+        };
+        match self.revert(operation.clone(), workspace) {
+            Ok(undone) => {
+                stacks.redo.push(undone.clone());
+                Ok(Some(undone))
+            }
+            Err(e) => {
+                stacks.undo.push(operation);
+                Err(e)
+            }
+        }
+    }
+
+    /// Redo the last undone operation. Returns it, or None if there is nothing to redo.
+    pub fn redo(&self, workspace: Option<&Path>) -> Result<Option<FileOperation>, String> {
+        let mut stacks = self.stacks.lock().unwrap();
+        let Some(operation) = stacks.redo.pop() else {
+            return Ok(None);
+        };
+        match self.replay(operation.clone(), workspace) {
+            Ok(redone) => {
+                stacks.undo.push(redone.clone());
+                Ok(Some(redone))
+            }
+            Err(e) => {
+                stacks.redo.push(operation);
+                Err(e)
+            }
+        }
+    }
+
+    fn revert(&self, operation: FileOperation, workspace: Option<&Path>) -> Result<FileOperation, String> {
+        match operation {
+            FileOperation::Create { path, is_directory, .. } => {
+                let trashed = move_to_trash(&path, workspace, self.system_trash)?;
+                Ok(FileOperation::Create { path, is_directory, trashed: Some(trashed) })
+            }
+            FileOperation::Rename { old_path, new_path } => {
+                rename_entry(&new_path, &old_path)?;
+                Ok(FileOperation::Rename { old_path, new_path })
+            }
+            FileOperation::Delete { path, trashed } => {
+                restore_from_trash(&trashed)?;
+                Ok(FileOperation::Delete { path, trashed })
+            }
+            FileOperation::Batch { operations } => {
+                let mut undone = Vec::with_capacity(operations.len());
+                for operation in operations.into_iter().rev() {
+                    match self.revert(operation, workspace) {
+                        Ok(operation) => undone.push(operation),
+                        Err(e) => {
+                            // Leave the batch as it was, so the undo can be retried
+                            for operation in undone.into_iter().rev() {
+                                let _ = self.replay(operation, workspace);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                undone.reverse();
+                Ok(FileOperation::Batch { operations: undone })
+            }
+        }
+    }
+
+    fn replay(&self, operation: FileOperation, workspace: Option<&Path>) -> Result<FileOperation, String> {
+        match operation {
+            FileOperation::Create { path, is_directory, trashed } => {
+                match trashed {
+                    Some(trashed) => restore_from_trash(&trashed)?,
+                    None if is_directory => fs::create_dir_all(&path)
+                        .map_err(|e| format!("Failed to create directory '{}': {}", path.display(), e))?,
+                    None => fs::write(&path, "")
+                        .map_err(|e| format!("Failed to create file '{}': {}", path.display(), e))?,
+                }
+                Ok(FileOperation::Create { path, is_directory, trashed: None })
+            }
+            FileOperation::Rename { old_path, new_path } => {
+                rename_entry(&old_path, &new_path)?;
+                Ok(FileOperation::Rename { old_path, new_path })
+            }
+            FileOperation::Delete { path, .. } => {
+                let trashed = move_to_trash(&path, workspace, self.system_trash)?;
+                Ok(FileOperation::Delete { path, trashed })
+            }
+            FileOperation::Batch { operations } => {
+                let mut redone = Vec::with_capacity(operations.len());
+                for operation in operations {
+                    match self.replay(operation, workspace) {
+                        Ok(operation) => redone.push(operation),
+                        Err(e) => {
+                            for operation in redone.into_iter().rev() {
+                                let _ = self.revert(operation, workspace);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                Ok(FileOperation::Batch { operations: redone })
+            }
+        }
+    }
+}
+
+impl Default for OperationJournal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("file-ops-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(CONFIG_DIR)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_workspace_trash_round_trip() {
+        let ws = workspace("trash");
+        let dir = ws.join("lib");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.u"), "a = 1").unwrap();
+
+        let entry = move_to_trash(&dir, None, false).unwrap();
+        assert!(!dir.exists());
+        assert!(entry.trash_path.starts_with(ws.join(CONFIG_DIR).join(TRASH_DIR)));
+        assert_eq!(entry.info_path, None);
+
+        restore_from_trash(&entry).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.u")).unwrap(), "a = 1");
+        assert!(!entry.trash_path.parent().unwrap().exists());
+        fs::remove_dir_all(ws).unwrap();
+    }
+
+    #[test]
+    fn test_journal_undo_redo() {
+        let ws = workspace("journal");
+        let journal = OperationJournal::with_system_trash(false);
+        let a = ws.join("a.u");
+        let b = ws.join("b.u");
+
+        fs::write(&a, "").unwrap();
+        journal.record(FileOperation::Create { path: a.clone(), is_directory: false, trashed: None });
+        fs::write(&a, "edited").unwrap();
+        fs::rename(&a, &b).unwrap();
+        journal.record(FileOperation::Rename { old_path: a.clone(), new_path: b.clone() });
+        journal.delete(&b, Some(&ws)).unwrap();
+        assert!(!b.exists());
+
+        // Undo delete, rename and create
+        assert!(matches!(journal.undo(Some(&ws)), Ok(Some(FileOperation::Delete { .. }))));
+        assert!(b.exists());
+        assert!(matches!(journal.undo(Some(&ws)), Ok(Some(FileOperation::Rename { .. }))));
+        assert!(a.exists() && !b.exists());
+        assert!(matches!(journal.undo(Some(&ws)), Ok(Some(FileOperation::Create { .. }))));
+        assert!(!a.exists());
+        assert_eq!(journal.undo(Some(&ws)), Ok(None));
+
+        // Redo brings back the edited content
+        journal.redo(Some(&ws)).unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "edited");
+        journal.redo(Some(&ws)).unwrap();
+        assert!(b.exists());
+
+        // A new operation clears the redo stack
+        fs::write(ws.join("c.u"), "").unwrap();
+        journal.record(FileOperation::Create { path: ws.join("c.u"), is_directory: false, trashed: None });
+        assert_eq!(journal.redo(Some(&ws)), Ok(None));
+
+        // A failing undo stays undoable
+        fs::write(&a, "in the way").unwrap();
+        journal.undo(Some(&ws)).ok();
+        assert!(journal.undo(Some(&ws)).is_err());
+        fs::remove_file(&a).unwrap();
+        assert!(journal.undo(Some(&ws)).unwrap().is_some());
+        fs::remove_dir_all(ws).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_encode_trash_path() {
+        assert_eq!(encode_trash_path(Path::new("/home/me/my file%.u")), "/home/me/my%20file%25.u");
+    }
+}
@@ -0,0 +1,400 @@
+//! File Transfer - Copy, duplicate and move for the file explorer
+//!
+//! This module provides:
+//! - `transfer`: copies or moves entries to their targets, resolving conflicts by
+//!   `ConflictPolicy` and journaling the whole transfer as one undoable operation
+//! - `duplicate_target`: the first free `name copy.ext`, `name copy 2.ext`, ... next to
+//!   a path
+//! - `TransferProgress`: progress of a transfer, reported through a callback at most
+//!   every `PROGRESS_INTERVAL` (and once at the end)
+//!
+//! Overwriting moves the existing target to the trash, so it can be undone too.
+//! Moves are renames, falling back to copy and remove across filesystems. Symlinks
+//! are copied as links (on Unix), never followed, so copies can't loop.
+
+use crate::file_operations::{FileOperation, OperationJournal};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two progress reports
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do when a target already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    /// Replace the target (moved to the trash)
+    Overwrite,
+    /// Leave the target alone and skip the entry
+    Skip,
+    /// Use a free `name copy.ext` next to the target
+    Rename,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferMode {
+    Copy,
+    Move,
+}
+
+/// Outcome of a transfer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferSummary {
+    /// Where the transferred entries ended up, in order
+    pub targets: Vec<PathBuf>,
+    /// Sources skipped because their target existed
+    pub skipped: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferProgress {
+    /// Source entry being transferred
+    pub current: PathBuf,
+    /// Files, directories and links done. A move by rename counts one per source.
+    #[serde(rename = "doneEntries")]
+    pub done_entries: u64,
+    #[serde(rename = "totalEntries")]
+    pub total_entries: u64,
+    #[serde(rename = "doneBytes")]
+    pub done_bytes: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+}
+
+/// A `file-transfer-progress` event, tagged with the caller's transfer id
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferEvent<'a> {
+    #[serde(rename = "transferId")]
+    pub transfer_id: &'a str,
+    #[serde(flatten)]
+    pub progress: &'a TransferProgress,
+}
+
+struct Progress<'a> {
+    state: TransferProgress,
+    report: &'a mut dyn FnMut(&TransferProgress),
+    last_report: Option<Instant>,
+}
+
+impl Progress<'_> {
+    fn advance(&mut self, entries: u64, bytes: u64) {
+        self.state.done_entries += entries;
+        self.state.done_bytes += bytes;
+        let due = self
+            .last_report
+            .map_or(true, |last| last.elapsed() >= PROGRESS_INTERVAL);
+        if due {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        (self.report)(&self.state);
+        self.last_report = Some(Instant::now());
+    }
+}
+
+/// Entries and bytes below `path` (itself included), without following symlinks
+fn measure(path: &Path) -> (u64, u64) {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+    if !metadata.is_dir() {
+        return (1, if metadata.is_file() { metadata.len() } else { 0 });
+    }
+    let mut total = (1, 0);
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        let (entries, bytes) = measure(&entry.path());
+        total.0 += entries;
+        total.1 += bytes;
+    }
+    total
+}
+
+/// The first free `name copy.ext`, `name copy 2.ext`, ... next to `path`
+pub fn duplicate_target(path: &Path) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Cannot duplicate '{}'", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    // Directories and dotfiles (".env") keep their whole name as the stem
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 && !path.is_dir() => name.split_at(dot),
+        _ => (name.as_str(), ""),
+    };
+    (1..10_000)
+        .map(|n| match n {
+            1 => path.with_file_name(format!("{} copy{}", stem, extension)),
+            n => path.with_file_name(format!("{} copy {}{}", stem, n, extension)),
+        })
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .ok_or_else(|| format!("No free name to duplicate '{}'", path.display()))
+}
+
+fn is_cross_device(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    return error.raw_os_error() == Some(libc::EXDEV);
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(windows)]
+    return error.raw_os_error() == Some(17);
+    #[cfg(not(any(unix, windows)))]
+    return false;
+}
+
+fn copy_tree(source: &Path, target: &Path, progress: &mut Progress) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(source)
+        .map_err(|e| format!("Failed to read '{}': {}", source.display(), e))?;
+    let file_type = metadata.file_type();
+
+    if file_type.is_dir() {
+        fs::create_dir(target)
+            .map_err(|e| format!("Failed to create directory '{}': {}", target.display(), e))?;
+        progress.advance(1, 0);
+        let entries = fs::read_dir(source)
+            .map_err(|e| format!("Failed to read directory '{}': {}", source.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            copy_tree(&entry.path(), &target.join(entry.file_name()), progress)?;
+        }
+        // Keep the source's permissions (set last, in case it is read-only)
+        let _ = fs::set_permissions(target, metadata.permissions());
+    } else if file_type.is_symlink() {
+        #[cfg(unix)]
+        {
+            let link = fs::read_link(source)
+                .map_err(|e| format!("Failed to read link '{}': {}", source.display(), e))?;
+            std::os::unix::fs::symlink(link, target)
+                .map_err(|e| format!("Failed to create link '{}': {}", target.display(), e))?;
+        }
+        #[cfg(not(unix))]
+        log::warn!("Not copying symlink '{}'", source.display());
+        progress.advance(1, 0);
+    } else {
+        let bytes = fs::copy(source, target).map_err(|e| {
+            format!("Failed to copy '{}' to '{}': {}", source.display(), target.display(), e)
+        })?;
+        progress.advance(1, bytes);
+    }
+    Ok(())
+}
+
+/// `copy_tree` that doesn't leave a partial copy behind when it fails
+fn copy_partial(source: &Path, target: &Path, progress: &mut Progress) -> Result<(), String> {
+    let existed = fs::symlink_metadata(target).is_ok();
+    let copied = copy_tree(source, target, progress);
+    if copied.is_err() && !existed {
+        let _ = remove_entry(target);
+    }
+    copied
+}
+
+fn remove_entry(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn move_entry(source: &Path, target: &Path, progress: &mut Progress) -> Result<(), String> {
+    match fs::rename(source, target) {
+        Ok(()) => {
+            progress.advance(1, 0);
+            Ok(())
+        }
+        Err(e) if is_cross_device(&e) => {
+            let (entries, bytes) = measure(source);
+            progress.state.total_entries += entries.saturating_sub(1);
+            progress.state.total_bytes += bytes;
+            copy_partial(source, target, progress)?;
+            remove_entry(source).map_err(|e| {
+                format!("Copied '{}' but failed to remove it: {}", source.display(), e)
+            })
+        }
+        Err(e) => Err(format!(
+            "Failed to move '{}' to '{}': {}",
+            source.display(),
+            target.display(),
+            e
+        )),
+    }
+}
+
+/// Copy or move each `(source, target)` pair. Conflicts are resolved per pair with
+/// `policy`. Everything done is journaled as one operation, also when a later pair
+/// fails.
+pub fn transfer(
+    pairs: &[(PathBuf, PathBuf)],
+    mode: TransferMode,
+    policy: ConflictPolicy,
+    journal: &OperationJournal,
+    workspace: Option<&Path>,
+    report: &mut dyn FnMut(&TransferProgress),
+) -> Result<TransferSummary, String> {
+    let (total_entries, total_bytes) = match mode {
+        TransferMode::Copy => pairs
+            .iter()
+            .map(|(source, _)| measure(source))
+            .fold((0, 0), |total, (entries, bytes)| (total.0 + entries, total.1 + bytes)),
+        TransferMode::Move => (pairs.len() as u64, 0),
+    };
+    let mut progress = Progress {
+        state: TransferProgress {
+            total_entries,
+            total_bytes,
+            ..TransferProgress::default()
+        },
+        report,
+        last_report: None,
+    };
+
+    let mut summary = TransferSummary::default();
+    let mut operations = Vec::new();
+    let result = pairs.iter().try_for_each(|(source, target)| {
+        progress.state.current = source.clone();
+        transfer_one(source, target, mode, policy, journal, workspace, &mut progress, &mut summary, &mut operations)
+    });
+
+    progress.flush();
+    match operations.len() {
+        0 => {}
+        1 => journal.record(operations.remove(0)),
+        _ => journal.record(FileOperation::Batch { operations }),
+    }
+    result.map(|_| summary)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transfer_one(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    policy: ConflictPolicy,
+    journal: &OperationJournal,
+    workspace: Option<&Path>,
+    progress: &mut Progress,
+    summary: &mut TransferSummary,
+    operations: &mut Vec<FileOperation>,
+) -> Result<(), String> {
+    let is_directory = fs::symlink_metadata(source)
+        .map_err(|_| format!("Source path does not exist: {}", source.display()))?
+        .is_dir();
+
+    // Moving an entry onto itself leaves it where it is
+    if mode == TransferMode::Move && source == target {
+        summary.targets.push(target.to_path_buf());
+        progress.advance(1, 0);
+        return Ok(());
+    }
+    if target.starts_with(source) || source.starts_with(target) {
+        return Err(format!(
+            "Cannot transfer '{}' into '{}'",
+            source.display(),
+            target.display()
+        ));
+    }
+
+    let mut target = target.to_path_buf();
+    if fs::symlink_metadata(&target).is_ok() {
+        match policy {
+            ConflictPolicy::Skip => {
+                summary.skipped.push(source.to_path_buf());
+                let (entries, bytes) = match mode {
+                    TransferMode::Copy => measure(source),
+                    TransferMode::Move => (1, 0),
+                };
+                progress.advance(entries, bytes);
+                return Ok(());
+            }
+            ConflictPolicy::Rename => target = duplicate_target(&target)?,
+            ConflictPolicy::Overwrite => {
+                let trashed = journal.trash(&target, workspace)?;
+                operations.push(FileOperation::Delete {
+                    path: target.clone(),
+                    trashed,
+                });
+            }
+        }
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create parent directory: {}", e))?;
+    }
+
+    match mode {
+        TransferMode::Copy => {
+            copy_partial(source, &target, progress)?;
+            operations.push(FileOperation::Create {
+                path: target.clone(),
+                is_directory,
+                trashed: None,
+            });
+        }
+        TransferMode::Move => {
+            move_entry(source, &target, progress)?;
+            operations.push(FileOperation::Rename {
+                old_path: source.to_path_buf(),
+                new_path: target.clone(),
+            });
+        }
+    }
+    summary.targets.push(target);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_history::CONFIG_DIR;
+
+    #[test]
+    fn test_transfer_conflicts_and_undo() {
+        let ws = std::env::temp_dir().join(format!("file-transfer-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&ws);
+        fs::create_dir_all(ws.join(CONFIG_DIR)).unwrap();
+        fs::create_dir_all(ws.join("lib/sub")).unwrap();
+        fs::create_dir_all(ws.join("dest")).unwrap();
+        fs::write(ws.join("lib/a.u"), "a = 1").unwrap();
+        fs::write(ws.join("lib/sub/b.u"), "b = 2").unwrap();
+        fs::write(ws.join("dest/a.u"), "old").unwrap();
+        let journal = OperationJournal::with_system_trash(false);
+        let mut reports = Vec::new();
+        let mut report = |progress: &TransferProgress| reports.push(progress.clone());
+
+        // Duplicating picks the first free name
+        assert_eq!(duplicate_target(&ws.join("lib/a.u")).unwrap(), ws.join("lib/a copy.u"));
+        assert_eq!(duplicate_target(&ws.join("lib")).unwrap(), ws.join("lib copy"));
+        let pairs = [(ws.join("lib"), ws.join("lib copy"))];
+        transfer(&pairs, TransferMode::Copy, ConflictPolicy::Skip, &journal, Some(&ws), &mut report).unwrap();
+        assert_eq!(fs::read_to_string(ws.join("lib copy/sub/b.u")).unwrap(), "b = 2");
+        let last = reports.last().unwrap();
+        assert_eq!((last.done_entries, last.total_entries, last.done_bytes), (4, 4, 10));
+
+        // Copy into itself is refused
+        let pairs = [(ws.join("lib"), ws.join("lib/sub/lib"))];
+        assert!(transfer(&pairs, TransferMode::Copy, ConflictPolicy::Skip, &journal, None, &mut |_| {}).is_err());
+
+        let a_to_dest = (ws.join("lib/a.u"), ws.join("dest/a.u"));
+        let summary = transfer(std::slice::from_ref(&a_to_dest), TransferMode::Copy, ConflictPolicy::Skip, &journal, None, &mut |_| {}).unwrap();
+        assert_eq!(summary.skipped, vec![ws.join("lib/a.u")]);
+        let summary = transfer(std::slice::from_ref(&a_to_dest), TransferMode::Copy, ConflictPolicy::Rename, &journal, None, &mut |_| {}).unwrap();
+        assert_eq!(summary.targets, vec![ws.join("dest/a copy.u")]);
+
+        // Moving with overwrite trashes the target; one undo reverts the whole move
+        let pairs = [a_to_dest, (ws.join("lib/sub"), ws.join("dest/sub"))];
+        transfer(&pairs, TransferMode::Move, ConflictPolicy::Overwrite, &journal, Some(&ws), &mut |_| {}).unwrap();
+        assert_eq!(fs::read_to_string(ws.join("dest/a.u")).unwrap(), "a = 1");
+        assert!(ws.join("dest/sub/b.u").exists() && !ws.join("lib/sub").exists());
+
+        journal.undo(Some(&ws)).unwrap();
+        assert_eq!(fs::read_to_string(ws.join("dest/a.u")).unwrap(), "old");
+        assert!(ws.join("lib/a.u").exists() && ws.join("lib/sub/b.u").exists());
+        assert!(!ws.join("dest/sub").exists());
+        journal.redo(Some(&ws)).unwrap();
+        assert_eq!(fs::read_to_string(ws.join("dest/a.u")).unwrap(), "a = 1");
+        fs::remove_dir_all(ws).unwrap();
+    }
+}
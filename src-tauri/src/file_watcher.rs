@@ -1,19 +1,55 @@
 //! File Watcher Module - Watches files for external changes
 //!
-//! This module provides per-file watching with fast event delivery.
-//! When a watched file changes, it emits a Tauri event to the frontend.
+//! This module provides:
+//! - Per-file watching with fast event delivery: when a watched file changes, a
+//!   `file-changed` event is emitted to the frontend
+//! - A workspace watch (`watch_workspace`): a non-recursive watch of every directory
+//!   that isn't ignored (kept up to date by `tree_delta` as directories come and go,
+//!   so `.git`, `node_modules` and ignored build output cost no watches), whose events
+//!   are batched into debounced `workspace-changed` events with the created, renamed,
+//!   modified and deleted entries of the tree
+//! - `WatcherConfig`: the backend, poll interval and content comparison
+//!   (`configure_file_watcher`)
+//! - Self-write suppression: `record_write` registers the content hash of a file the
+//!   editor wrote, and `file-changed` is only emitted when a file's hash differs from
+//!   the last one the editor knows, so the editor's own saves and duplicate events
+//!   never reach the frontend as external changes
 //!
-//! Uses PollWatcher for predictable, fast detection across all platforms.
+//! The native backend (inotify, FSEvents, ...) is preferred: it costs nothing while
+//! files are idle. Open files are watched through their parent directory, so atomic
+//! saves (write to a temp file, rename over the original) keep being seen. Polling is
+//! used for network filesystems, whose remote changes native watchers never see, and
+//! whenever the native watcher fails (e.g. the inotify watch limit is reached).
 
-use notify::{Config, Event, PollWatcher, RecursiveMode, Watcher};
+use crate::file_io::content_version;
+use crate::tree_delta::TreeDelta;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter};
 
+/// Quiet time after the last workspace event before a batch is emitted
+const WORKSPACE_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Longest a workspace change waits while events keep coming
+const WORKSPACE_MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Shortest allowed poll interval
+const MIN_POLL_INTERVAL_MS: u64 = 100;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 /// Event payload sent to frontend when a file changes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileChangeEvent {
@@ -23,24 +59,336 @@ pub struct FileChangeEvent {
     /// Timestamp when the change was detected (milliseconds since epoch)
     #[serde(rename = "detectedAt")]
     pub detected_at: u64,
+    /// Content hash of the file now (see `file_io::content_version`), None if deleted
+    pub version: Option<String>,
+}
+
+/// Which watcher backend to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WatcherBackend {
+    /// Native, except on network filesystems or when the native watcher fails
+    Auto,
+    /// Native only
+    Native,
+    /// Polling only
+    Poll,
+}
+
+/// Tunable cost of file watching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatcherConfig {
+    pub backend: WatcherBackend,
+    /// How often polled files are checked
+    #[serde(rename = "pollIntervalMs")]
+    pub poll_interval_ms: u64,
+    /// Polling compares file contents instead of modification times. Catches writes
+    /// within the filesystem's timestamp granularity, but reads every polled file on
+    /// every interval.
+    #[serde(rename = "compareContents")]
+    pub compare_contents: bool,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            backend: WatcherBackend::Auto,
+            poll_interval_ms: 500,
+            compare_contents: false,
+        }
+    }
+}
+
+/// Backend actually watching a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BackendKind {
+    Native,
+    Poll,
+}
+
+type EventCallback = Arc<dyn Fn(notify::Result<Event>) + Send + Sync>;
+
+fn create_watcher(
+    kind: BackendKind,
+    config: &WatcherConfig,
+    callback: &EventCallback,
+) -> notify::Result<Box<dyn Watcher + Send>> {
+    let callback = callback.clone();
+    let handler = move |result: notify::Result<Event>| callback(result);
+    Ok(match kind {
+        BackendKind::Native => Box::new(RecommendedWatcher::new(handler, Config::default())?),
+        BackendKind::Poll => {
+            let interval = config.poll_interval_ms.max(MIN_POLL_INTERVAL_MS);
+            let notify_config = Config::default()
+                .with_poll_interval(Duration::from_millis(interval))
+                .with_compare_contents(config.compare_contents);
+            Box::new(PollWatcher::new(handler, notify_config)?)
+        }
+    })
+}
+
+/// Backend `config` picks for `path`
+fn backend_for(path: &Path, config: &WatcherConfig) -> BackendKind {
+    match config.backend {
+        WatcherBackend::Native => BackendKind::Native,
+        WatcherBackend::Poll => BackendKind::Poll,
+        WatcherBackend::Auto if is_network_filesystem(path) => BackendKind::Poll,
+        WatcherBackend::Auto => BackendKind::Native,
+    }
+}
+
+/// What a backend watches for an open file: the native backend watches the parent
+/// directory (a watch on the file itself ends when an atomic save replaces it), polling
+/// watches just the file
+fn watch_target(file: &Path, kind: BackendKind) -> PathBuf {
+    match (kind, file.parent()) {
+        (BackendKind::Native, Some(parent)) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => file.to_path_buf(),
+    }
+}
+
+/// Whether `path` is on a network (or FUSE, e.g. sshfs) filesystem, where native
+/// watchers don't see changes made by other machines
+#[cfg(target_os = "linux")]
+fn is_network_filesystem(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    const NETWORK_FILESYSTEMS: &[u32] = &[
+        0x6969,      // NFS
+        0x517b,      // SMB
+        0xff53_4d42, // CIFS
+        0xfe53_4d42, // SMB2
+        0x6573_5546, // FUSE
+        0x0102_1997, // 9P
+        0x5346_414f, // AFS
+        0x00c3_6400, // Ceph
+        0x4750_4653, // GPFS
+    ];
+
+    let Some(existing) = path.ancestors().find(|ancestor| ancestor.exists()) else {
+        return false;
+    };
+    let Ok(c_path) = std::ffi::CString::new(existing.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    NETWORK_FILESYSTEMS.contains(&(stat.f_type as u32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_filesystem(_path: &Path) -> bool {
+    false
+}
+
+/// Change type of the `index`th path of `event`, for a watched file
+fn file_change_type(event: &Event, index: usize, path: &Path) -> Option<&'static str> {
+    match event.kind {
+        EventKind::Remove(_) => Some("deleted"),
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => Some("deleted"),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if index == 0 => Some("deleted"),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Any)) if !path.exists() => Some("deleted"),
+        EventKind::Modify(_) | EventKind::Create(_) => Some("modified"),
+        // Ignore other event types (access, etc.)
+        _ => None,
+    }
+}
+
+/// Last known state of each watched file: the hash of its content, or None if it
+/// was deleted
+type KnownVersions = HashMap<PathBuf, Option<String>>;
+
+/// Whether a change of `path` is new to the editor. Returns the file's version to
+/// report, or None for the editor's own writes and repeated events.
+fn unseen_change(known: &mut KnownVersions, path: &Path, change_type: &str) -> Option<Option<String>> {
+    let version = if change_type == "deleted" {
+        None
+    } else {
+        // Unreadable mid-replace; the event completing the replace follows
+        Some(content_version(&std::fs::read(path).ok()?))
+    };
+    if known.get(path) == Some(&version) {
+        return None;
+    }
+    known.insert(path.to_path_buf(), version.clone());
+    Some(version)
+}
+
+/// The watchers of open files
+struct FileWatchers {
+    config: WatcherConfig,
+    callback: Option<EventCallback>,
+    native: Option<Box<dyn Watcher + Send>>,
+    poll: Option<Box<dyn Watcher + Send>>,
+    /// Watch target of each watched file, and its backend
+    files: HashMap<PathBuf, (PathBuf, BackendKind)>,
+    /// Watched files per watch target
+    targets: HashMap<(PathBuf, BackendKind), usize>,
+}
+
+impl FileWatchers {
+    fn watcher(&mut self, kind: BackendKind) -> Result<&mut Box<dyn Watcher + Send>, String> {
+        let callback = self.callback.clone().ok_or("File watcher not initialized")?;
+        let slot = match kind {
+            BackendKind::Native => &mut self.native,
+            BackendKind::Poll => &mut self.poll,
+        };
+        if slot.is_none() {
+            let watcher = create_watcher(kind, &self.config, &callback)
+                .map_err(|e| format!("Failed to create {:?} file watcher: {}", kind, e))?;
+            *slot = Some(watcher);
+        }
+        Ok(slot.as_mut().unwrap())
+    }
+
+    fn watch_with(&mut self, file: &Path, kind: BackendKind) -> Result<(), String> {
+        let target = watch_target(file, kind);
+        let key = (target.clone(), kind);
+        if !self.targets.contains_key(&key) {
+            self.watcher(kind)?
+                .watch(&target, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("Failed to watch '{}': {}", target.display(), e))?;
+        }
+        *self.targets.entry(key).or_insert(0) += 1;
+        self.files.insert(file.to_path_buf(), (target, kind));
+        Ok(())
+    }
+
+    fn watch(&mut self, file: &Path) -> Result<BackendKind, String> {
+        let kind = backend_for(file, &self.config);
+        match self.watch_with(file, kind) {
+            Ok(()) => Ok(kind),
+            Err(e) if kind == BackendKind::Native && self.config.backend == WatcherBackend::Auto => {
+                log::warn!("[FileWatcher] Native watch failed, polling instead: {}", e);
+                self.watch_with(file, BackendKind::Poll).map(|_| BackendKind::Poll)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn unwatch(&mut self, file: &Path) {
+        let Some((target, kind)) = self.files.remove(file) else {
+            return;
+        };
+        let key = (target, kind);
+        let remaining = self.targets.get_mut(&key).map(|count| {
+            *count -= 1;
+            *count
+        });
+        if remaining == Some(0) {
+            self.targets.remove(&key);
+            let watcher = match kind {
+                BackendKind::Native => self.native.as_mut(),
+                BackendKind::Poll => self.poll.as_mut(),
+            };
+            if let Some(watcher) = watcher {
+                let _ = watcher.unwatch(&key.0);
+            }
+        }
+    }
+
+    /// Drop every watch, e.g. before applying a new config
+    fn reset(&mut self) -> Vec<PathBuf> {
+        self.native = None;
+        self.poll = None;
+        self.targets.clear();
+        self.files.drain().map(|(file, _)| file).collect()
+    }
+}
+
+/// The watcher of the workspace directories, and its backend
+struct WorkspaceWatcher {
+    kind: BackendKind,
+    watcher: Box<dyn Watcher + Send>,
+}
+
+/// A running workspace watch. Dropping it stops the watcher, which ends its batching
+/// thread (the thread only holds weak references).
+struct WorkspaceWatch {
+    root: PathBuf,
+    _watcher: Arc<Mutex<WorkspaceWatcher>>,
+}
+
+/// Start and stop the directory watches `delta` asks for. Under `Auto`, reaching the
+/// native watch limit (inotify's `max_user_watches`) moves the whole workspace to polling.
+fn update_workspace_watches(
+    watcher: &Weak<Mutex<WorkspaceWatcher>>,
+    callback: &Weak<dyn Fn(notify::Result<Event>) + Send + Sync>,
+    config: &WatcherConfig,
+    delta: &mut TreeDelta,
+) {
+    let (watch, unwatch) = delta.take_watch_changes();
+    if watch.is_empty() && unwatch.is_empty() {
+        return;
+    }
+    let Some(watcher) = watcher.upgrade() else {
+        return;
+    };
+    let mut watcher = watcher.lock();
+    for dir in &unwatch {
+        // Native watches of deleted directories are already gone
+        let _ = watcher.watcher.unwatch(dir);
+    }
+    for dir in &watch {
+        let Err(e) = watcher.watcher.watch(dir, RecursiveMode::NonRecursive) else {
+            continue;
+        };
+        // Only the watch limit calls for polling; a directory may also vanish meanwhile
+        let at_limit = matches!(e.kind, notify::ErrorKind::MaxFilesWatch);
+        if !at_limit || watcher.kind != BackendKind::Native || config.backend != WatcherBackend::Auto {
+            log::warn!("[FileWatcher] Failed to watch '{}': {}", dir.display(), e);
+            continue;
+        }
+        log::warn!("[FileWatcher] Native workspace watch failed, polling instead: {}", e);
+        let Some(callback) = callback.upgrade() else {
+            return;
+        };
+        let poll = match create_watcher(BackendKind::Poll, config, &callback) {
+            Ok(poll) => poll,
+            Err(e) => {
+                log::error!("[FileWatcher] Failed to create workspace poll watcher: {}", e);
+                return;
+            }
+        };
+        *watcher = WorkspaceWatcher { kind: BackendKind::Poll, watcher: poll };
+        for dir in delta.watched_dirs() {
+            if let Err(e) = watcher.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::warn!("[FileWatcher] Failed to watch '{}': {}", dir.display(), e);
+            }
+        }
+        return;
+    }
 }
 
 /// File Watcher Manager - manages watched files and emits change events
 pub struct FileWatcherManager {
     watched_paths: Arc<Mutex<HashSet<PathBuf>>>,
-    watcher: Arc<Mutex<Option<PollWatcher>>>,
+    watchers: Mutex<FileWatchers>,
     app_handle: Arc<Mutex<Option<AppHandle>>>,
-    // Track last event time per path to debounce duplicates
-    last_event_times: Arc<Mutex<HashMap<PathBuf, u64>>>,
+    /// Last content hash the editor knows for each watched file, to drop self-writes
+    /// and duplicate events
+    known_versions: Arc<Mutex<KnownVersions>>,
+    /// Watch of the workspace directories, if any
+    workspace_watch: Mutex<Option<WorkspaceWatch>>,
 }
 
 impl FileWatcherManager {
     pub fn new() -> Self {
         Self {
             watched_paths: Arc::new(Mutex::new(HashSet::new())),
-            watcher: Arc::new(Mutex::new(None)),
+            watchers: Mutex::new(FileWatchers {
+                config: WatcherConfig::default(),
+                callback: None,
+                native: None,
+                poll: None,
+                files: HashMap::new(),
+                targets: HashMap::new(),
+            }),
             app_handle: Arc::new(Mutex::new(None)),
-            last_event_times: Arc::new(Mutex::new(HashMap::new())),
+            known_versions: Arc::new(Mutex::new(HashMap::new())),
+            workspace_watch: Mutex::new(None),
         }
     }
 
@@ -55,86 +403,103 @@ impl FileWatcherManager {
         drop(handle_guard);
 
         let watched_paths = self.watched_paths.clone();
-        let app_handle_for_callback = app_handle.clone();
-        let last_event_times = self.last_event_times.clone();
-
-        // Use PollWatcher with 500ms interval for fast, predictable detection
-        // This is more reliable than FSEvents on macOS which can have unpredictable delays
-        let config = Config::default()
-            .with_poll_interval(Duration::from_millis(500))
-            .with_compare_contents(true); // Compare file contents to detect changes
-
-        let watcher = PollWatcher::new(
-            move |result: Result<Event, notify::Error>| {
-                let now_ms = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-
-                match result {
-                    Ok(event) => {
-                        // Determine the change type based on the event kind
-                        let change_type = if event.kind.is_remove() {
-                            "deleted"
-                        } else if event.kind.is_modify() || event.kind.is_create() {
-                            "modified"
-                        } else {
-                            // Ignore other event types (access, etc.)
-                            return;
+        let known_versions = self.known_versions.clone();
+
+        let callback: EventCallback = Arc::new(move |result: notify::Result<Event>| {
+            let now_ms = now_ms();
+
+            match result {
+                Ok(event) => {
+                    let paths = watched_paths.lock();
+                    let mut known = known_versions.lock();
+
+                    for (index, path) in event.paths.iter().enumerate() {
+                        // Only emit for files we're actually watching (the native
+                        // backend reports every entry of their directories)
+                        if !paths.contains(path) {
+                            continue;
+                        }
+                        let Some(change_type) = file_change_type(&event, index, path) else {
+                            continue;
                         };
 
-                        let paths = watched_paths.lock();
-                        let mut last_times = last_event_times.lock();
-
-                        for path in &event.paths {
-                            // Only emit for files we're actually watching
-                            if paths.contains(path) {
-                                // Debounce: skip if we emitted for this path within last 100ms
-                                // (but don't debounce delete events)
-                                if change_type != "deleted" {
-                                    if let Some(&last_time) = last_times.get(path) {
-                                        if now_ms - last_time < 100 {
-                                            log::debug!("[FileWatcher] Skipping duplicate event for {} ({}ms since last)", path.display(), now_ms - last_time);
-                                            continue;
-                                        }
-                                    }
-                                }
-                                last_times.insert(path.clone(), now_ms);
-
-                                let change_event = FileChangeEvent {
-                                    path: path.to_string_lossy().to_string(),
-                                    change_type: change_type.to_string(),
-                                    detected_at: now_ms,
-                                };
-
-                                log::info!(
-                                    "[FileWatcher] File {} detected at {}ms, path: {}, event kind: {:?}",
-                                    change_type,
-                                    now_ms,
-                                    path.display(),
-                                    event.kind
-                                );
-
-                                if let Err(e) = app_handle_for_callback.emit("file-changed", change_event) {
-                                    log::error!("[FileWatcher] Failed to emit file-changed event: {}", e);
-                                }
-                            }
+                        // Skip the editor's own writes and repeated events for a change
+                        let Some(version) = unseen_change(&mut known, path, change_type) else {
+                            log::debug!("[FileWatcher] Skipping known content of {} ({:?})", path.display(), event.kind);
+                            continue;
+                        };
+
+                        let change_event = FileChangeEvent {
+                            path: path.to_string_lossy().to_string(),
+                            change_type: change_type.to_string(),
+                            detected_at: now_ms,
+                            version,
+                        };
+
+                        log::info!(
+                            "[FileWatcher] File {} detected at {}ms, path: {}, event kind: {:?}",
+                            change_type,
+                            now_ms,
+                            path.display(),
+                            event.kind
+                        );
+
+                        if let Err(e) = app_handle.emit("file-changed", change_event) {
+                            log::error!("[FileWatcher] Failed to emit file-changed event: {}", e);
                         }
                     }
-                    Err(e) => log::error!("[FileWatcher] File watcher error: {:?}", e),
                 }
-            },
-            config,
-        ).map_err(|e| format!("Failed to create file watcher: {}", e))?;
+                Err(e) => log::error!("[FileWatcher] File watcher error: {:?}", e),
+            }
+        });
 
-        *self.watcher.lock() = Some(watcher);
-        log::info!("[FileWatcher] File watcher initialized with 500ms poll interval");
+        let mut watchers = self.watchers.lock();
+        watchers.callback = Some(callback);
+        log::info!("[FileWatcher] File watcher initialized with {:?}", watchers.config);
         Ok(())
     }
 
-    /// Start watching a file for changes
-    pub fn watch_file(&self, path: &str) -> Result<(), String> {
-        let path_buf = PathBuf::from(path);
+    /// Apply a new watcher config, re-creating the watches of open files and of the
+    /// workspace
+    pub fn configure(&self, config: WatcherConfig) -> Result<(), String> {
+        let files = {
+            let mut watchers = self.watchers.lock();
+            if watchers.config == config {
+                return Ok(());
+            }
+            watchers.config = config;
+            watchers.reset()
+        };
+        log::info!("[FileWatcher] Watcher config set to {:?}", config);
+
+        let mut errors = Vec::new();
+        {
+            let mut watchers = self.watchers.lock();
+            for file in files {
+                if let Err(e) = watchers.watch(&file) {
+                    errors.push(e);
+                }
+            }
+        }
+
+        let workspace_root = self.workspace_watch.lock().take().map(|watch| watch.root);
+        if let Some(root) = workspace_root {
+            if let Err(e) = self.watch_workspace(&root) {
+                errors.push(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Start watching a file for changes. `path` is canonical (see `path_guard`), like
+    /// the paths of the events and of `record_write`.
+    pub fn watch_file(&self, path: &Path) -> Result<(), String> {
+        let path_buf = path.to_path_buf();
 
         // Check if already watching
         {
@@ -145,22 +510,38 @@ impl FileWatcherManager {
             paths.insert(path_buf.clone());
         }
 
+        // The content the editor opened isn't a change
+        if let Ok(bytes) = std::fs::read(&path_buf) {
+            self.known_versions.lock().insert(path_buf.clone(), Some(content_version(&bytes)));
+        }
+
         // Add to watcher
-        let mut watcher_guard = self.watcher.lock();
-        if let Some(ref mut watcher) = *watcher_guard {
-            watcher
-                .watch(&path_buf, RecursiveMode::NonRecursive)
-                .map_err(|e| format!("Failed to watch file '{}': {}", path, e))?;
-            log::info!("[FileWatcher] Started watching file: {}", path);
-        } else {
-            return Err("File watcher not initialized".to_string());
+        match self.watchers.lock().watch(&path_buf) {
+            Ok(kind) => {
+                log::info!("[FileWatcher] Started watching file ({:?}): {}", kind, path.display());
+                Ok(())
+            }
+            Err(e) => {
+                self.watched_paths.lock().remove(&path_buf);
+                Err(format!("Failed to watch file '{}': {}", path.display(), e))
+            }
+        }
+    }
+
+    /// Register content the editor is about to write to `path`, so the write isn't
+    /// reported back as an external change. Call before writing: the event can arrive
+    /// before the write call returns.
+    pub fn record_write(&self, path: &Path, version: &str) {
+        if self.watched_paths.lock().contains(path) {
+            self.known_versions
+                .lock()
+                .insert(path.to_path_buf(), Some(version.to_string()));
         }
-        Ok(())
     }
 
     /// Stop watching a file
-    pub fn unwatch_file(&self, path: &str) -> Result<(), String> {
-        let path_buf = PathBuf::from(path);
+    pub fn unwatch_file(&self, path: &Path) -> Result<(), String> {
+        let path_buf = path.to_path_buf();
 
         // Remove from tracked paths
         {
@@ -171,21 +552,106 @@ impl FileWatcherManager {
             }
         }
 
-        // Remove from last event times
-        {
-            let mut last_times = self.last_event_times.lock();
-            last_times.remove(&path_buf);
-        }
+        // Remove from known versions
+        self.known_versions.lock().remove(&path_buf);
 
         // Remove from watcher
-        let mut watcher_guard = self.watcher.lock();
-        if let Some(ref mut watcher) = *watcher_guard {
-            let _ = watcher.unwatch(&path_buf);
-            log::info!("[FileWatcher] Stopped watching file: {}", path);
+        self.watchers.lock().unwatch(&path_buf);
+        log::info!("[FileWatcher] Stopped watching file: {}", path.display());
+        Ok(())
+    }
+
+    /// Watch the whole workspace at `root` for created, renamed, modified and deleted
+    /// entries, replacing any previous workspace watch
+    pub fn watch_workspace(&self, root: &Path) -> Result<(), String> {
+        let app_handle = self
+            .app_handle
+            .lock()
+            .clone()
+            .ok_or("File watcher not initialized")?;
+        let root = std::fs::canonicalize(root)
+            .map_err(|e| format!("Failed to resolve workspace '{}': {}", root.display(), e))?;
+
+        let mut workspace_watch = self.workspace_watch.lock();
+        if workspace_watch.as_ref().is_some_and(|watch| watch.root == root) {
+            return Ok(());
         }
+        // Stop the previous watch before starting the new one
+        *workspace_watch = None;
+
+        let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+        let sender = Mutex::new(sender);
+        let callback: EventCallback = Arc::new(move |result| {
+            let _ = sender.lock().send(result);
+        });
+
+        let config = self.watchers.lock().config;
+        let kind = backend_for(&root, &config);
+        let (kind, watcher) = match create_watcher(kind, &config, &callback) {
+            Err(e) if kind == BackendKind::Native && config.backend == WatcherBackend::Auto => {
+                log::warn!("[FileWatcher] Native workspace watcher failed, polling instead: {}", e);
+                (BackendKind::Poll, create_watcher(BackendKind::Poll, &config, &callback))
+            }
+            result => (kind, result),
+        };
+        let watcher = watcher.map_err(|e| format!("Failed to create workspace watcher: {}", e))?;
+        let watcher = Arc::new(Mutex::new(WorkspaceWatcher { kind, watcher }));
+
+        // The thread scans the tree and adds the directory watches, off the caller
+        let thread_root = root.clone();
+        let thread_watcher = Arc::downgrade(&watcher);
+        let thread_callback = Arc::downgrade(&callback);
+        drop(callback);
+        std::thread::Builder::new()
+            .name("workspace-watcher".to_string())
+            .spawn(move || {
+                let mut delta = TreeDelta::new(thread_root);
+                update_workspace_watches(&thread_watcher, &thread_callback, &config, &mut delta);
+                let mut first_change: Option<Instant> = None;
+                loop {
+                    let received = match first_change {
+                        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                        Some(first) => {
+                            let remaining = WORKSPACE_MAX_DELAY.saturating_sub(first.elapsed());
+                            receiver.recv_timeout(WORKSPACE_DEBOUNCE.min(remaining))
+                        }
+                    };
+                    match received {
+                        Ok(Ok(event)) => {
+                            delta.push_event(&event);
+                            update_workspace_watches(&thread_watcher, &thread_callback, &config, &mut delta);
+                            if first_change.is_none() && !delta.is_empty() {
+                                first_change = Some(Instant::now());
+                            }
+                        }
+                        Ok(Err(e)) => log::error!("[FileWatcher] Workspace watcher error: {:?}", e),
+                        Err(RecvTimeoutError::Timeout) => {
+                            first_change = None;
+                            if let Some(batch) = delta.take_batch(now_ms()) {
+                                log::debug!("[FileWatcher] Workspace changed: {} changes", batch.changes.len());
+                                if let Err(e) = app_handle.emit("workspace-changed", batch) {
+                                    log::error!("[FileWatcher] Failed to emit workspace-changed event: {}", e);
+                                }
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start workspace watcher thread: {}", e))?;
+
+        log::info!("[FileWatcher] Started watching workspace ({:?}): {}", kind, root.display());
+        *workspace_watch = Some(WorkspaceWatch { root, _watcher: watcher });
         Ok(())
     }
 
+    /// Stop the workspace-root watch
+    pub fn unwatch_workspace(&self) {
+        if let Some(watch) = self.workspace_watch.lock().take() {
+            log::info!("[FileWatcher] Stopped watching workspace: {}", watch.root.display());
+        }
+    }
+
     /// Get list of currently watched files
     #[allow(dead_code)]
     pub fn get_watched_files(&self) -> Vec<String> {
@@ -202,3 +668,142 @@ impl Default for FileWatcherManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("file-watcher-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_native_watch_sees_atomic_saves() {
+        let dir = temp_dir("atomic");
+        let file = dir.join("main.u");
+        fs::write(&file, "a = 1").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let callback: EventCallback = Arc::new(move |result| {
+            let _ = sender.lock().send(result);
+        });
+        let mut watchers = FileWatchers {
+            config: WatcherConfig { backend: WatcherBackend::Native, ..WatcherConfig::default() },
+            callback: Some(callback),
+            native: None,
+            poll: None,
+            files: HashMap::new(),
+            targets: HashMap::new(),
+        };
+        assert_eq!(watchers.watch(&file), Ok(BackendKind::Native));
+
+        // Two atomic saves: the second is only seen if the first didn't end the watch
+        for content in ["a = 2", "a = 3"] {
+            crate::file_io::write_atomic(&file, content.as_bytes()).unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let seen = std::iter::from_fn(|| receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok())
+                .filter_map(Result::ok)
+                .any(|event| {
+                    event.paths.iter().enumerate().any(|(index, path)| {
+                        *path == file && file_change_type(&event, index, path) == Some("modified")
+                    })
+                });
+            assert!(seen, "no event for {}", content);
+        }
+
+        watchers.unwatch(&file);
+        assert!(watchers.targets.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unseen_change_skips_known_content() {
+        let dir = temp_dir("unseen");
+        let file = dir.join("main.u");
+        let mut known = KnownVersions::new();
+
+        // The editor's own write
+        fs::write(&file, "a = 1").unwrap();
+        known.insert(file.clone(), Some(content_version(b"a = 1")));
+        assert_eq!(unseen_change(&mut known, &file, "modified"), None);
+
+        // An external write is reported once, with its version
+        fs::write(&file, "a = 2").unwrap();
+        let version = Some(content_version(b"a = 2"));
+        assert_eq!(unseen_change(&mut known, &file, "modified"), Some(version));
+        assert_eq!(unseen_change(&mut known, &file, "modified"), None);
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(unseen_change(&mut known, &file, "modified"), None);
+        assert_eq!(unseen_change(&mut known, &file, "deleted"), Some(None));
+        assert_eq!(unseen_change(&mut known, &file, "deleted"), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// CPU cost of watching 200 open files with each backend. Run with
+    /// `cargo test --release watcher_cpu_cost -- --ignored --nocapture`.
+    #[cfg(unix)]
+    #[test]
+    #[ignore]
+    fn bench_watcher_cpu_cost() {
+        const FILES: usize = 200;
+        const DURATION: Duration = Duration::from_secs(10);
+
+        fn cpu_time() -> Duration {
+            let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+            unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+            let micros = |time: libc::timeval| time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64;
+            Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+        }
+
+        let dir = temp_dir("bench");
+        let files: Vec<PathBuf> = (0..FILES)
+            .map(|i| {
+                let file = dir.join(format!("file{}.u", i));
+                fs::write(&file, "x = 1\n".repeat(1000)).unwrap();
+                file
+            })
+            .collect();
+
+        let configs = [
+            ("native", BackendKind::Native, WatcherConfig::default()),
+            ("poll 500ms, mtime", BackendKind::Poll, WatcherConfig::default()),
+            (
+                "poll 500ms, contents",
+                BackendKind::Poll,
+                WatcherConfig { compare_contents: true, ..WatcherConfig::default() },
+            ),
+            (
+                "poll 2000ms, mtime",
+                BackendKind::Poll,
+                WatcherConfig { poll_interval_ms: 2000, ..WatcherConfig::default() },
+            ),
+        ];
+        println!("CPU cost of watching {} files for {:?}:", FILES, DURATION);
+        for (name, kind, config) in configs {
+            let callback: EventCallback = Arc::new(|_| {});
+            let mut watcher = create_watcher(kind, &config, &callback).unwrap();
+            let targets: HashSet<PathBuf> = files.iter().map(|file| watch_target(file, kind)).collect();
+            for target in &targets {
+                watcher.watch(target, RecursiveMode::NonRecursive).unwrap();
+            }
+
+            let start = cpu_time();
+            std::thread::sleep(DURATION);
+            let used = cpu_time() - start;
+            drop(watcher);
+            println!(
+                "  {:<22} {:>8.1} ms CPU ({:.2}% of one core)",
+                name,
+                used.as_secs_f64() * 1000.0,
+                used.as_secs_f64() / DURATION.as_secs_f64() * 100.0
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
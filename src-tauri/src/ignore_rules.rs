@@ -0,0 +1,220 @@
+//! Ignore Rules - Which workspace entries the editor leaves out
+//!
+//! This module provides:
+//! - `IgnoreRules::load`: the rules of a workspace - hidden entries, the directories
+//!   in `IGNORED_DIRS`, and the patterns of the root `.gitignore` and `.ignore`
+//! - `IgnoreRules::load_nested`: adds the `.gitignore`/`.ignore` files of the
+//!   directories down to a listed directory, each applying below its own directory
+//!   (`IgnoreRules::add_directory` adds a single directory's files)
+//! - `IgnoreRules::is_ignored`: whether a workspace-relative path is ignored, itself
+//!   or through one of its parent directories
+//! - `IgnoreRules::is_gitignored`: the same, by ignore-file patterns only
+//!
+//! Ignore-file support covers what workspaces use in practice: `#` comments, `!`
+//! negation, trailing `/` for directories, and patterns anchored with a `/`
+//! (otherwise matched against the entry name at any depth). Deeper files take
+//! precedence, as in git.
+
+use glob::{MatchOptions, Pattern};
+use std::fs;
+use std::path::{Component, Path};
+
+/// Directories always ignored, besides hidden ones. Build output (`target`, `dist`,
+/// ...) is left to the ignore files, since a workspace may keep sources there.
+pub const IGNORED_DIRS: &[&str] = &["node_modules"];
+
+/// Ignore files read in each directory, in order of precedence
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    /// Workspace-relative directory of the ignore file ("" at the root)
+    base: String,
+    pattern: Pattern,
+    negated: bool,
+    directory_only: bool,
+    /// Matched against the whole relative path instead of the entry name
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    fn parse(base: &str, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.trim_start_matches('/');
+        let pattern = Pattern::new(line).ok()?;
+        Some(Self { base: base.to_string(), pattern, negated, directory_only, anchored })
+    }
+
+    fn matches(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        let relative = if self.base.is_empty() {
+            relative
+        } else {
+            match relative.strip_prefix(self.base.as_str()).and_then(|rest| rest.strip_prefix('/')) {
+                Some(rest) => rest,
+                None => return false,
+            }
+        };
+        let subject = if self.anchored { relative } else { name };
+        self.pattern.matches_with(subject, MATCH_OPTIONS)
+    }
+}
+
+fn component_names(relative: &Path) -> Vec<String> {
+    relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Ignore rules of one workspace
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    /// Rules of the workspace at `root` (hidden, `IGNORED_DIRS`, root ignore files)
+    pub fn load(root: &Path) -> Self {
+        let mut rules = Self::default();
+        rules.add_ignore_files(root, "");
+        rules
+    }
+
+    /// Add the ignore files of the directories below `root` down to `dir` (inclusive)
+    pub fn load_nested(&mut self, root: &Path, dir: &Path) {
+        let Ok(relative) = dir.strip_prefix(root) else {
+            return;
+        };
+        let mut base = String::new();
+        for component in relative.components() {
+            if let Component::Normal(name) = component {
+                if !base.is_empty() {
+                    base.push('/');
+                }
+                base.push_str(&name.to_string_lossy());
+                let path = root.join(&base);
+                self.add_ignore_files(&path, &base);
+            }
+        }
+    }
+
+    /// Add the ignore files of `dir` alone, for rules already holding its parents'
+    pub fn add_directory(&mut self, root: &Path, dir: &Path) {
+        if let Ok(relative) = dir.strip_prefix(root) {
+            self.add_ignore_files(dir, &component_names(relative).join("/"));
+        }
+    }
+
+    fn add_ignore_files(&mut self, dir: &Path, base: &str) {
+        for file in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(dir.join(file)) {
+                self.add_patterns(base, &contents);
+            }
+        }
+    }
+
+    fn add_patterns(&mut self, base: &str, contents: &str) {
+        self.patterns
+            .extend(contents.lines().filter_map(|line| IgnorePattern::parse(base, line)));
+    }
+
+    /// Whether `relative` (relative to the workspace root) is ignored. Parent
+    /// directories of `relative` are checked too, as git does.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let names = component_names(relative);
+
+        let last = names.len().saturating_sub(1);
+        names.iter().enumerate().any(|(i, name)| {
+            name.starts_with('.') || ((is_dir || i < last) && IGNORED_DIRS.contains(&name.as_str()))
+        }) || self.is_gitignored(relative, is_dir)
+    }
+
+    /// Whether `relative` is ignored by the patterns of the ignore files alone
+    pub fn is_gitignored(&self, relative: &Path, is_dir: bool) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let names = component_names(relative);
+
+        let mut prefix = String::new();
+        for (i, name) in names.iter().enumerate() {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(name);
+            if self.matches_gitignore(&prefix, name, is_dir || i + 1 < names.len()) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Last matching pattern wins
+    fn matches_gitignore(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(relative, name, is_dir))
+            .is_some_and(|pattern| !pattern.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_rules() {
+        let mut rules = IgnoreRules::default();
+        rules.add_patterns("", "# build output\n*.log\n!keep.log\n/out/\ndocs/*.tmp\n");
+        // A nested ignore file applies below its directory and overrides the root
+        rules.add_patterns("lib", "/gen/\n!debug.log\n");
+        let ignored = |path: &str, is_dir| rules.is_ignored(Path::new(path), is_dir);
+
+        assert!(!ignored("lib/main.u", false));
+        // Hidden entries and always-ignored directories
+        assert!(ignored(".git/HEAD", false));
+        assert!(ignored("lib/.scratch.u", false));
+        assert!(ignored("node_modules/x/index.js", false));
+        assert!(!ignored("build.u", false));
+        assert!(!ignored("build/main.u", false));
+        // Unanchored patterns match at any depth, negation wins when last
+        assert!(ignored("a/b/debug.log", false));
+        assert!(!ignored("a/keep.log", false));
+        // Anchored and directory-only patterns
+        assert!(ignored("out", true));
+        assert!(ignored("out/main.u", false));
+        assert!(!ignored("out", false));
+        assert!(!ignored("lib/out/main.u", false));
+        assert!(ignored("docs/a.tmp", false));
+        assert!(!ignored("docs/sub/a.tmp", false));
+        assert!(ignored("lib/gen/a.u", false));
+        assert!(!ignored("gen/a.u", false));
+        assert!(!ignored("lib/debug.log", false));
+        assert!(ignored("debug.log", false));
+    }
+}
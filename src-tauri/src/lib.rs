@@ -1,10 +1,27 @@
+mod codebase_lock;
 mod commands;
+mod directory_listing;
+mod file_history;
+mod file_io;
+mod file_operations;
+mod file_transfer;
 mod file_watcher;
+mod ignore_rules;
+mod lsp_inspector;
+mod lsp_middleware;
 mod mcp_client;
+mod path_guard;
 mod port_utils;
+mod problems;
+mod pty_session;
+mod transcript;
+mod tree_delta;
 mod ucm_api;
+mod ucm_locator;
 mod lsp_proxy;
 mod ucm_pty;
+mod ucm_session;
+mod workspace_search;
 
 use commands::{AppState, LSPConnection};
 
@@ -44,18 +61,35 @@ pub fn run() {
       commands::get_dependents,
       commands::check_ucm_connection,
       commands::configure_ucm,
+      commands::pick_workspace_folder,
+      commands::open_workspace,
+      commands::close_workspace,
       commands::read_file,
       commands::write_file,
       commands::list_directory,
+      commands::list_directory_page,
+      commands::search_workspace,
+      commands::cancel_workspace_search,
+      commands::replace_in_workspace,
       commands::create_file,
       commands::delete_file,
       commands::rename_file,
+      commands::copy_path,
+      commands::duplicate_path,
+      commands::move_paths,
       commands::file_exists,
+      commands::undo_file_operation,
+      commands::redo_file_operation,
+      // File history
+      commands::list_file_history,
+      commands::diff_file_history,
+      commands::restore_file_history,
       commands::switch_project_branch,
       commands::ucm_update,
       commands::ucm_typecheck,
       commands::ucm_run_tests,
       commands::ucm_run,
+      commands::run_transcript,
       commands::view_definitions,
       commands::lsp_connect,
       commands::lsp_disconnect,
@@ -63,16 +97,50 @@ pub fn run() {
       // UCM PTY commands for integrated terminal
       commands::ucm_pty_spawn,
       commands::ucm_pty_write,
+      commands::ucm_pty_exec,
       commands::ucm_pty_resize,
+      commands::ucm_pty_attach,
       commands::ucm_pty_get_context,
+      commands::ucm_pty_get_prompt_state,
       commands::ucm_pty_switch_context,
       commands::ucm_pty_kill,
+      commands::ucm_pty_interrupt,
+      // UCM session management
+      commands::list_ucm_sessions,
+      commands::close_ucm_session,
+      // Codebase lock recovery
+      commands::get_codebase_lock_holder,
+      commands::kill_codebase_lock_holder,
+      commands::connect_to_lock_holder,
+      commands::open_degraded_session,
+      // UCM binary discovery
+      commands::set_ucm_path,
+      commands::get_ucm_info,
+      // Shell PTY commands (general terminal)
+      commands::pty_spawn,
+      commands::pty_write,
+      commands::pty_resize,
+      commands::pty_attach,
+      commands::pty_kill,
+      commands::pty_list,
       // Service port management
       commands::get_service_ports,
       // File watcher commands
       commands::init_file_watcher,
+      commands::configure_file_watcher,
       commands::watch_file,
       commands::unwatch_file,
+      commands::watch_workspace,
+      commands::unwatch_workspace,
+      // LSP traffic inspector commands
+      commands::lsp_inspector_start,
+      commands::lsp_inspector_stop,
+      commands::lsp_inspector_get_entries,
+      commands::lsp_inspector_clear,
+      commands::lsp_inspector_export,
+      // Workspace problems commands
+      commands::get_problems,
+      commands::scan_workspace_problems,
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
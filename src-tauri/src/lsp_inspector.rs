@@ -0,0 +1,443 @@
+//! LSP Inspector - Captures JSON-RPC traffic flowing through the LSP proxy
+//!
+//! This module provides:
+//! - An opt-in ring buffer of full LSP messages with timestamps and direction
+//! - Live `lsp-traffic` events while capture is enabled
+//! - Export of a captured session to a `.jsonl` file (one entry per line)
+//! - In tests, a replay server that plays a recorded session back as a fake UCM LSP
+//!   upstream, so proxy behaviour can be regression tested without a running UCM
+
+use crate::ucm_session::emit_session_event;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+/// Default number of messages kept in the ring buffer
+pub const DEFAULT_INSPECTOR_CAPACITY: usize = 5000;
+
+/// Direction of a captured LSP message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrafficDirection {
+    /// Monaco -> UCM
+    ClientToServer,
+    /// UCM -> Monaco
+    ServerToClient,
+}
+
+/// A single captured LSP message (also the line format of exported `.jsonl` files)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LspTrafficEntry {
+    /// Monotonic sequence number, survives ring buffer eviction
+    pub seq: u64,
+    /// Capture time (milliseconds since epoch)
+    pub timestamp: u64,
+    pub direction: TrafficDirection,
+    /// Full JSON-RPC message body, without the Content-Length header
+    pub message: String,
+}
+
+/// LSP Inspector - records proxy traffic into a bounded ring buffer when enabled
+pub struct LspInspector {
+    enabled: AtomicBool,
+    next_seq: AtomicU64,
+    capacity: Mutex<usize>,
+    entries: Mutex<VecDeque<LspTrafficEntry>>,
+    /// App handle and session id used to emit tagged events
+    event_target: Mutex<Option<(AppHandle, String)>>,
+}
+
+impl LspInspector {
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            next_seq: AtomicU64::new(1),
+            capacity: Mutex::new(DEFAULT_INSPECTOR_CAPACITY),
+            entries: Mutex::new(VecDeque::new()),
+            event_target: Mutex::new(None),
+        }
+    }
+
+    /// Set the app handle used to emit `lsp-traffic` events, tagged with `session_id`
+    pub fn set_app_handle(&self, app_handle: AppHandle, session_id: &str) {
+        *self.event_target.lock() = Some((app_handle, session_id.to_string()));
+    }
+
+    /// Start capturing traffic, optionally changing the ring buffer capacity
+    pub fn start(&self, capacity: Option<usize>) {
+        if let Some(capacity) = capacity {
+            let capacity = capacity.max(1);
+            *self.capacity.lock() = capacity;
+
+            let mut entries = self.entries.lock();
+            while entries.len() > capacity {
+                entries.pop_front();
+            }
+        }
+        self.enabled.store(true, Ordering::SeqCst);
+        log::info!("[LspInspector] Capture started (capacity {})", *self.capacity.lock());
+    }
+
+    /// Stop capturing traffic (already captured entries are kept)
+    pub fn stop(&self) {
+        self.enabled.store(false, Ordering::SeqCst);
+        log::info!("[LspInspector] Capture stopped");
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Record a message if capture is enabled. Cheap no-op otherwise.
+    pub fn record(&self, direction: TrafficDirection, message: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let entry = LspTrafficEntry {
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            direction,
+            message: message.to_string(),
+        };
+
+        {
+            let capacity = *self.capacity.lock();
+            let mut entries = self.entries.lock();
+            while entries.len() >= capacity {
+                entries.pop_front();
+            }
+            entries.push_back(entry.clone());
+        }
+
+        if let Some((ref handle, ref session_id)) = *self.event_target.lock() {
+            emit_session_event(handle, session_id, "lsp-traffic", entry);
+        }
+    }
+
+    /// Get a snapshot of all captured entries, oldest first
+    pub fn entries(&self) -> Vec<LspTrafficEntry> {
+        self.entries.lock().iter().cloned().collect()
+    }
+
+    /// Drop all captured entries
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    /// Export captured entries to a `.jsonl` file, returning the number written
+    pub fn export_jsonl(&self, path: &Path) -> Result<usize, String> {
+        let entries = self.entries();
+
+        let mut file = fs::File::create(path)
+            .map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
+
+        for entry in &entries {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| format!("Failed to serialize entry {}: {}", entry.seq, e))?;
+            writeln!(file, "{}", line)
+                .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+        }
+
+        log::info!("[LspInspector] Exported {} entries to {}", entries.len(), path.display());
+        Ok(entries.len())
+    }
+}
+
+impl Default for LspInspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Replay of recorded sessions, used by the proxy and middleware tests
+#[cfg(test)]
+pub(crate) mod replay {
+    use super::*;
+    use crate::lsp_proxy::LspProxy;
+    use anyhow::{Context, Result};
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Load a recorded session from a `.jsonl` file produced by `export_jsonl`
+    pub fn load_recording(path: &Path) -> Result<Vec<LspTrafficEntry>, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read recording '{}': {}", path.display(), e))?;
+
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("Invalid entry on line {}: {}", idx + 1, e))
+            })
+            .collect()
+    }
+
+    /// Fake UCM LSP upstream that replays a recorded session
+    ///
+    /// Every connection replays the recording from the start. Server messages recorded
+    /// before the first client message are sent on connect. After that, each incoming
+    /// client message consumes the next recorded client message and is answered with
+    /// the server messages that followed it in the recording. Response ids are rewritten
+    /// when the live request id differs from the recorded one.
+    pub struct LspReplayServer {
+        entries: Vec<LspTrafficEntry>,
+    }
+
+    impl LspReplayServer {
+        pub fn new(entries: Vec<LspTrafficEntry>) -> Self {
+            Self { entries }
+        }
+
+        /// Bind to an ephemeral port on localhost and start serving in the background
+        ///
+        /// # Returns
+        /// The port the replay server is listening on
+        pub async fn start(self: Arc<Self>) -> Result<u16> {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .context("Failed to bind LSP replay server")?;
+            let port = listener.local_addr()?.port();
+
+            log::info!("[LspReplay] Replaying {} entries on port {}", self.entries.len(), port);
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let server = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = server.replay(stream).await {
+                            log::error!("[LspReplay] Replay error: {}", e);
+                        }
+                    });
+                }
+            });
+
+            Ok(port)
+        }
+
+        async fn replay(&self, stream: TcpStream) -> Result<()> {
+            let (mut read, mut write) = stream.into_split();
+            let mut cursor = self.send_server_messages(0, None, &mut write).await?;
+
+            while let Ok(incoming) = LspProxy::read_lsp_message(&mut read).await {
+                let next_client = self.entries[cursor..]
+                    .iter()
+                    .position(|e| e.direction == TrafficDirection::ClientToServer);
+
+                let Some(offset) = next_client else {
+                    log::debug!("[LspReplay] Recording exhausted, ignoring client message");
+                    continue;
+                };
+
+                let expected = &self.entries[cursor + offset];
+                let id_remap = match (json_rpc_id(&expected.message), json_rpc_id(&incoming)) {
+                    (Some(recorded), Some(live)) if recorded != live => Some((recorded, live)),
+                    _ => None,
+                };
+
+                cursor = self
+                    .send_server_messages(cursor + offset + 1, id_remap.as_ref(), &mut write)
+                    .await?;
+            }
+
+            Ok(())
+        }
+
+        /// Send recorded server messages starting at `from` until the next client message.
+        /// Returns the index of that client message (or the end of the recording).
+        async fn send_server_messages(
+            &self,
+            from: usize,
+            id_remap: Option<&(Value, Value)>,
+            write: &mut tokio::net::tcp::OwnedWriteHalf,
+        ) -> Result<usize> {
+            let mut cursor = from;
+
+            while let Some(entry) = self.entries.get(cursor) {
+                if entry.direction == TrafficDirection::ClientToServer {
+                    break;
+                }
+
+                let message = match id_remap {
+                    Some((recorded, live)) => remap_response_id(&entry.message, recorded, live),
+                    None => entry.message.clone(),
+                };
+
+                let framed = format!("Content-Length: {}\r\n\r\n{}", message.len(), message);
+                write
+                    .write_all(framed.as_bytes())
+                    .await
+                    .context("Failed to write replayed message")?;
+                cursor += 1;
+            }
+
+            write.flush().await.context("Failed to flush replayed messages")?;
+            Ok(cursor)
+        }
+    }
+
+    /// A recorded message, for building recordings in tests
+    pub fn entry(seq: u64, direction: TrafficDirection, message: &str) -> LspTrafficEntry {
+        LspTrafficEntry {
+            seq,
+            timestamp: 0,
+            direction,
+            message: message.to_string(),
+        }
+    }
+
+    /// Serve `proxy` on an ephemeral port, bound before this returns so clients can
+    /// connect right away. Returns the port.
+    pub async fn spawn_proxy(proxy: LspProxy) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(Arc::new(proxy).serve(listener));
+        port
+    }
+
+    /// Extract the JSON-RPC `id` of a message, if it has one
+    pub(super) fn json_rpc_id(message: &str) -> Option<Value> {
+        serde_json::from_str::<Value>(message)
+            .ok()
+            .and_then(|v| v.get("id").cloned())
+    }
+
+    /// Rewrite the `id` of a response if it matches the recorded request id
+    pub(super) fn remap_response_id(message: &str, recorded: &Value, live: &Value) -> String {
+        match serde_json::from_str::<Value>(message) {
+            Ok(mut value) if value.get("id") == Some(recorded) && value.get("method").is_none() => {
+                value["id"] = live.clone();
+                value.to_string()
+            }
+            _ => message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replay::*;
+    use super::*;
+    use crate::lsp_proxy::LspProxy;
+    use futures::{SinkExt, StreamExt};
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+    #[test]
+    fn test_record_ignored_when_disabled() {
+        let inspector = LspInspector::new();
+        inspector.record(TrafficDirection::ClientToServer, "{}");
+        assert!(inspector.entries().is_empty());
+    }
+
+    #[test]
+    fn test_ring_buffer_evicts_oldest() {
+        let inspector = LspInspector::new();
+        inspector.start(Some(2));
+        inspector.record(TrafficDirection::ClientToServer, "1");
+        inspector.record(TrafficDirection::ServerToClient, "2");
+        inspector.record(TrafficDirection::ClientToServer, "3");
+
+        let entries = inspector.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].message, "2");
+        assert_eq!(entries[1].message, "3");
+        assert_eq!(entries[1].seq, 3);
+    }
+
+    #[test]
+    fn test_export_and_load_roundtrip() {
+        let inspector = LspInspector::new();
+        inspector.start(None);
+        inspector.record(TrafficDirection::ClientToServer, r#"{"id":1,"method":"initialize"}"#);
+        inspector.record(TrafficDirection::ServerToClient, r#"{"id":1,"result":{}}"#);
+
+        let path = std::env::temp_dir().join(format!("lsp-inspector-{}.jsonl", std::process::id()));
+        assert_eq!(inspector.export_jsonl(&path).unwrap(), 2);
+
+        let loaded = load_recording(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].direction, TrafficDirection::ClientToServer);
+        assert_eq!(loaded[1].message, r#"{"id":1,"result":{}}"#);
+    }
+
+    #[test]
+    fn test_remap_response_id() {
+        let remapped = remap_response_id(r#"{"id":1,"result":null}"#, &Value::from(1), &Value::from(7));
+        assert_eq!(json_rpc_id(&remapped), Some(Value::from(7)));
+
+        // Server-initiated requests keep their own id
+        let request = r#"{"id":1,"method":"window/workDoneProgress/create"}"#;
+        assert_eq!(remap_response_id(request, &Value::from(1), &Value::from(7)), request);
+    }
+
+    #[tokio::test]
+    async fn test_replay_through_proxy() {
+        let recording = vec![
+            entry(1, TrafficDirection::ClientToServer, r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+            entry(2, TrafficDirection::ServerToClient, r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":{}}}"#),
+            entry(3, TrafficDirection::ClientToServer, r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{}}"#),
+            entry(4, TrafficDirection::ServerToClient, r#"{"jsonrpc":"2.0","method":"window/logMessage","params":{}}"#),
+            entry(5, TrafficDirection::ServerToClient, r#"{"jsonrpc":"2.0","id":2,"result":null}"#),
+        ];
+
+        let upstream_port = Arc::new(LspReplayServer::new(recording.clone()))
+            .start()
+            .await
+            .unwrap();
+
+        let inspector = Arc::new(LspInspector::new());
+        inspector.start(None);
+
+        let ws_port = spawn_proxy(LspProxy::new(
+            0,
+            "127.0.0.1".to_string(),
+            upstream_port,
+            "test-token".to_string(),
+            inspector.clone(),
+        ))
+        .await;
+
+        let (mut ws, _) = connect_async(format!("ws://127.0.0.1:{}/?token=test-token", ws_port)).await.unwrap();
+
+        let mut received = Vec::new();
+        for client in recording.iter().filter(|e| e.direction == TrafficDirection::ClientToServer) {
+            ws.send(Message::Text(client.message.clone())).await.unwrap();
+        }
+        while received.len() < 3 {
+            match ws.next().await {
+                Some(Ok(Message::Text(text))) => received.push(text),
+                other => panic!("Unexpected WebSocket message: {:?}", other),
+            }
+        }
+
+        let expected: Vec<_> = recording
+            .iter()
+            .filter(|e| e.direction == TrafficDirection::ServerToClient)
+            .map(|e| e.message.clone())
+            .collect();
+        assert_eq!(received, expected);
+
+        let captured = inspector.entries();
+        assert_eq!(captured.len(), recording.len());
+        assert_eq!(
+            captured.iter().filter(|e| e.direction == TrafficDirection::ClientToServer).count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! LSP Middleware - Post-processes UCM LSP responses inside the proxy
+//!
+//! The proxy remembers the method of every client request it forwards. When the
+//! matching response comes back from UCM, each middleware that handles that method
+//! gets a chance to rewrite the response before it reaches Monaco. An
+//! `LspNotificationObserver` passively observes server notifications instead (they
+//! are forwarded unchanged).
+//!
+//! Built-in middleware:
+//! - `CodebaseEnricher` - fills in docs for `textDocument/hover` and signatures/docs
+//!   for `completionItem/resolve` using the UCM codebase API
+
+use crate::ucm_api::{DefinitionSummary, UCMApiClient};
+use crate::ucm_pty::UCMContext;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// A response post-processor plugged into the `LspProxy`
+pub trait LspMiddleware: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Whether this middleware wants to see responses to `method`
+    fn handles(&self, method: &str) -> bool;
+
+    /// Rewrite `response` (the full JSON-RPC response) in place.
+    /// `request` is the original client request that produced it.
+    fn process_response<'a>(
+        &'a self,
+        method: &'a str,
+        request: &'a Value,
+        response: &'a mut Value,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// A passive observer of server->client notifications plugged into the `LspProxy`
+pub trait LspNotificationObserver: Send + Sync {
+    /// Observe a notification such as `textDocument/publishDiagnostics`
+    fn observe_notification(&self, method: &str, params: &Value);
+}
+
+/// Enriches hovers and completion items with docs and signatures from the codebase API
+pub struct CodebaseEnricher {
+    client: UCMApiClient,
+    /// Project/branch detected from the UCM prompt, shared with the PTY manager
+    context: Arc<Mutex<UCMContext>>,
+}
+
+impl CodebaseEnricher {
+    pub fn new(client: UCMApiClient, context: Arc<Mutex<UCMContext>>) -> Self {
+        Self { client, context }
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Option<DefinitionSummary>> {
+        let (project, branch) = {
+            let ctx = self.context.lock();
+            match (ctx.project.clone(), ctx.branch.clone()) {
+                (Some(project), Some(branch)) => (project, branch),
+                _ => return Ok(None),
+            }
+        };
+
+        self.client.get_definition(&project, &branch, name, true).await
+    }
+
+    async fn enrich_hover(&self, response: &mut Value) -> Result<()> {
+        let Some(result) = response.get_mut("result").filter(|r| !r.is_null()) else {
+            return Ok(());
+        };
+
+        let Some(name) = hover_symbol_name(&result["contents"]) else {
+            return Ok(());
+        };
+
+        let Some(docs) = self
+            .lookup(&name)
+            .await?
+            .and_then(|def| def.doc.as_ref().and_then(doc_to_markdown))
+        else {
+            return Ok(());
+        };
+
+        append_hover_markdown(result, &docs);
+        Ok(())
+    }
+
+    async fn enrich_completion_item(&self, response: &mut Value) -> Result<()> {
+        let Some(item) = response.get_mut("result").filter(|r| r.is_object()) else {
+            return Ok(());
+        };
+
+        let needs_detail = item.get("detail").and_then(|d| d.as_str()).map_or(true, str::is_empty);
+        let needs_docs = item.get("documentation").map_or(true, Value::is_null);
+        if !needs_detail && !needs_docs {
+            return Ok(());
+        }
+
+        let Some(label) = item.get("label").and_then(|l| l.as_str()).map(str::to_string) else {
+            return Ok(());
+        };
+
+        let Some(def) = self.lookup(&label).await? else {
+            return Ok(());
+        };
+
+        if needs_detail {
+            if let Some(signature) = def.signature {
+                item["detail"] = Value::String(signature);
+            }
+        }
+
+        if needs_docs {
+            if let Some(docs) = def.doc.as_ref().and_then(doc_to_markdown) {
+                item["documentation"] = json!({ "kind": "markdown", "value": docs });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl LspMiddleware for CodebaseEnricher {
+    fn name(&self) -> &'static str {
+        "codebase-enricher"
+    }
+
+    fn handles(&self, method: &str) -> bool {
+        matches!(method, "textDocument/hover" | "completionItem/resolve")
+    }
+
+    fn process_response<'a>(
+        &'a self,
+        method: &'a str,
+        _request: &'a Value,
+        response: &'a mut Value,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match method {
+                "textDocument/hover" => self.enrich_hover(response).await,
+                "completionItem/resolve" => self.enrich_completion_item(response).await,
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+/// Flatten hover contents (MarkupContent, MarkedString or an array of them) to text
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(hover_text).collect::<Vec<_>>().join("\n"),
+        Value::Object(obj) => obj
+            .get("value")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Extract the symbol name from a UCM hover, which starts with a signature like
+/// "```unison\nList.map : (a ->{e} b) -> [a] ->{e} [b]\n```"
+fn hover_symbol_name(contents: &Value) -> Option<String> {
+    let text = hover_text(contents);
+    let signature = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("```"))?;
+
+    let (name, _) = signature.split_once(" : ")?;
+    let name = name.trim();
+
+    if name.is_empty() || name.contains(' ') {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Append markdown to a hover result, normalising its contents to MarkupContent
+fn append_hover_markdown(result: &mut Value, markdown: &str) {
+    let contents = &result["contents"];
+    let existing = match contents {
+        Value::Object(obj) if obj.contains_key("language") => {
+            format!("```{}\n{}\n```", obj["language"].as_str().unwrap_or_default(), hover_text(contents))
+        }
+        _ => hover_text(contents),
+    };
+
+    // UCM sometimes already includes the docs
+    if existing.contains(markdown.trim()) {
+        return;
+    }
+
+    let value = if existing.trim().is_empty() {
+        markdown.to_string()
+    } else {
+        format!("{}\n\n---\n\n{}", existing, markdown)
+    };
+
+    result["contents"] = json!({ "kind": "markdown", "value": value });
+}
+
+/// Render the Doc AST returned by `getDefinition` (`termDocs`) as markdown
+///
+/// UCM returns docs as `[[name, hash, docAst], ...]`; only the first doc is rendered.
+pub fn doc_to_markdown(doc: &Value) -> Option<String> {
+    let root = doc.as_array()?.first()?.as_array()?.get(2)?;
+    let mut out = String::new();
+    render_doc(root, 1, &mut out);
+
+    let trimmed = out.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+fn render_doc(doc: &Value, section_level: usize, out: &mut String) {
+    let Some(tag) = doc.get("tag").and_then(|t| t.as_str()) else {
+        if let Some(s) = doc.as_str() {
+            out.push_str(s);
+        }
+        return;
+    };
+    let contents = &doc["contents"];
+
+    match tag {
+        "Word" => out.push_str(contents.as_str().unwrap_or_default()),
+        "Code" => {
+            out.push('`');
+            render_doc(contents, section_level, out);
+            out.push('`');
+        }
+        "CodeBlock" => {
+            let lang = contents[0].as_str().unwrap_or("unison");
+            out.push_str(&format!("\n```{}\n", lang));
+            render_doc(&contents[1], section_level, out);
+            out.push_str("\n```\n");
+        }
+        "Bold" => wrap(contents, "**", section_level, out),
+        "Italic" => wrap(contents, "_", section_level, out),
+        "Strikethrough" => wrap(contents, "~~", section_level, out),
+        "Blockquote" => {
+            out.push_str("\n> ");
+            render_doc(contents, section_level, out);
+            out.push('\n');
+        }
+        "Blankline" => out.push_str("\n\n"),
+        "Linebreak" => out.push('\n'),
+        "SectionBreak" => out.push_str("\n\n---\n\n"),
+        "Paragraph" | "Span" | "Join" => render_joined(contents, " ", section_level, out),
+        "BulletedList" => {
+            for item in contents.as_array().into_iter().flatten() {
+                out.push_str("\n- ");
+                render_doc(item, section_level, out);
+            }
+            out.push('\n');
+        }
+        "NumberedList" => {
+            let start = contents[0].as_u64().unwrap_or(1);
+            for (idx, item) in contents[1].as_array().into_iter().flatten().enumerate() {
+                out.push_str(&format!("\n{}. ", start + idx as u64));
+                render_doc(item, section_level, out);
+            }
+            out.push('\n');
+        }
+        "Section" => {
+            out.push_str(&format!("\n\n{} ", "#".repeat(section_level.min(6))));
+            render_doc(&contents[0], section_level, out);
+            out.push_str("\n\n");
+            match &contents[1] {
+                Value::Array(items) => render_joined_blocks(items, section_level + 1, out),
+                item => render_doc(item, section_level + 1, out),
+            }
+        }
+        "UntitledSection" => {
+            if let Some(items) = contents.as_array() {
+                render_joined_blocks(items, section_level, out);
+            }
+        }
+        "Special" => render_special(contents, out),
+        _ => match contents {
+            Value::Array(items) => {
+                for item in items {
+                    render_doc(item, section_level, out);
+                }
+            }
+            Value::Null => {}
+            other => render_doc(other, section_level, out),
+        },
+    }
+}
+
+fn wrap(contents: &Value, marker: &str, section_level: usize, out: &mut String) {
+    out.push_str(marker);
+    render_doc(contents, section_level, out);
+    out.push_str(marker);
+}
+
+fn render_joined(contents: &Value, separator: &str, section_level: usize, out: &mut String) {
+    match contents {
+        Value::Array(items) => {
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(separator);
+                }
+                render_doc(item, section_level, out);
+            }
+        }
+        other => render_doc(other, section_level, out),
+    }
+}
+
+fn render_joined_blocks(items: &[Value], section_level: usize, out: &mut String) {
+    for item in items {
+        render_doc(item, section_level, out);
+        out.push_str("\n\n");
+    }
+}
+
+/// Special forms (examples, links, evals) carry syntax segments; render their text
+fn render_special(special: &Value, out: &mut String) {
+    let tag = special.get("tag").and_then(|t| t.as_str()).unwrap_or_default();
+    let text = segments_text(&special["contents"]);
+
+    match tag {
+        "ExampleBlock" | "Source" | "FoldedSource" => {
+            out.push_str(&format!("\n```unison\n{}\n```\n", text.trim_end()))
+        }
+        _ if !text.is_empty() => out.push_str(&format!("`{}`", text)),
+        _ => {}
+    }
+}
+
+/// Concatenate the `segment` strings of (possibly nested) syntax segment arrays
+fn segments_text(value: &Value) -> String {
+    match value {
+        Value::Array(items) => items.iter().map(segments_text).collect(),
+        Value::Object(obj) => match obj.get("segment").and_then(|s| s.as_str()) {
+            Some(segment) => segment.to_string(),
+            None => obj.get("contents").map(segments_text).unwrap_or_default(),
+        },
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp_inspector::replay::{entry, spawn_proxy, LspReplayServer};
+    use crate::lsp_inspector::{LspInspector, TrafficDirection};
+    use crate::lsp_proxy::LspProxy;
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+    fn word(w: &str) -> Value {
+        json!({ "tag": "Word", "contents": w })
+    }
+
+    #[test]
+    fn test_hover_symbol_name() {
+        let contents = json!({
+            "kind": "markdown",
+            "value": "```unison\nList.map : (a ->{e} b) -> [a] ->{e} [b]\n```"
+        });
+        assert_eq!(hover_symbol_name(&contents), Some("List.map".to_string()));
+        assert_eq!(hover_symbol_name(&json!("no signature here")), None);
+    }
+
+    #[test]
+    fn test_doc_to_markdown() {
+        let doc = json!([[
+            "List.map",
+            "#abc",
+            {
+                "tag": "UntitledSection",
+                "contents": [
+                    { "tag": "Paragraph", "contents": [word("Maps"), word("a"), { "tag": "Code", "contents": word("List") }] },
+                    { "tag": "BulletedList", "contents": [word("one"), word("two")] }
+                ]
+            }
+        ]]);
+
+        let md = doc_to_markdown(&doc).unwrap();
+        assert!(md.starts_with("Maps a `List`"));
+        assert!(md.contains("- one\n- two"));
+        assert_eq!(doc_to_markdown(&json!([])), None);
+    }
+
+    #[test]
+    fn test_append_hover_markdown() {
+        let mut result = json!({ "contents": { "kind": "markdown", "value": "sig" } });
+        append_hover_markdown(&mut result, "docs");
+        assert_eq!(result["contents"]["value"], "sig\n\n---\n\ndocs");
+
+        // Already present docs are not duplicated
+        append_hover_markdown(&mut result, "docs");
+        assert_eq!(result["contents"]["value"], "sig\n\n---\n\ndocs");
+    }
+
+    struct SuffixHover;
+
+    impl LspMiddleware for SuffixHover {
+        fn name(&self) -> &'static str {
+            "suffix-hover"
+        }
+
+        fn handles(&self, method: &str) -> bool {
+            method == "textDocument/hover"
+        }
+
+        fn process_response<'a>(
+            &'a self,
+            _method: &'a str,
+            _request: &'a Value,
+            response: &'a mut Value,
+        ) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                append_hover_markdown(&mut response["result"], "enriched");
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_applies_middleware_to_matching_responses() {
+        let recording = vec![
+            entry(1, TrafficDirection::ClientToServer, r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":{}}"#),
+            entry(2, TrafficDirection::ServerToClient, r#"{"jsonrpc":"2.0","id":1,"result":{"contents":"sig"}}"#),
+            entry(3, TrafficDirection::ClientToServer, r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{}}"#),
+            entry(4, TrafficDirection::ServerToClient, r#"{"jsonrpc":"2.0","id":2,"result":null}"#),
+        ];
+
+        let upstream_port = Arc::new(LspReplayServer::new(recording.clone()))
+            .start()
+            .await
+            .unwrap();
+
+        let inspector = Arc::new(LspInspector::new());
+        inspector.start(None);
+
+        let proxy = LspProxy::new(
+            0,
+            "127.0.0.1".to_string(),
+            upstream_port,
+            "test-token".to_string(),
+            inspector.clone(),
+        )
+        .with_middleware(Arc::new(SuffixHover));
+        let ws_port = spawn_proxy(proxy).await;
+
+        let (mut ws, _) = connect_async(format!("ws://127.0.0.1:{}/?token=test-token", ws_port)).await.unwrap();
+        ws.send(Message::Text(recording[0].message.clone())).await.unwrap();
+        ws.send(Message::Text(recording[2].message.clone())).await.unwrap();
+
+        let mut responses = Vec::new();
+        while responses.len() < 2 {
+            if let Some(Ok(Message::Text(text))) = ws.next().await {
+                responses.push(serde_json::from_str::<Value>(&text).unwrap());
+            }
+        }
+        // The enriched response is processed in its own task and may arrive second
+        responses.sort_by_key(|response| response["id"].as_u64());
+
+        assert_eq!(responses[0]["result"]["contents"]["value"], "sig\n\n---\n\nenriched");
+        assert_eq!(responses[1], serde_json::from_str::<Value>(&recording[3].message).unwrap());
+
+        // The inspector shows the response the client received
+        let captured = inspector.entries();
+        assert!(captured
+            .iter()
+            .any(|e| e.direction == TrafficDirection::ServerToClient && e.message.contains("enriched")));
+    }
+}
@@ -1,11 +1,59 @@
+use crate::lsp_inspector::{LspInspector, TrafficDirection};
+use crate::lsp_middleware::{LspMiddleware, LspNotificationObserver};
 use anyhow::{Context, Result};
 use futures::{SinkExt, StreamExt};
-use log::{error, info};
+use log::{debug, error, info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Mutex;
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::{accept_hdr_async, tungstenite::Message};
+
+/// Origins allowed to open the proxy WebSocket: the Tauri webview on each
+/// platform. Clients that send no Origin header (non-browser tools) are still
+/// subject to the token check.
+const ALLOWED_ORIGINS: &[&str] = &[
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+
+/// The Vite dev server, which serves the webview in debug builds only
+#[cfg(debug_assertions)]
+const DEV_ORIGINS: &[&str] = &["http://localhost:5173", "http://127.0.0.1:5173"];
+
+#[cfg(not(debug_assertions))]
+const DEV_ORIGINS: &[&str] = &[];
+
+/// Query parameter carrying the proxy token (`ws://127.0.0.1:<port>/?token=...`)
+const TOKEN_QUERY_PARAM: &str = "token";
+
+/// Subprotocol prefix carrying the proxy token (`Sec-WebSocket-Protocol: token.<secret>`)
+const TOKEN_SUBPROTOCOL_PREFIX: &str = "token.";
+
+/// Maximum time a middleware may spend on a single response before the
+/// original response is forwarded unchanged
+const MIDDLEWARE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Time after which a tracked request whose response never arrived is forgotten
+const PENDING_TTL: Duration = Duration::from_secs(60);
+
+/// A client request whose response some middleware wants to post-process
+struct PendingRequest {
+    method: String,
+    request: Value,
+    sent_at: Instant,
+}
+
+/// Client requests awaiting a response, keyed by JSON-RPC id
+type PendingRequests = Arc<Mutex<HashMap<String, PendingRequest>>>;
+
+type WsWriter = Arc<Mutex<futures::stream::SplitSink<tokio_tungstenite::WebSocketStream<TcpStream>, Message>>>;
 
 /// LSP Proxy Server that bridges WebSocket (for Monaco) to TCP (for UCM LSP)
 ///
@@ -13,32 +61,70 @@ use tokio_tungstenite::{accept_async, tungstenite::Message};
 /// Monaco (WebSocket) <-> Proxy (this) <-> UCM LSP Server (TCP)
 ///
 /// This proxy:
-/// 1. Accepts WebSocket connections from Monaco/browser
-/// 2. Maintains a TCP connection to UCM's LSP server (localhost:5757)
+/// 1. Accepts WebSocket connections from Monaco/browser that present the per-launch
+///    token and come from an allowed origin
+/// 2. Maintains a TCP connection to UCM's LSP server (localhost, port allocated at UCM spawn)
 /// 3. Bidirectionally forwards all LSP messages (JSON-RPC over Content-Length headers)
+/// 4. Hands every message to the `LspInspector` (which ignores it unless capture is on),
+///    server messages as forwarded to the client
+/// 5. Runs responses through any registered `LspMiddleware` before forwarding them.
+///    Each such response is processed in its own task, so a slow middleware never
+///    holds up the rest of the server's traffic.
+/// 6. Shows server notifications to any registered `LspNotificationObserver`
 pub struct LspProxy {
     ws_port: u16,
     lsp_host: String,
     lsp_port: u16,
+    /// Secret every WebSocket client must present during the handshake
+    auth_token: String,
+    inspector: Arc<LspInspector>,
+    middlewares: Arc<Vec<Arc<dyn LspMiddleware>>>,
+    observers: Arc<Vec<Arc<dyn LspNotificationObserver>>>,
 }
 
 impl LspProxy {
-    pub fn new(ws_port: u16, lsp_host: String, lsp_port: u16) -> Self {
+    pub fn new(
+        ws_port: u16,
+        lsp_host: String,
+        lsp_port: u16,
+        auth_token: String,
+        inspector: Arc<LspInspector>,
+    ) -> Self {
         Self {
             ws_port,
             lsp_host,
             lsp_port,
+            auth_token,
+            inspector,
+            middlewares: Arc::new(Vec::new()),
+            observers: Arc::new(Vec::new()),
         }
     }
 
+    /// Register a middleware that post-processes responses (applied in registration order)
+    pub fn with_middleware(mut self, middleware: Arc<dyn LspMiddleware>) -> Self {
+        Arc::make_mut(&mut self.middlewares).push(middleware);
+        self
+    }
+
+    /// Register an observer of server notifications
+    pub fn with_notification_observer(mut self, observer: Arc<dyn LspNotificationObserver>) -> Self {
+        Arc::make_mut(&mut self.observers).push(observer);
+        self
+    }
+
     /// Start the WebSocket proxy server
     pub async fn start(self: Arc<Self>) -> Result<()> {
         let addr = format!("127.0.0.1:{}", self.ws_port);
         let listener = TcpListener::bind(&addr)
             .await
             .context(format!("Failed to bind WebSocket server to {}", addr))?;
+        self.serve(listener).await
+    }
 
-        info!("LSP WebSocket proxy listening on {}", addr);
+    /// Serve WebSocket clients on an already bound `listener`
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        info!("LSP WebSocket proxy listening on {}", listener.local_addr()?);
         info!("Will forward to UCM LSP at {}:{}", self.lsp_host, self.lsp_port);
 
         loop {
@@ -60,11 +146,14 @@ impl LspProxy {
     }
 
     /// Handle a single WebSocket connection
+    #[allow(clippy::result_large_err)]
     async fn handle_connection(&self, stream: TcpStream) -> Result<()> {
-        // Upgrade to WebSocket
-        let ws_stream = accept_async(stream)
-            .await
-            .context("Failed to accept WebSocket")?;
+        // Upgrade to WebSocket, rejecting unknown origins and missing/invalid tokens
+        let ws_stream = accept_hdr_async(stream, |req: &Request, response: Response| {
+            self.authorize_handshake(req, response)
+        })
+        .await
+        .context("Failed to accept WebSocket")?;
 
         info!("WebSocket handshake completed");
 
@@ -110,12 +199,18 @@ impl LspProxy {
 
         let ws_write = Arc::new(Mutex::new(ws_write));
         let lsp_write = Arc::new(Mutex::new(lsp_write));
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
 
         // Spawn task to forward WebSocket -> LSP
         let ws_to_lsp = {
             let lsp_write = lsp_write.clone();
+            let inspector = self.inspector.clone();
+            let middlewares = self.middlewares.clone();
+            let pending = pending.clone();
             tokio::spawn(async move {
-                if let Err(e) = Self::forward_ws_to_lsp(ws_read, lsp_write).await {
+                if let Err(e) =
+                    Self::forward_ws_to_lsp(ws_read, lsp_write, inspector, middlewares, pending).await
+                {
                     error!("WebSocket->LSP forwarding error: {}", e);
                 }
             })
@@ -124,8 +219,14 @@ impl LspProxy {
         // Spawn task to forward LSP -> WebSocket
         let lsp_to_ws = {
             let ws_write = ws_write.clone();
+            let inspector = self.inspector.clone();
+            let middlewares = self.middlewares.clone();
+            let observers = self.observers.clone();
+            let pending = pending.clone();
             tokio::spawn(async move {
-                if let Err(e) = Self::forward_lsp_to_ws(lsp_read, ws_write).await {
+                if let Err(e) =
+                    Self::forward_lsp_to_ws(lsp_read, ws_write, inspector, middlewares, observers, pending).await
+                {
                     error!("LSP->WebSocket forwarding error: {}", e);
                 }
             })
@@ -140,16 +241,71 @@ impl LspProxy {
         Ok(())
     }
 
+    /// Check the Origin header and proxy token of a WebSocket handshake.
+    /// If the token was sent as a subprotocol, it is echoed back as browsers require.
+    #[allow(clippy::result_large_err)]
+    fn authorize_handshake(&self, req: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+        if let Some(origin) = req.headers().get("Origin") {
+            let origin = origin.to_str().unwrap_or_default();
+            if !ALLOWED_ORIGINS.contains(&origin) && !DEV_ORIGINS.contains(&origin) {
+                warn!("Rejected LSP WebSocket connection from origin '{}'", origin);
+                return Err(reject(StatusCode::FORBIDDEN, "Origin not allowed"));
+            }
+        }
+
+        let query_token = req.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == TOKEN_QUERY_PARAM)
+                .map(|(_, value)| value.to_string())
+        });
+
+        let protocol_token = req
+            .headers()
+            .get("Sec-WebSocket-Protocol")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|protocols| {
+                protocols
+                    .split(',')
+                    .map(str::trim)
+                    .find(|p| p.starts_with(TOKEN_SUBPROTOCOL_PREFIX))
+                    .map(str::to_string)
+            });
+
+        if let Some(token) = query_token {
+            if constant_time_eq(token.as_bytes(), self.auth_token.as_bytes()) {
+                return Ok(response);
+            }
+        } else if let Some(protocol) = protocol_token {
+            let token = &protocol[TOKEN_SUBPROTOCOL_PREFIX.len()..];
+            if constant_time_eq(token.as_bytes(), self.auth_token.as_bytes()) {
+                if let Ok(value) = HeaderValue::from_str(&protocol) {
+                    response.headers_mut().insert("Sec-WebSocket-Protocol", value);
+                }
+                return Ok(response);
+            }
+        }
+
+        warn!("Rejected LSP WebSocket connection with missing or invalid token");
+        Err(reject(StatusCode::UNAUTHORIZED, "Missing or invalid token"))
+    }
+
     /// Forward messages from WebSocket to LSP (Monaco -> UCM)
     async fn forward_ws_to_lsp(
         mut ws_read: futures::stream::SplitStream<tokio_tungstenite::WebSocketStream<TcpStream>>,
         lsp_write: Arc<Mutex<tokio::net::tcp::OwnedWriteHalf>>,
+        inspector: Arc<LspInspector>,
+        middlewares: Arc<Vec<Arc<dyn LspMiddleware>>>,
+        pending: PendingRequests,
     ) -> Result<()> {
         while let Some(msg) = ws_read.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
-                    info!("WS->LSP: Received message of {} bytes", text.len());
-                    info!("WS->LSP: Message: {}", &text[..text.len().min(200)]);
+                    // Full messages are available through the LSP inspector
+                    debug!("WS->LSP: Received message of {} bytes", text.len());
+                    inspector.record(TrafficDirection::ClientToServer, &text);
+                    Self::track_request(&text, &middlewares, &pending).await;
 
                     // LSP uses Content-Length header format
                     let content_length = text.len();
@@ -161,7 +317,7 @@ impl LspProxy {
                         .await
                         .context("Failed to write to LSP")?;
                     writer.flush().await.context("Failed to flush LSP write")?;
-                    info!("WS->LSP: Forwarded {} bytes to LSP", lsp_message.len());
+                    debug!("WS->LSP: Forwarded {} bytes to LSP", lsp_message.len());
                 }
                 Ok(Message::Close(_)) => {
                     info!("WebSocket closed by client");
@@ -182,22 +338,38 @@ impl LspProxy {
     /// Forward messages from LSP to WebSocket (UCM -> Monaco)
     async fn forward_lsp_to_ws(
         mut lsp_read: tokio::net::tcp::OwnedReadHalf,
-        ws_write: Arc<Mutex<futures::stream::SplitSink<tokio_tungstenite::WebSocketStream<TcpStream>, Message>>>,
+        ws_write: WsWriter,
+        inspector: Arc<LspInspector>,
+        middlewares: Arc<Vec<Arc<dyn LspMiddleware>>>,
+        observers: Arc<Vec<Arc<dyn LspNotificationObserver>>>,
+        pending: PendingRequests,
     ) -> Result<()> {
         loop {
             // Read LSP message (Content-Length header format)
             match Self::read_lsp_message(&mut lsp_read).await {
                 Ok(content) => {
-                    info!("LSP->WS: Received {} bytes from LSP", content.len());
-                    info!("LSP->WS: Message: {}", &content[..content.len().min(200)]);
+                    debug!("LSP->WS: Received {} bytes from LSP", content.len());
+                    Self::observe_notification(&content, &observers);
 
-                    // Forward to WebSocket as text message
-                    let mut writer = ws_write.lock().await;
-                    writer
-                        .send(Message::Text(content.clone()))
-                        .await
-                        .context("Failed to send to WebSocket")?;
-                    info!("LSP->WS: Forwarded {} bytes to WebSocket", content.len());
+                    // Messages are recorded as the client receives them, after the middlewares
+                    let Some((response, tracked)) = Self::take_pending(&content, &middlewares, &pending).await
+                    else {
+                        inspector.record(TrafficDirection::ServerToClient, &content);
+                        Self::send_to_ws(&ws_write, content).await?;
+                        continue;
+                    };
+
+                    // Responses are matched by id, so the enriched one may overtake others
+                    let middlewares = middlewares.clone();
+                    let inspector = inspector.clone();
+                    let ws_write = ws_write.clone();
+                    tokio::spawn(async move {
+                        let content = Self::apply_middlewares(response, tracked, &middlewares).await;
+                        inspector.record(TrafficDirection::ServerToClient, &content);
+                        if let Err(e) = Self::send_to_ws(&ws_write, content).await {
+                            debug!("LSP->WS: Dropped processed response: {}", e);
+                        }
+                    });
                 }
                 Err(e) => {
                     if e.to_string().contains("unexpected end of file") {
@@ -212,8 +384,129 @@ impl LspProxy {
         Ok(())
     }
 
+    /// Forward one message to the WebSocket as a text message
+    async fn send_to_ws(ws_write: &WsWriter, content: String) -> Result<()> {
+        let len = content.len();
+        ws_write
+            .lock()
+            .await
+            .send(Message::Text(content))
+            .await
+            .context("Failed to send to WebSocket")?;
+        debug!("LSP->WS: Forwarded {} bytes to WebSocket", len);
+        Ok(())
+    }
+
+    /// Remember client requests whose responses some middleware wants to post-process,
+    /// and forget those the client cancelled or that never got a response
+    async fn track_request(
+        text: &str,
+        middlewares: &[Arc<dyn LspMiddleware>],
+        pending: &PendingRequests,
+    ) {
+        if middlewares.is_empty() {
+            return;
+        }
+
+        let Ok(request) = serde_json::from_str::<Value>(text) else {
+            return;
+        };
+        let Some(method) = request.get("method").and_then(|m| m.as_str()) else {
+            return;
+        };
+
+        let mut pending = pending.lock().await;
+        let now = Instant::now();
+        pending.retain(|_, tracked| now.duration_since(tracked.sent_at) < PENDING_TTL);
+
+        if method == "$/cancelRequest" {
+            if let Some(id) = request.get("params").and_then(|params| params.get("id")) {
+                pending.remove(&id.to_string());
+            }
+            return;
+        }
+
+        if let Some(id) = request.get("id") {
+            if middlewares.iter().any(|m| m.handles(method)) {
+                let tracked = PendingRequest {
+                    method: method.to_string(),
+                    request: request.clone(),
+                    sent_at: now,
+                };
+                pending.insert(id.to_string(), tracked);
+            }
+        }
+    }
+
+    /// Let observers see server notifications (messages with a method but no id)
+    fn observe_notification(content: &str, observers: &[Arc<dyn LspNotificationObserver>]) {
+        if observers.is_empty() || !content.contains("\"method\"") {
+            return;
+        }
+
+        let Ok(message) = serde_json::from_str::<Value>(content) else {
+            return;
+        };
+
+        if message.get("id").is_some() {
+            return;
+        }
+
+        if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
+            for observer in observers {
+                observer.observe_notification(method, &message["params"]);
+            }
+        }
+    }
+
+    /// The parsed response and its request, if `content` answers a tracked request
+    async fn take_pending(
+        content: &str,
+        middlewares: &[Arc<dyn LspMiddleware>],
+        pending: &PendingRequests,
+    ) -> Option<(Value, PendingRequest)> {
+        if middlewares.is_empty() || pending.lock().await.is_empty() {
+            return None;
+        }
+
+        let response = serde_json::from_str::<Value>(content).ok()?;
+
+        // Server-initiated requests also carry an id, but have a method
+        if response.get("method").is_some() {
+            return None;
+        }
+
+        let key = response.get("id")?.to_string();
+        let tracked = pending.lock().await.remove(&key)?;
+        Some((response, tracked))
+    }
+
+    /// Run a response through the middlewares registered for its request method
+    async fn apply_middlewares(
+        mut response: Value,
+        tracked: PendingRequest,
+        middlewares: &[Arc<dyn LspMiddleware>],
+    ) -> String {
+        let PendingRequest { method, request, .. } = tracked;
+        for middleware in middlewares.iter().filter(|m| m.handles(&method)) {
+            let mut candidate = response.clone();
+            match tokio::time::timeout(
+                MIDDLEWARE_TIMEOUT,
+                middleware.process_response(&method, &request, &mut candidate),
+            )
+            .await
+            {
+                Ok(Ok(())) => response = candidate,
+                Ok(Err(e)) => warn!("LSP middleware '{}' failed on {}: {}", middleware.name(), method, e),
+                Err(_) => warn!("LSP middleware '{}' timed out on {}", middleware.name(), method),
+            }
+        }
+
+        response.to_string()
+    }
+
     /// Read a single LSP message from TCP stream (handles Content-Length header)
-    async fn read_lsp_message(stream: &mut tokio::net::tcp::OwnedReadHalf) -> Result<String> {
+    pub(crate) async fn read_lsp_message(stream: &mut tokio::net::tcp::OwnedReadHalf) -> Result<String> {
         // Read headers until we find Content-Length and reach \r\n\r\n
         let mut headers = Vec::new();
         let mut buffer = [0u8; 1];
@@ -254,3 +547,157 @@ impl LspProxy {
         String::from_utf8(content).context("Invalid UTF-8 in message content")
     }
 }
+
+/// Generate the per-launch secret required to connect to the LSP proxy
+pub fn generate_proxy_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare two secrets without short-circuiting on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Build an HTTP error response for a rejected handshake
+fn reject(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp_inspector::replay::spawn_proxy;
+    use tokio_tungstenite::connect_async;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Error as WsError;
+
+    async fn start_proxy(token: &str) -> u16 {
+        // Upstream port is never reached: every test below fails or succeeds at the handshake
+        spawn_proxy(LspProxy::new(
+            0,
+            "127.0.0.1".to_string(),
+            1,
+            token.to_string(),
+            Arc::new(LspInspector::new()),
+        ))
+        .await
+    }
+
+    fn status_of(result: Result<impl Sized, WsError>) -> Option<StatusCode> {
+        match result {
+            Err(WsError::Http(response)) => Some(response.status()),
+            _ => None,
+        }
+    }
+
+    struct DefinitionMiddleware;
+
+    impl LspMiddleware for DefinitionMiddleware {
+        fn name(&self) -> &'static str {
+            "definition"
+        }
+
+        fn handles(&self, method: &str) -> bool {
+            method == "textDocument/definition"
+        }
+
+        fn process_response<'a>(
+            &'a self,
+            _method: &'a str,
+            _request: &'a Value,
+            _response: &'a mut Value,
+        ) -> futures::future::BoxFuture<'a, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_requests_are_evicted() {
+        let middlewares: Vec<Arc<dyn LspMiddleware>> = vec![Arc::new(DefinitionMiddleware)];
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let track = |text: &'static str| LspProxy::track_request(text, &middlewares, &pending);
+
+        track(r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/definition","params":{}}"#).await;
+        track(r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{}}"#).await;
+        track(r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{}}"#).await;
+        assert_eq!(pending.lock().await.len(), 2);
+
+        track(r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":1}}"#).await;
+        assert!(!pending.lock().await.contains_key("1"));
+
+        // A request whose response never arrived is dropped on the next tracked message
+        pending.lock().await.get_mut("3").unwrap().sent_at = Instant::now() - PENDING_TTL;
+        track(r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/definition","params":{}}"#).await;
+        let keys: Vec<String> = pending.lock().await.keys().cloned().collect();
+        assert_eq!(keys, vec!["4".to_string()]);
+    }
+
+    #[test]
+    fn test_generate_proxy_token() {
+        let a = generate_proxy_token();
+        let b = generate_proxy_token();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_requires_token() {
+        let port = start_proxy("abc123").await;
+
+        let missing = connect_async(format!("ws://127.0.0.1:{}", port)).await;
+        assert_eq!(status_of(missing), Some(StatusCode::UNAUTHORIZED));
+
+        let wrong = connect_async(format!("ws://127.0.0.1:{}/?token=nope", port)).await;
+        assert_eq!(status_of(wrong), Some(StatusCode::UNAUTHORIZED));
+
+        let ok = connect_async(format!("ws://127.0.0.1:{}/?token=abc123", port)).await;
+        assert!(ok.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_accepts_token_subprotocol() {
+        let port = start_proxy("abc123").await;
+
+        let mut request = format!("ws://127.0.0.1:{}", port).into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", HeaderValue::from_static("token.abc123"));
+
+        let (_, response) = connect_async(request).await.unwrap();
+        assert_eq!(
+            response.headers().get("Sec-WebSocket-Protocol").unwrap(),
+            "token.abc123"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_unknown_origin() {
+        let port = start_proxy("abc123").await;
+
+        let mut request = format!("ws://127.0.0.1:{}/?token=abc123", port)
+            .into_client_request()
+            .unwrap();
+        request
+            .headers_mut()
+            .insert("Origin", HeaderValue::from_static("https://evil.example"));
+        assert_eq!(status_of(connect_async(request).await), Some(StatusCode::FORBIDDEN));
+
+        let mut request = format!("ws://127.0.0.1:{}/?token=abc123", port)
+            .into_client_request()
+            .unwrap();
+        request
+            .headers_mut()
+            .insert("Origin", HeaderValue::from_static("tauri://localhost"));
+        assert!(connect_async(request).await.is_ok());
+    }
+}
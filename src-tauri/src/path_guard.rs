@@ -0,0 +1,268 @@
+//! Path Guard - Confines file commands to the open workspace
+//!
+//! This module provides:
+//! - `WorkspaceGuard`: the root of the workspace opened with the `open_workspace`
+//!   command, held in `AppState`. File commands resolve their paths through it and
+//!   fail while no workspace is open. A `workspace` passed by the frontend can only
+//!   narrow the root, never widen it.
+//! - Trusted workspaces: only folders the user picked in the native folder dialog,
+//!   shown by the backend (`pick_workspace_folder`), can be opened. They are kept in
+//!   `TRUSTED_FILE` so recent workspaces reopen in later launches.
+//! - `normalize`: lexical resolution of `.` and `..` components
+//! - `resolve_within`: resolves a path, existing or not, to its canonical form and
+//!   checks that it lies inside a root
+//!
+//! Symlinks are resolved before the containment check, so a link inside the workspace
+//! can't lead out of it. Dangling links are refused, since writing through one would
+//! create its target wherever it points.
+
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Folders picked as workspaces, in the app's config directory
+const TRUSTED_FILE: &str = "trusted-workspaces.json";
+
+/// App identifier (see `tauri.conf.json`), naming the config directory
+const APP_IDENTIFIER: &str = "com.uxerv.unison.editor";
+
+/// Trusted workspaces kept, most recently picked first
+const MAX_TRUSTED: usize = 50;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TrustedWorkspaces {
+    roots: Vec<PathBuf>,
+}
+
+/// Resolve `.` and `..` components without touching the filesystem. `..` at the root
+/// stays at the root.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Canonical form of `path`, which must lie inside `root` (canonical). Components
+/// that don't exist yet (a file about to be created, with its parents) are appended
+/// to the canonicalized existing part.
+pub fn resolve_within(path: &Path, root: &Path) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err(format!("Path must be absolute: {}", path.display()));
+    }
+    let normalized = normalize(path);
+
+    let mut existing = normalized.as_path();
+    let mut missing: Vec<OsString> = Vec::new();
+    let canonical = loop {
+        match fs::canonicalize(existing) {
+            Ok(canonical) => break canonical,
+            Err(e) => {
+                // The entry is there but can't be resolved: a dangling symlink or no access
+                if fs::symlink_metadata(existing).is_ok() {
+                    return Err(format!("Failed to resolve path '{}': {}", existing.display(), e));
+                }
+                match (existing.parent(), existing.file_name()) {
+                    (Some(parent), Some(name)) => {
+                        missing.push(name.to_os_string());
+                        existing = parent;
+                    }
+                    _ => return Err(format!("Failed to resolve path '{}': {}", path.display(), e)),
+                }
+            }
+        }
+    };
+    let resolved = missing
+        .iter()
+        .rev()
+        .fold(canonical, |resolved, name| resolved.join(name));
+
+    if !resolved.starts_with(root) {
+        return Err(format!(
+            "Path '{}' is outside the workspace directory",
+            path.display()
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Root of the open workspace, which file commands are confined to
+pub struct WorkspaceGuard {
+    root: Mutex<Option<PathBuf>>,
+    trusted: Mutex<TrustedWorkspaces>,
+    /// Where `trusted` is persisted (None = this launch only)
+    trusted_file: Option<PathBuf>,
+}
+
+impl WorkspaceGuard {
+    pub fn new() -> Self {
+        let trusted_file = dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER).join(TRUSTED_FILE));
+        Self::with_trusted_file(trusted_file)
+    }
+
+    fn with_trusted_file(trusted_file: Option<PathBuf>) -> Self {
+        let trusted = trusted_file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            root: Mutex::new(None),
+            trusted: Mutex::new(trusted),
+            trusted_file,
+        }
+    }
+
+    /// Trust `path` as a workspace. Only call this with a folder the user picked in
+    /// the native dialog. Returns the canonical folder. The filesystem root and the
+    /// home directory are refused, as they would confine nothing.
+    pub fn trust(&self, path: &Path) -> Result<PathBuf, String> {
+        let root = fs::canonicalize(path)
+            .map_err(|e| format!("Failed to resolve workspace '{}': {}", path.display(), e))?;
+        if !root.is_dir() {
+            return Err(format!("Workspace is not a directory: {}", path.display()));
+        }
+        let home = dirs::home_dir().and_then(|home| fs::canonicalize(home).ok());
+        if root.parent().is_none() || home.as_ref() == Some(&root) {
+            return Err(format!("Cannot open '{}' as a workspace", root.display()));
+        }
+
+        let mut trusted = self.trusted.lock().unwrap();
+        trusted.roots.retain(|trusted| trusted != &root);
+        trusted.roots.insert(0, root.clone());
+        trusted.roots.truncate(MAX_TRUSTED);
+        if let Some(file) = &self.trusted_file {
+            let saved = serde_json::to_string_pretty(&*trusted)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| {
+                    if let Some(dir) = file.parent() {
+                        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                    }
+                    fs::write(file, contents).map_err(|e| e.to_string())
+                });
+            if let Err(e) = saved {
+                log::warn!("Failed to save trusted workspaces: {}", e);
+            }
+        }
+        Ok(root)
+    }
+
+    /// Confine file commands to `path`, which must have been picked (see `trust`).
+    /// Returns the canonical root.
+    pub fn open(&self, path: &Path) -> Result<PathBuf, String> {
+        let root = fs::canonicalize(path)
+            .map_err(|e| format!("Failed to resolve workspace '{}': {}", path.display(), e))?;
+        if !self.trusted.lock().unwrap().roots.contains(&root) {
+            return Err(format!(
+                "'{}' was not picked as a workspace; open it from the folder dialog",
+                root.display()
+            ));
+        }
+        *self.root.lock().unwrap() = Some(root.clone());
+        Ok(root)
+    }
+
+    /// Close the workspace; file commands fail until the next `open`
+    pub fn close(&self) {
+        *self.root.lock().unwrap() = None;
+    }
+
+    pub fn root(&self) -> Option<PathBuf> {
+        self.root.lock().unwrap().clone()
+    }
+
+    /// The workspace root, narrowed to `workspace` if given (which must lie inside it)
+    pub fn root_for(&self, workspace: Option<&str>) -> Result<PathBuf, String> {
+        let root = self.root().ok_or("No workspace is open")?;
+        match workspace {
+            Some(workspace) => resolve_within(Path::new(workspace), &root),
+            None => Ok(root),
+        }
+    }
+
+    /// Canonical form of `path`, which must lie inside the workspace (see `root_for`)
+    pub fn resolve(&self, path: &str, workspace: Option<&str>) -> Result<PathBuf, String> {
+        resolve_within(Path::new(path), &self.root_for(workspace)?)
+    }
+
+    /// `resolve` for commands that create, move or remove `path`, which refuses the
+    /// workspace root itself
+    pub fn resolve_entry(&self, path: &str, workspace: Option<&str>) -> Result<PathBuf, String> {
+        let resolved = self.resolve(path, workspace)?;
+        if Some(&resolved) == self.root().as_ref() {
+            return Err(format!("Cannot modify the workspace root: {}", path));
+        }
+        Ok(resolved)
+    }
+}
+
+impl Default for WorkspaceGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("/ws/./lib/../a.u")), PathBuf::from("/ws/a.u"));
+        assert_eq!(normalize(Path::new("/../../etc")), PathBuf::from("/etc"));
+        // ".." only counts as a whole component
+        assert_eq!(normalize(Path::new("/ws/a..b/..c")), PathBuf::from("/ws/a..b/..c"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_guard_confines_paths() {
+        let base = std::env::temp_dir().join(format!("path-guard-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let ws = base.join("ws");
+        fs::create_dir_all(ws.join("lib")).unwrap();
+        fs::create_dir_all(base.join("outside")).unwrap();
+        std::os::unix::fs::symlink(base.join("outside"), ws.join("escape")).unwrap();
+        std::os::unix::fs::symlink(base.join("missing"), ws.join("dangling")).unwrap();
+
+        let trusted_file = base.join("trusted.json");
+        let guard = WorkspaceGuard::with_trusted_file(Some(trusted_file.clone()));
+        assert!(guard.resolve(ws.join("lib").to_str().unwrap(), None).is_err());
+        assert!(guard.trust(Path::new("/")).is_err());
+        // Only picked folders open, also in a later launch
+        assert!(guard.open(&ws).is_err());
+        guard.trust(&ws).unwrap();
+        let root = WorkspaceGuard::with_trusted_file(Some(trusted_file)).open(&ws).unwrap();
+        guard.open(&ws).unwrap();
+        let resolve = |path: PathBuf| guard.resolve(path.to_str().unwrap(), None);
+
+        // New files below missing directories, and ".." that stays inside
+        assert_eq!(resolve(ws.join("lib/new/a.u")).unwrap(), root.join("lib/new/a.u"));
+        assert_eq!(resolve(ws.join("lib/../b.u")).unwrap(), root.join("b.u"));
+        assert!(resolve(ws.join("../outside/a.u")).is_err());
+        assert!(resolve(ws.join("lib/../../outside")).is_err());
+        // Symlinks are followed before the check
+        assert!(resolve(ws.join("escape/a.u")).is_err());
+        assert!(resolve(ws.join("dangling")).is_err());
+        assert!(guard.resolve("lib/a.u", None).is_err());
+
+        // A frontend workspace narrows the root but can't widen it
+        let lib = ws.join("lib");
+        assert!(guard.resolve(ws.join("b.u").to_str().unwrap(), lib.to_str()).is_err());
+        assert!(guard.root_for(base.to_str()).is_err());
+        assert!(guard.resolve_entry(ws.to_str().unwrap(), None).is_err());
+
+        guard.close();
+        assert!(resolve(ws.join("b.u")).is_err());
+        fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+//! Problems Store - Workspace-wide diagnostics collected from the UCM LSP
+//!
+//! This module provides:
+//! - Passive collection of `textDocument/publishDiagnostics` from the LSP proxy stream
+//!   (files open in Monaco)
+//! - An optional background scan that opens every `.u` file in the workspace over a
+//!   separate LSP connection, so files that were never opened get diagnostics too
+//! - `problems-changed` events with the new diagnostics of a single file
+
+use crate::lsp_middleware::LspNotificationObserver;
+use crate::lsp_proxy::LspProxy;
+use crate::ucm_session::emit_session_event;
+use crate::workspace_search::{walk_files, PathFilter};
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+
+/// Upper bound on files opened by a background scan
+const MAX_SCAN_FILES: usize = 1000;
+
+/// A background scan is finished once UCM has been quiet for this long
+const SCAN_IDLE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Hard limit for a single background scan
+const SCAN_TOTAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Diagnostics for one file (also the `problems-changed` event payload)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileProblems {
+    pub uri: String,
+    /// Local file path, when the URI is a `file://` URI
+    pub path: Option<String>,
+    /// LSP `Diagnostic` objects exactly as published by UCM
+    pub diagnostics: Vec<Value>,
+}
+
+/// Problems Store - keeps the latest diagnostics per file URI
+pub struct ProblemsStore {
+    files: Mutex<HashMap<String, Vec<Value>>>,
+    /// App handle and session id used to emit tagged events
+    event_target: Mutex<Option<(AppHandle, String)>>,
+    scanning: AtomicBool,
+}
+
+impl ProblemsStore {
+    pub fn new() -> Self {
+        Self {
+            files: Mutex::new(HashMap::new()),
+            event_target: Mutex::new(None),
+            scanning: AtomicBool::new(false),
+        }
+    }
+
+    /// Set the app handle used to emit `problems-changed` events, tagged with `session_id`
+    pub fn set_app_handle(&self, app_handle: AppHandle, session_id: &str) {
+        *self.event_target.lock() = Some((app_handle, session_id.to_string()));
+    }
+
+    /// Record the params of a `textDocument/publishDiagnostics` notification
+    pub fn record_publish(&self, params: &Value) {
+        let Some(uri) = params.get("uri").and_then(|u| u.as_str()) else {
+            return;
+        };
+        let diagnostics = params
+            .get("diagnostics")
+            .and_then(|d| d.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        {
+            let mut files = self.files.lock();
+            let unchanged = match files.get(uri) {
+                Some(existing) => *existing == diagnostics,
+                None => diagnostics.is_empty(),
+            };
+            if unchanged {
+                return;
+            }
+
+            if diagnostics.is_empty() {
+                files.remove(uri);
+            } else {
+                files.insert(uri.to_string(), diagnostics.clone());
+            }
+        }
+
+        let event = FileProblems {
+            uri: uri.to_string(),
+            path: file_uri_to_path(uri),
+            diagnostics,
+        };
+
+        if let Some((ref handle, ref session_id)) = *self.event_target.lock() {
+            emit_session_event(handle, session_id, "problems-changed", event);
+        }
+    }
+
+    /// Get all files that currently have diagnostics, sorted by URI
+    pub fn get_problems(&self) -> Vec<FileProblems> {
+        let mut problems: Vec<FileProblems> = self
+            .files
+            .lock()
+            .iter()
+            .map(|(uri, diagnostics)| FileProblems {
+                uri: uri.clone(),
+                path: file_uri_to_path(uri),
+                diagnostics: diagnostics.clone(),
+            })
+            .collect();
+        problems.sort_by(|a, b| a.uri.cmp(&b.uri));
+        problems
+    }
+
+    /// Forget all diagnostics (e.g. when UCM is respawned)
+    pub fn clear(&self) {
+        self.files.lock().clear();
+    }
+
+    /// Open every `.u` file under `workspace` against the UCM LSP on a separate
+    /// connection and collect the diagnostics it publishes.
+    ///
+    /// Returns an error if a scan is already running.
+    pub async fn scan_workspace(self: Arc<Self>, workspace: PathBuf, lsp_port: u16) -> Result<usize> {
+        if self.scanning.swap(true, Ordering::SeqCst) {
+            anyhow::bail!("A workspace problems scan is already running");
+        }
+
+        let result = self.scan_workspace_impl(&workspace, lsp_port).await;
+        self.scanning.store(false, Ordering::SeqCst);
+        result
+    }
+
+    async fn scan_workspace_impl(&self, workspace: &Path, lsp_port: u16) -> Result<usize> {
+        let files = collect_unison_files(workspace);
+
+        log::info!(
+            "[Problems] Scanning {} .u files in {} via LSP port {}",
+            files.len(),
+            workspace.display(),
+            lsp_port
+        );
+
+        let stream = TcpStream::connect(("127.0.0.1", lsp_port))
+            .await
+            .context("Failed to connect to UCM LSP for problems scan")?;
+        let (mut read, mut write) = stream.into_split();
+
+        send_message(&mut write, &json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "processId": null,
+                "rootUri": path_to_file_uri(workspace),
+                "capabilities": {}
+            }
+        }))
+        .await?;
+
+        // Wait for the initialize response before opening documents
+        loop {
+            let message: Value = serde_json::from_str(&LspProxy::read_lsp_message(&mut read).await?)?;
+            if message.get("id") == Some(&json!(1)) && message.get("method").is_none() {
+                break;
+            }
+            self.handle_server_message(&message, &mut write).await?;
+        }
+
+        send_message(&mut write, &json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} })).await?;
+
+        let mut opened = 0;
+        for file in &files {
+            let Ok(text) = fs::read_to_string(file) else {
+                continue;
+            };
+            send_message(&mut write, &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": {
+                    "textDocument": {
+                        "uri": path_to_file_uri(file),
+                        "languageId": "unison",
+                        "version": 1,
+                        "text": text
+                    }
+                }
+            }))
+            .await?;
+            opened += 1;
+        }
+
+        // Collect diagnostics until UCM goes quiet
+        let started = std::time::Instant::now();
+        while started.elapsed() < SCAN_TOTAL_TIMEOUT {
+            match tokio::time::timeout(SCAN_IDLE_TIMEOUT, LspProxy::read_lsp_message(&mut read)).await {
+                Ok(Ok(content)) => {
+                    if let Ok(message) = serde_json::from_str::<Value>(&content) {
+                        self.handle_server_message(&message, &mut write).await?;
+                    }
+                }
+                Ok(Err(e)) => {
+                    log::warn!("[Problems] LSP connection closed during scan: {}", e);
+                    return Ok(opened);
+                }
+                Err(_) => break,
+            }
+        }
+
+        send_message(&mut write, &json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" })).await?;
+        send_message(&mut write, &json!({ "jsonrpc": "2.0", "method": "exit" })).await?;
+
+        log::info!("[Problems] Background scan finished, opened {} files", opened);
+        Ok(opened)
+    }
+
+    /// Record diagnostics and answer server->client requests during a scan
+    async fn handle_server_message(&self, message: &Value, write: &mut OwnedWriteHalf) -> Result<()> {
+        match (message.get("method").and_then(|m| m.as_str()), message.get("id")) {
+            (Some("textDocument/publishDiagnostics"), None) => {
+                self.record_publish(&message["params"]);
+            }
+            // Requests like workspace/configuration need an answer or UCM may stall
+            (Some(_), Some(id)) => {
+                send_message(write, &json!({ "jsonrpc": "2.0", "id": id, "result": null })).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Default for ProblemsStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LspNotificationObserver for ProblemsStore {
+    fn observe_notification(&self, method: &str, params: &Value) {
+        if method == "textDocument/publishDiagnostics" {
+            self.record_publish(params);
+        }
+    }
+}
+
+async fn send_message(write: &mut OwnedWriteHalf, message: &Value) -> Result<()> {
+    let content = message.to_string();
+    let framed = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+    write
+        .write_all(framed.as_bytes())
+        .await
+        .context("Failed to write to LSP")?;
+    write.flush().await.context("Failed to flush LSP write")
+}
+
+/// The workspace's `.u` files (at most `MAX_SCAN_FILES`), skipping what the search
+/// skips (see `workspace_search::walk_files`)
+fn collect_unison_files(workspace: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let _ = walk_files(workspace, &PathFilter::default(), &mut |path| {
+        files.push(path.to_path_buf());
+        if files.len() < MAX_SCAN_FILES {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    });
+    files
+}
+
+/// Convert an absolute path to a `file://` URI, percent-encoding reserved characters
+pub fn path_to_file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        // Windows drive paths: file:///C:/...
+        uri.push('/');
+    }
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Convert a `file://` URI back to a local path
+pub fn file_uri_to_path(uri: &str) -> Option<String> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    let path = String::from_utf8(decoded).ok()?;
+    // file:///C:/foo -> C:/foo
+    if path.len() > 2 && path.as_bytes()[2] == b':' && path.starts_with('/') {
+        Some(path[1..].to_string())
+    } else {
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_uri_roundtrip() {
+        let path = Path::new("/home/me/my project/scratch.u");
+        let uri = path_to_file_uri(path);
+        assert_eq!(uri, "file:///home/me/my%20project/scratch.u");
+        assert_eq!(file_uri_to_path(&uri).unwrap(), "/home/me/my project/scratch.u");
+        assert_eq!(file_uri_to_path("untitled:1"), None);
+    }
+
+    #[test]
+    fn test_record_publish_replaces_and_clears() {
+        let store = ProblemsStore::new();
+        let diagnostic = json!({ "message": "oops", "severity": 1 });
+
+        store.record_publish(&json!({ "uri": "file:///a.u", "diagnostics": [diagnostic] }));
+        store.record_publish(&json!({ "uri": "file:///b.u", "diagnostics": [] }));
+
+        let problems = store.get_problems();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path.as_deref(), Some("/a.u"));
+        assert_eq!(problems[0].diagnostics.len(), 1);
+
+        store.record_publish(&json!({ "uri": "file:///a.u", "diagnostics": [] }));
+        assert!(store.get_problems().is_empty());
+    }
+
+    #[test]
+    fn test_collect_unison_files_skips_hidden() {
+        let root = std::env::temp_dir().join(format!("problems-scan-{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("main.u"), "").unwrap();
+        fs::write(root.join("lib/util.u"), "").unwrap();
+        fs::write(root.join(".git/hidden.u"), "").unwrap();
+        fs::write(root.join("notes.md"), "").unwrap();
+
+        let mut files = collect_unison_files(&root);
+        files.sort();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(files, vec![root.join("lib/util.u"), root.join("main.u")]);
+    }
+}
@@ -0,0 +1,785 @@
+//! PTY Sessions - Processes running in a pseudo-terminal
+//!
+//! This module provides:
+//! - `PtyProcess`: the channel-based, non-blocking PTY machinery shared by UCM and
+//!   shell terminals - a writer task fed by write/resize channels, a reader thread,
+//!   a byte-accurate scrollback ring buffer, exit status reporting and SIGTERM/SIGKILL
+//!   escalation
+//! - `PtySessionManager`: the user's login shell (or any command) in the workspace
+//!   directory, for the general terminal panel. Sessions are keyed by a frontend-chosen
+//!   id and emit `pty-output` and `pty-exited` events tagged with that id.
+
+use crate::ucm_locator::with_ucm_dirs;
+use crate::ucm_session::emit_session_event;
+use parking_lot::Mutex;
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::sync::mpsc;
+
+/// Bytes of PTY output kept for reattaching terminals
+const SCROLLBACK_CAPACITY: usize = 2 * 1024 * 1024;
+
+/// Initial PTY size, until the terminal reports its own
+pub const INITIAL_ROWS: u16 = 24;
+pub const INITIAL_COLS: u16 = 80;
+
+/// How long a process gets to handle SIGTERM before being sent SIGKILL
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long to wait for the process to disappear after SIGKILL
+const KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a shell gets to honour SIGHUP (the terminal closing) before SIGTERM
+const HANGUP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Exit status payload for the `ucm-process-exited` and `pty-exited` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyExitStatus {
+    /// Process exit code, if the process could be reaped
+    #[serde(rename = "exitCode")]
+    pub exit_code: Option<u32>,
+    pub success: bool,
+    /// How the editor stopped the process ("exit", "SIGHUP", "SIGTERM" or "SIGKILL"),
+    /// or None if it exited on its own (user typed `exit`, crash, ...)
+    #[serde(rename = "terminatedBy")]
+    pub terminated_by: Option<String>,
+}
+
+/// Event payload for `ucm-pty-output` and `pty-output`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyOutput {
+    /// Raw bytes read from the PTY
+    pub data: Vec<u8>,
+    /// Stream offset of the first byte, to line events up with an attach snapshot
+    pub offset: u64,
+}
+
+/// Buffered output and terminal size returned by `ucm_pty_attach` and `pty_attach`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyAttachSnapshot {
+    /// Buffered raw output, oldest first
+    pub data: Vec<u8>,
+    /// Stream offset of the first buffered byte
+    #[serde(rename = "startOffset")]
+    pub start_offset: u64,
+    /// Stream offset just past the last buffered byte; output events with a
+    /// lower offset are already part of `data`
+    #[serde(rename = "endOffset")]
+    pub end_offset: u64,
+    /// Whether older output has been dropped from the buffer
+    pub truncated: bool,
+    pub rows: u16,
+    pub cols: u16,
+}
+
+/// Bounded ring buffer of raw PTY output, addressed by stream offset
+pub struct ScrollbackBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    /// Total bytes ever pushed (offset just past the newest byte)
+    end_offset: u64,
+}
+
+impl ScrollbackBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::new(),
+            capacity: capacity.max(1),
+            end_offset: 0,
+        }
+    }
+
+    /// Append output, evicting the oldest bytes when full.
+    /// Returns the stream offset of the first appended byte.
+    pub fn push(&mut self, bytes: &[u8]) -> u64 {
+        let offset = self.end_offset;
+        self.end_offset += bytes.len() as u64;
+
+        let kept = &bytes[bytes.len().saturating_sub(self.capacity)..];
+        let overflow = (self.data.len() + kept.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(kept);
+        offset
+    }
+
+    /// Stream offset of the oldest buffered byte
+    pub fn start_offset(&self) -> u64 {
+        self.end_offset - self.data.len() as u64
+    }
+
+    pub fn end_offset(&self) -> u64 {
+        self.end_offset
+    }
+
+    /// Copy out the buffered bytes with their start offset. If eviction cut a UTF-8
+    /// character in half, its leftover continuation bytes are skipped.
+    pub fn snapshot(&self) -> (Vec<u8>, u64) {
+        let mut start = self.start_offset();
+        let mut bytes = self.data.iter().copied().peekable();
+        if start > 0 {
+            while bytes.next_if(|b| b & 0xC0 == 0x80).is_some() {
+                start += 1;
+            }
+        }
+        (bytes.collect(), start)
+    }
+}
+
+/// Environment every PTY process gets: a color-capable terminal, UTF-8 and the
+/// inherited `PATH` with UCM's install locations added, which GUI apps on macOS
+/// don't inherit from the shell
+pub fn apply_terminal_env(cmd: &mut CommandBuilder) {
+    let path = with_ucm_dirs(cmd.get_env("PATH"));
+    cmd.env("PATH", path);
+    cmd.env("TERM", "xterm-256color");
+    cmd.env("LANG", "en_US.UTF-8");
+    cmd.env("LC_ALL", "en_US.UTF-8");
+    cmd.env("COLORTERM", "truecolor");
+    cmd.env("TERMINFO_DIRS", "/usr/share/terminfo:/lib/terminfo:/etc/terminfo");
+
+    if let Some(home) = dirs::home_dir() {
+        cmd.env("HOME", home.to_string_lossy().to_string());
+    }
+}
+
+/// A process running in a PTY.
+///
+/// Writes and resizes go through channels to a writer task, output is read by a
+/// dedicated thread, so no call ever blocks on the PTY.
+pub struct PtyProcess {
+    /// Name used in log messages ("UCM", "shell", ...)
+    label: String,
+    /// Channel to send input to PTY writer task
+    write_tx: mpsc::Sender<Vec<u8>>,
+    /// Channel to send resize commands
+    resize_tx: mpsc::Sender<(u16, u16)>,
+    /// Flag to signal threads to stop
+    running: Arc<Mutex<bool>>,
+    /// The process, kept so it can be waited on and terminated
+    child: Arc<Mutex<Box<dyn Child + Send + Sync>>>,
+    /// Set while the process is being stopped, so the exit event can say how
+    terminated_by: Arc<Mutex<Option<String>>>,
+    /// Recent raw output, replayed by `attach`
+    scrollback: Arc<Mutex<ScrollbackBuffer>>,
+    /// Current PTY size as (rows, cols)
+    size: Mutex<(u16, u16)>,
+}
+
+impl PtyProcess {
+    /// Spawn `cmd` in a new PTY of `rows` x `cols` and start the writer task and reader thread.
+    ///
+    /// `on_output` sees every read (after it has been added to the scrollback) with its
+    /// stream offset; returning `ControlFlow::Break` stops reading and marks the process
+    /// as no longer running. `on_exit` is called with the exit status once the PTY
+    /// reports EOF or the process is gone.
+    pub fn spawn<F, E>(
+        label: &str,
+        cmd: CommandBuilder,
+        rows: u16,
+        cols: u16,
+        mut on_output: F,
+        on_exit: E,
+    ) -> Result<Self, String>
+    where
+        F: FnMut(&[u8], u64) -> ControlFlow<()> + Send + 'static,
+        E: FnOnce(PtyExitStatus) + Send + 'static,
+    {
+        let pty_system = native_pty_system();
+        let pair = pty_system
+            .openpty(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("Failed to open PTY: {}", e))?;
+
+        log::info!("PTY created successfully");
+
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| format!("Failed to spawn {}: {}", label, e))?;
+
+        log::info!("{} process spawned successfully (pid {:?})", label, child.process_id());
+        let child = Arc::new(Mutex::new(child));
+        let terminated_by = Arc::new(Mutex::new(None));
+
+        let master = pair.master;
+        let writer = master
+            .take_writer()
+            .map_err(|e| format!("Failed to get PTY writer: {}", e))?;
+        let mut reader = master
+            .try_clone_reader()
+            .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
+
+        let scrollback = Arc::new(Mutex::new(ScrollbackBuffer::new(SCROLLBACK_CAPACITY)));
+        let running = Arc::new(Mutex::new(true));
+
+        let (write_tx, resize_tx) = spawn_writer(label, writer, master, running.clone());
+
+        // Reader thread - reads output and hands it to `on_output`
+        let running_clone = running.clone();
+        let child_clone = child.clone();
+        let terminated_by_clone = terminated_by.clone();
+        let scrollback_clone = scrollback.clone();
+        let reader_label = label.to_string();
+
+        thread::spawn(move || {
+            // Larger buffer for better throughput during heavy output (e.g., run commands)
+            let mut buffer = [0u8; 32768];
+
+            // Keep reading until EOF: stopping the process closes the PTY, so the exit is always observed
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => {
+                        // EOF - the process exited (user typed 'exit' or process terminated)
+                        log::info!("{} PTY EOF - process exited", reader_label);
+                        *running_clone.lock() = false;
+                        on_exit(collect_exit_status(&reader_label, &child_clone, &terminated_by_clone));
+                        break;
+                    }
+                    Ok(n) => {
+                        let output = &buffer[..n];
+
+                        // Buffer before handing output on, so an attach snapshot never misses output
+                        let offset = scrollback_clone.lock().push(output);
+
+                        if on_output(output, offset).is_break() {
+                            *running_clone.lock() = false;
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        // Check if it's a would-block error (expected for non-blocking)
+                        if e.kind() == std::io::ErrorKind::WouldBlock {
+                            thread::sleep(Duration::from_millis(10));
+                            continue;
+                        }
+                        // Other errors - the process likely crashed or was killed
+                        // (Linux reports EIO on the master once the child is gone)
+                        log::info!("{} PTY read error (process likely exited): {}", reader_label, e);
+                        *running_clone.lock() = false;
+                        on_exit(collect_exit_status(&reader_label, &child_clone, &terminated_by_clone));
+                        break;
+                    }
+                }
+            }
+
+            log::info!("{} PTY reader thread exiting", reader_label);
+        });
+
+        Ok(Self {
+            label: label.to_string(),
+            write_tx,
+            resize_tx,
+            running,
+            child,
+            terminated_by,
+            scrollback,
+            size: Mutex::new((rows, cols)),
+        })
+    }
+
+    /// Send raw bytes to the PTY writer task
+    pub async fn send(&self, data: &[u8]) -> Result<(), String> {
+        self.write_tx
+            .send(data.to_vec())
+            .await
+            .map_err(|e| format!("Failed to send write to PTY: {}", e))
+    }
+
+    /// Resize the PTY (async, via channel)
+    pub async fn resize(&self, rows: u16, cols: u16) -> Result<(), String> {
+        self.resize_tx
+            .send((rows, cols))
+            .await
+            .map_err(|e| format!("Failed to send resize to PTY: {}", e))?;
+        *self.size.lock() = (rows, cols);
+        Ok(())
+    }
+
+    /// Buffered output and current size, for a terminal (re)attaching mid-session
+    pub fn attach(&self) -> PtyAttachSnapshot {
+        let scrollback = self.scrollback.lock();
+        let (data, start_offset) = scrollback.snapshot();
+        let (rows, cols) = *self.size.lock();
+        PtyAttachSnapshot {
+            data,
+            start_offset,
+            end_offset: scrollback.end_offset(),
+            truncated: start_offset > 0,
+            rows,
+            cols,
+        }
+    }
+
+    /// OS process id, if known
+    pub fn process_id(&self) -> Option<u32> {
+        self.child.lock().process_id()
+    }
+
+    /// Shared running flag, cleared when the process exits or is stopped
+    pub fn running_handle(&self) -> Arc<Mutex<bool>> {
+        self.running.clone()
+    }
+
+    /// Stop the writer task
+    pub fn stop(&self) {
+        *self.running.lock() = false;
+    }
+
+    /// Check if the PTY is still running
+    pub fn is_running(&self) -> bool {
+        *self.running.lock()
+    }
+
+    /// Record how the process is being stopped, for the exit event
+    pub fn set_terminated_by(&self, how: &str) {
+        *self.terminated_by.lock() = Some(how.to_string());
+    }
+
+    /// Check whether the process has exited (reaping it if so)
+    pub fn has_exited(&self) -> bool {
+        !matches!(self.child.lock().try_wait(), Ok(None))
+    }
+
+    /// Wait up to `timeout` for the process to exit
+    pub async fn wait_for_exit(&self, timeout: Duration) -> bool {
+        let started = std::time::Instant::now();
+        while started.elapsed() < timeout {
+            if self.has_exited() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.has_exited()
+    }
+
+    /// Send `signal` to the process and wait up to `timeout` for it to exit
+    #[cfg(unix)]
+    async fn signal(&self, signal: libc::c_int, name: &str, timeout: Duration) -> bool {
+        let Some(pid) = self.process_id() else {
+            return false;
+        };
+        log::warn!("Sending {} to {} (pid {})", name, self.label, pid);
+        self.set_terminated_by(name);
+        unsafe { libc::kill(pid as libc::pid_t, signal) };
+        self.wait_for_exit(timeout).await
+    }
+
+    /// Terminate the process with SIGTERM, escalating to SIGKILL if it does not exit in time
+    pub async fn terminate(&self) -> Result<(), String> {
+        #[cfg(unix)]
+        if self.signal(libc::SIGTERM, "SIGTERM", TERMINATE_TIMEOUT).await {
+            self.stop();
+            return Ok(());
+        }
+
+        let pid = self.process_id();
+        log::warn!("{} (pid {:?}) still running, sending SIGKILL", self.label, pid);
+        self.set_terminated_by("SIGKILL");
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = self.child.lock().kill();
+        }
+
+        let exited = self.wait_for_exit(KILL_TIMEOUT).await;
+        self.stop();
+        if exited {
+            Ok(())
+        } else {
+            Err(format!("{} (pid {:?}) did not exit after SIGKILL", self.label, pid))
+        }
+    }
+
+    /// Hang up the terminal (SIGHUP, like closing a terminal window), then escalate
+    pub async fn hang_up(&self) -> Result<(), String> {
+        if self.has_exited() {
+            self.stop();
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        if self.signal(libc::SIGHUP, "SIGHUP", HANGUP_TIMEOUT).await {
+            self.stop();
+            return Ok(());
+        }
+
+        self.terminate().await
+    }
+}
+
+impl Drop for PtyProcess {
+    fn drop(&mut self) {
+        self.stop();
+        // Never leave an orphaned process behind
+        if !self.has_exited() {
+            log::info!("Killing {} (pid {:?}) on drop", self.label, self.process_id());
+            self.set_terminated_by("SIGKILL");
+            let _ = self.child.lock().kill();
+        }
+    }
+}
+
+/// Writer task - handles writes and resizes via channels. Runs on Tauri's runtime, so
+/// it can be started from sync commands (on the main thread, outside any runtime).
+fn spawn_writer(
+    label: &str,
+    writer: Box<dyn Write + Send>,
+    master: Box<dyn MasterPty + Send>,
+    running: Arc<Mutex<bool>>,
+) -> (mpsc::Sender<Vec<u8>>, mpsc::Sender<(u16, u16)>) {
+    let (write_tx, mut write_rx) = mpsc::channel::<Vec<u8>>(100);
+    let (resize_tx, mut resize_rx) = mpsc::channel::<(u16, u16)>(10);
+    let label = label.to_string();
+
+    tauri::async_runtime::spawn(async move {
+        let mut writer = writer;
+        loop {
+            tokio::select! {
+                Some(data) = write_rx.recv() => {
+                    if let Err(e) = writer.write_all(&data) {
+                        log::error!("PTY write error: {}", e);
+                    }
+                    let _ = writer.flush();
+                }
+                Some((rows, cols)) = resize_rx.recv() => {
+                    if let Err(e) = master.resize(PtySize {
+                        rows,
+                        cols,
+                        pixel_width: 0,
+                        pixel_height: 0,
+                    }) {
+                        log::error!("PTY resize error: {}", e);
+                    } else {
+                        log::debug!("PTY resized to {}x{}", cols, rows);
+                    }
+                }
+                else => {
+                    if !*running.lock() {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        }
+        log::info!("{} PTY writer task exiting", label);
+    });
+
+    (write_tx, resize_tx)
+}
+
+/// Reap the exited process (giving it a moment after PTY EOF) and build the exit payload
+fn collect_exit_status(
+    label: &str,
+    child: &Mutex<Box<dyn Child + Send + Sync>>,
+    terminated_by: &Mutex<Option<String>>,
+) -> PtyExitStatus {
+    let mut status = None;
+    for _ in 0..40 {
+        match child.lock().try_wait() {
+            Ok(Some(exit)) => {
+                status = Some(exit);
+                break;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => {
+                log::warn!("Failed to get {} exit status: {}", label, e);
+                break;
+            }
+        }
+    }
+
+    let status = PtyExitStatus {
+        exit_code: status.as_ref().map(|s| s.exit_code()),
+        success: status.as_ref().is_some_and(|s| s.success()),
+        terminated_by: terminated_by.lock().clone(),
+    };
+    log::info!("{} exit status: {:?}", label, status);
+    status
+}
+
+/// The user's login shell
+fn default_shell() -> String {
+    #[cfg(unix)]
+    {
+        std::env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| "/bin/sh".to_string())
+    }
+    #[cfg(not(unix))]
+    {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    }
+}
+
+/// Summary of a shell PTY session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtySessionInfo {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    /// Program running in the PTY
+    pub command: String,
+    pub pid: Option<u32>,
+    pub running: bool,
+}
+
+/// A shell (or other command) PTY and what it was started with
+struct PtySession {
+    process: Arc<PtyProcess>,
+    command: String,
+}
+
+/// PTY Session Manager - shell PTYs for the general terminal panel, keyed by session id
+pub struct PtySessionManager {
+    sessions: std::sync::Mutex<HashMap<String, PtySession>>,
+}
+
+impl PtySessionManager {
+    pub fn new() -> Self {
+        Self {
+            sessions: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spawn `command` (default: the user's login shell) in `cwd` (default: the home
+    /// directory; `pty_spawn` passes the workspace root).
+    /// If the session is already running, it is kept and its info returned, so a
+    /// remounted terminal can reattach instead of starting a new shell.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn(
+        &self,
+        app_handle: AppHandle,
+        session_id: &str,
+        command: Option<String>,
+        args: Vec<String>,
+        cwd: Option<String>,
+        rows: u16,
+        cols: u16,
+    ) -> Result<PtySessionInfo, String> {
+        if let Ok(info) = self.info(session_id) {
+            if info.running {
+                return Ok(info);
+            }
+        }
+
+        let login_shell = command.is_none();
+        let program = command.unwrap_or_else(default_shell);
+        log::info!("Spawning {} in PTY session {}", program, session_id);
+
+        let mut cmd = CommandBuilder::new(&program);
+        if login_shell && cfg!(unix) {
+            cmd.arg("-l");
+        }
+        cmd.args(&args);
+        apply_terminal_env(&mut cmd);
+
+        match cwd {
+            Some(dir) => cmd.cwd(dir),
+            None => {
+                if let Some(home) = dirs::home_dir() {
+                    cmd.cwd(home);
+                }
+            }
+        }
+
+        let output_app = app_handle.clone();
+        let output_session = session_id.to_string();
+        let exit_session = session_id.to_string();
+        let process = PtyProcess::spawn(
+            "shell",
+            cmd,
+            rows,
+            cols,
+            move |data, offset| {
+                emit_session_event(
+                    &output_app,
+                    &output_session,
+                    "pty-output",
+                    PtyOutput {
+                        data: data.to_vec(),
+                        offset,
+                    },
+                );
+                ControlFlow::Continue(())
+            },
+            move |status| emit_session_event(&app_handle, &exit_session, "pty-exited", status),
+        )?;
+
+        let session = PtySession {
+            process: Arc::new(process),
+            command: program,
+        };
+        let info = session.info(session_id);
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), session);
+        Ok(info)
+    }
+
+    fn process(&self, session_id: &str) -> Result<Arc<PtyProcess>, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .map(|session| session.process.clone())
+            .ok_or_else(|| format!("Unknown PTY session: {}", session_id))
+    }
+
+    pub fn info(&self, session_id: &str) -> Result<PtySessionInfo, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .map(|session| session.info(session_id))
+            .ok_or_else(|| format!("Unknown PTY session: {}", session_id))
+    }
+
+    /// All sessions, sorted by id
+    pub fn list(&self) -> Vec<PtySessionInfo> {
+        let mut sessions: Vec<PtySessionInfo> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, session)| session.info(id))
+            .collect();
+        sessions.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        sessions
+    }
+
+    pub async fn write(&self, session_id: &str, data: &[u8]) -> Result<(), String> {
+        self.process(session_id)?.send(data).await
+    }
+
+    pub async fn resize(&self, session_id: &str, rows: u16, cols: u16) -> Result<(), String> {
+        self.process(session_id)?.resize(rows, cols).await
+    }
+
+    pub fn attach(&self, session_id: &str) -> Result<PtyAttachSnapshot, String> {
+        Ok(self.process(session_id)?.attach())
+    }
+
+    /// Hang up and remove a session. Unknown sessions are ignored.
+    pub async fn kill(&self, session_id: &str) -> Result<(), String> {
+        let session = self.sessions.lock().unwrap().remove(session_id);
+        if let Some(session) = session {
+            log::info!("Killing PTY session {}", session_id);
+            session.process.hang_up().await?;
+        }
+        Ok(())
+    }
+}
+
+impl PtySession {
+    fn info(&self, session_id: &str) -> PtySessionInfo {
+        PtySessionInfo {
+            session_id: session_id.to_string(),
+            command: self.command.clone(),
+            pid: self.process.process_id(),
+            running: self.process.is_running(),
+        }
+    }
+}
+
+impl Default for PtySessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollback_evicts_oldest_bytes() {
+        let mut buffer = ScrollbackBuffer::new(8);
+        assert_eq!(buffer.push(b"hello"), 0);
+        assert_eq!(buffer.push(b" world"), 5);
+        assert_eq!(buffer.end_offset(), 11);
+        assert_eq!(buffer.start_offset(), 3);
+        assert_eq!(buffer.snapshot(), (b"lo world".to_vec(), 3));
+
+        // A single write larger than the buffer keeps only its tail
+        assert_eq!(buffer.push(b"0123456789"), 11);
+        assert_eq!(buffer.snapshot(), (b"23456789".to_vec(), 13));
+    }
+
+    #[test]
+    fn test_scrollback_snapshot_skips_split_utf8() {
+        let mut buffer = ScrollbackBuffer::new(3);
+        buffer.push("ab⧩".as_bytes()); // '⧩' is 3 bytes
+        buffer.push(b"c"); // evicts the first byte of '⧩'
+        let (data, start) = buffer.snapshot();
+        assert_eq!(data, b"c".to_vec());
+        assert_eq!(start, 5);
+        assert_eq!(buffer.end_offset(), 6);
+    }
+
+    /// Sync commands spawn PTYs on the main thread, which has no Tokio runtime
+    #[test]
+    fn test_pty_process_spawns_outside_runtime() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.args(["-c", "read line; printf \"got $line\""]);
+        let process = PtyProcess::spawn(
+            "test",
+            cmd,
+            INITIAL_ROWS,
+            INITIAL_COLS,
+            |_, _| ControlFlow::Continue(()),
+            move |status| {
+                let _ = tx.send(status);
+            },
+        )
+        .unwrap();
+
+        // Input goes through the writer task
+        process.write_tx.blocking_send(b"ping\n".to_vec()).unwrap();
+        let status = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(status.success);
+        assert!(String::from_utf8_lossy(&process.attach().data).contains("got ping"));
+    }
+
+    #[tokio::test]
+    async fn test_pty_process_runs_command() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.args(["-c", "printf hello; exit 3"]);
+        let process = PtyProcess::spawn(
+            "test",
+            cmd,
+            INITIAL_ROWS,
+            INITIAL_COLS,
+            |_, _| ControlFlow::Continue(()),
+            move |status| {
+                let _ = tx.send(status);
+            },
+        )
+        .unwrap();
+
+        let status = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.exit_code, Some(3));
+        assert!(!status.success);
+        assert!(!process.is_running());
+        assert!(String::from_utf8_lossy(&process.attach().data).contains("hello"));
+    }
+}
@@ -0,0 +1,503 @@
+//! Transcript Runner - Runs Unison `.md` transcripts and reports per-stanza results
+//!
+//! This module provides:
+//! - `run_transcript`: runs `ucm transcript` (fresh codebase) or `ucm transcript.fork`
+//!   (copy of the session's codebase) on a transcript, streaming its output as
+//!   `transcript-output` events
+//! - `parse_transcript_blocks`: the fenced stanzas of a transcript with their line ranges
+//! - `evaluate_transcript`: matches the generated `.output.md` against the source
+//!   stanzas to find which passed, which failed and which never ran
+
+use crate::mcp_client::TestResult;
+use crate::ucm_locator::ucm_search_path;
+use crate::ucm_pty::strip_ansi;
+use crate::ucm_session::{emit_session_event, UCMCodebase};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Instant, SystemTime};
+use tauri::AppHandle;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Stanza kinds UCM executes; other fenced blocks are copied to the output as-is
+const EXECUTED_KINDS: &[&str] = &["ucm", "unison", "api"];
+
+/// Lines UCM writes after the stanza that stopped a transcript
+const FAILURE_MARKERS: &[&str] = &[
+    "🛑",
+    "The transcript failed due to an error",
+    "The transcript was expecting an error",
+];
+
+/// Event payload for `transcript-output`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptOutputLine {
+    /// Transcript being run
+    pub path: String,
+    pub line: String,
+    /// "stdout" or "stderr"
+    pub stream: String,
+}
+
+/// A fenced stanza of a transcript
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptBlock {
+    /// Language of the fence (`ucm`, `unison`, `api`, ...)
+    pub kind: String,
+    /// Fence modifiers such as `:hide`, `:error` or `:bug`
+    pub modifiers: Vec<String>,
+    /// 1-based line of the opening fence
+    #[serde(rename = "startLine")]
+    pub start_line: usize,
+    /// 1-based line of the closing fence
+    #[serde(rename = "endLine")]
+    pub end_line: usize,
+    /// Lines between the fences
+    pub body: Vec<String>,
+}
+
+impl TranscriptBlock {
+    fn is_executed(&self) -> bool {
+        EXECUTED_KINDS.contains(&self.kind.as_str())
+    }
+
+    fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers.iter().any(|m| m == modifier)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TranscriptBlockStatus {
+    Passed,
+    Failed,
+    /// Not run, because an earlier stanza stopped the transcript
+    Skipped,
+}
+
+/// Outcome of one executed stanza
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptBlockResult {
+    /// Index among the transcript's executed stanzas
+    pub index: usize,
+    pub kind: String,
+    pub modifiers: Vec<String>,
+    #[serde(rename = "startLine")]
+    pub start_line: usize,
+    #[serde(rename = "endLine")]
+    pub end_line: usize,
+    pub status: TranscriptBlockStatus,
+    /// UCM's explanation for a failed stanza
+    pub message: Option<String>,
+}
+
+/// Result of running a transcript, shaped like `RunTestsResult` for the test panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptResult {
+    pub success: bool,
+    /// Combined stdout/stderr of UCM
+    pub output: String,
+    /// Unexpected errors: failed stanzas, or UCM failing without an output file
+    pub errors: Vec<String>,
+    /// The generated `.output.md`, if UCM wrote one
+    #[serde(rename = "outputFile")]
+    pub output_file: Option<String>,
+    pub blocks: Vec<TranscriptBlockResult>,
+    /// One entry per stanza that ran
+    #[serde(rename = "testResults")]
+    pub test_results: Vec<TestResult>,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: u64,
+}
+
+/// Parse the fenced stanzas of a transcript
+pub fn parse_transcript_blocks(markdown: &str) -> Vec<TranscriptBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(usize, TranscriptBlock)> = None;
+
+    for (i, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let fence_len = trimmed.chars().take_while(|c| *c == '`').count();
+
+        match open.take() {
+            Some((len, mut block)) => {
+                if fence_len >= len && trimmed[fence_len..].trim().is_empty() {
+                    block.end_line = i + 1;
+                    blocks.push(block);
+                } else {
+                    block.body.push(line.to_string());
+                    open = Some((len, block));
+                }
+            }
+            None if fence_len >= 3 => {
+                let info = trimmed[fence_len..].trim();
+                let (kind, rest) = info
+                    .find(|c: char| c.is_whitespace() || c == ':')
+                    .map(|pos| info.split_at(pos))
+                    .unwrap_or((info, ""));
+                let modifiers = rest
+                    .split_whitespace()
+                    .flat_map(|token| token.split(':').skip(1))
+                    .filter(|m| !m.is_empty())
+                    .map(|m| format!(":{}", m))
+                    .collect();
+                open = Some((
+                    fence_len,
+                    TranscriptBlock {
+                        kind: kind.to_string(),
+                        modifiers,
+                        start_line: i + 1,
+                        end_line: i + 1,
+                        body: Vec::new(),
+                    },
+                ));
+            }
+            None => {}
+        }
+    }
+
+    blocks
+}
+
+/// Whether a `ucm` block has a prompt line (`> cmd` or `project/branch> cmd`)
+fn has_prompt_line(block: &TranscriptBlock) -> bool {
+    block.body.iter().any(|line| {
+        line.find('>')
+            .is_some_and(|pos| !line[..pos].contains(char::is_whitespace))
+    })
+}
+
+/// Evaluate a transcript run from its source stanzas and the generated output
+///
+/// The output echoes every stanza except `:hide-all` ones, and adds a `ucm` block
+/// with the typechecking output after each `unison` stanza. UCM stops at the first
+/// failing stanza and writes one of `FAILURE_MARKERS` after it, so the failed stanza
+/// is the last one echoed before the marker.
+pub fn evaluate_transcript(
+    source: &[TranscriptBlock],
+    output: Option<&str>,
+    exit_success: bool,
+) -> (Vec<TranscriptBlockResult>, Vec<String>) {
+    let executed: Vec<&TranscriptBlock> = source.iter().filter(|b| b.is_executed()).collect();
+    let mut errors = Vec::new();
+
+    let result = |index: usize, block: &TranscriptBlock, status, message: Option<String>| {
+        TranscriptBlockResult {
+            index,
+            kind: block.kind.clone(),
+            modifiers: block.modifiers.clone(),
+            start_line: block.start_line,
+            end_line: block.end_line,
+            status,
+            message,
+        }
+    };
+
+    let Some(output) = output else {
+        errors.push("UCM did not write a transcript output file".to_string());
+        let blocks = executed
+            .iter()
+            .enumerate()
+            .map(|(i, b)| result(i, b, TranscriptBlockStatus::Skipped, None))
+            .collect();
+        return (blocks, errors);
+    };
+
+    let lines: Vec<&str> = output.lines().collect();
+    let marker_line = lines
+        .iter()
+        .position(|line| FAILURE_MARKERS.iter().any(|m| line.trim().starts_with(m)));
+
+    let Some(marker_line) = marker_line else {
+        if !exit_success {
+            errors.push("UCM exited with an error, but the transcript output shows no failure".to_string());
+        }
+        let blocks = executed
+            .iter()
+            .enumerate()
+            .map(|(i, b)| result(i, b, TranscriptBlockStatus::Passed, None))
+            .collect();
+        return (blocks, errors);
+    };
+
+    // Count the source stanzas echoed before the failure marker
+    let before_marker = lines[..marker_line].join("\n");
+    let mut echoed = 0usize;
+    let mut previous_kind = String::new();
+    for block in parse_transcript_blocks(&before_marker) {
+        if !block.is_executed() {
+            continue;
+        }
+        let generated = block.has_modifier(":added-by-ucm")
+            || (block.kind == "ucm" && previous_kind == "unison" && !has_prompt_line(&block));
+        if !generated {
+            echoed += 1;
+        }
+        previous_kind = block.kind.clone();
+    }
+
+    let message = lines[marker_line..]
+        .iter()
+        .map(|line| line.trim_end())
+        .filter(|line| line.trim() != "🛑" && !line.starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+
+    // Map the n-th echoed stanza back to the source (`:hide-all` stanzas aren't echoed)
+    let failed = echoed.checked_sub(1).and_then(|n| {
+        executed
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !b.has_modifier(":hide-all"))
+            .nth(n)
+            .map(|(i, _)| i)
+    });
+
+    let blocks = executed
+        .iter()
+        .enumerate()
+        .map(|(i, b)| match failed {
+            Some(f) if i < f => result(i, b, TranscriptBlockStatus::Passed, None),
+            Some(f) if i == f => {
+                errors.push(format!("Lines {}-{}: {}", b.start_line, b.end_line, message));
+                result(i, b, TranscriptBlockStatus::Failed, Some(message.clone()))
+            }
+            _ => result(i, b, TranscriptBlockStatus::Skipped, None),
+        })
+        .collect();
+    if failed.is_none() {
+        errors.push(message);
+    }
+    (blocks, errors)
+}
+
+/// `foo.md` -> `foo.output.md`
+pub fn transcript_output_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!("{}.output.md", stem))
+}
+
+/// Stream one of UCM's output pipes as `transcript-output` events, returning all of it
+async fn stream_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    stream: &str,
+    app_handle: &AppHandle,
+    session_id: &str,
+    path: &str,
+) -> String {
+    let mut collected = String::new();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = strip_ansi(&line);
+        emit_session_event(
+            app_handle,
+            session_id,
+            "transcript-output",
+            TranscriptOutputLine {
+                path: path.to_string(),
+                line: line.clone(),
+                stream: stream.to_string(),
+            },
+        );
+        collected.push_str(&line);
+        collected.push('\n');
+    }
+    collected
+}
+
+/// Run a transcript with `ucm transcript` or, with `fork`, `ucm transcript.fork` on
+/// a copy of `codebase` (UCM's default codebase if None)
+pub async fn run_transcript(
+    app_handle: &AppHandle,
+    session_id: &str,
+    ucm_path: &Path,
+    transcript: &Path,
+    fork: bool,
+    codebase: Option<&UCMCodebase>,
+) -> Result<TranscriptResult, String> {
+    let source = std::fs::read_to_string(transcript)
+        .map_err(|e| format!("Failed to read transcript '{}': {}", transcript.display(), e))?;
+    let blocks = parse_transcript_blocks(&source);
+    let output_path = transcript_output_path(transcript);
+    let path_str = transcript.to_string_lossy().to_string();
+
+    let mut cmd = tokio::process::Command::new(ucm_path);
+    if fork {
+        if let Some(codebase) = codebase {
+            cmd.args(codebase.args());
+        }
+    }
+    cmd.arg(if fork { "transcript.fork" } else { "transcript" })
+        .arg(transcript)
+        .env("PATH", ucm_search_path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(dir) = transcript.parent() {
+        cmd.current_dir(dir);
+    }
+
+    log::info!("Running transcript {} (fork: {})", path_str, fork);
+    let started = Instant::now();
+    let started_at = SystemTime::now();
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn ucm transcript: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+    let (stdout, stderr) = tokio::join!(
+        stream_lines(stdout, "stdout", app_handle, session_id, &path_str),
+        stream_lines(stderr, "stderr", app_handle, session_id, &path_str),
+    );
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("Failed to wait for ucm transcript: {}", e))?;
+
+    // Only trust an output file written by this run
+    let fresh_output = std::fs::metadata(&output_path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified >= started_at);
+    let output_md = if fresh_output {
+        std::fs::read_to_string(&output_path).ok()
+    } else {
+        None
+    };
+
+    let (block_results, mut errors) = evaluate_transcript(&blocks, output_md.as_deref(), status.success());
+    if output_md.is_none() && !stderr.trim().is_empty() {
+        errors.push(stderr.trim().to_string());
+    }
+
+    let file_name = transcript
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let test_results = block_results
+        .iter()
+        .filter(|b| b.status != TranscriptBlockStatus::Skipped)
+        .map(|b| TestResult {
+            name: format!("{}:{}-{} ({})", file_name, b.start_line, b.end_line, b.kind),
+            passed: b.status == TranscriptBlockStatus::Passed,
+            message: b.message.clone().unwrap_or_else(|| "Passed".to_string()),
+        })
+        .collect();
+
+    let success = status.success() && errors.is_empty();
+    log::info!("Transcript {} finished (success: {})", path_str, success);
+    Ok(TranscriptResult {
+        success,
+        output: format!("{}{}", stdout, stderr),
+        errors,
+        output_file: output_md.map(|_| output_path.to_string_lossy().to_string()),
+        blocks: block_results,
+        test_results,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "# Test
+
+```unison
+x = 1
+```
+
+```ucm
+> add
+```
+
+```ucm :hide-all
+> add
+```
+
+```unison :error
+y = missing
+```
+
+```ucm
+> view x
+```
+";
+
+    #[test]
+    fn test_parse_transcript_blocks() {
+        let blocks = parse_transcript_blocks(SOURCE);
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(blocks[0].kind, "unison");
+        assert_eq!((blocks[0].start_line, blocks[0].end_line), (3, 5));
+        assert_eq!(blocks[2].modifiers, vec![":hide-all"]);
+        assert_eq!(blocks[3].modifiers, vec![":error"]);
+        assert_eq!(blocks[4].body, vec!["> view x"]);
+    }
+
+    #[test]
+    fn test_evaluate_successful_transcript() {
+        let blocks = parse_transcript_blocks(SOURCE);
+        let (results, errors) = evaluate_transcript(&blocks, Some("no failures"), true);
+        assert!(errors.is_empty());
+        assert!(results.iter().all(|r| r.status == TranscriptBlockStatus::Passed));
+    }
+
+    #[test]
+    fn test_evaluate_failed_transcript() {
+        let blocks = parse_transcript_blocks(SOURCE);
+        let output = "# Test
+
+```unison
+x = 1
+```
+
+```ucm :added-by-ucm
+  Loading changes detected in scratch.u.
+```
+
+```ucm
+scratch/main> add
+```
+
+```unison :error
+y = missing
+```
+
+```ucm
+  Loading changes detected in scratch.u.
+  I found everything.
+```
+
+🛑
+
+The transcript was expecting an error in the stanza above, but did not encounter one.
+";
+        let (results, errors) = evaluate_transcript(&blocks, Some(output), false);
+        let statuses: Vec<_> = results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TranscriptBlockStatus::Passed,
+                TranscriptBlockStatus::Passed,
+                TranscriptBlockStatus::Passed,
+                TranscriptBlockStatus::Failed,
+                TranscriptBlockStatus::Skipped,
+            ]
+        );
+        assert_eq!(results[3].start_line, 15);
+        assert!(results[3].message.as_deref().unwrap().contains("expecting an error"));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_transcript_output_path() {
+        assert_eq!(
+            transcript_output_path(Path::new("/ws/tests/basic.md")),
+            PathBuf::from("/ws/tests/basic.output.md")
+        );
+    }
+}
@@ -0,0 +1,492 @@
+//! Tree Delta - Batches workspace watcher events into tree changes
+//!
+//! This module provides:
+//! - `TreeDelta::push_event`: turns raw `notify` events of the workspace directories
+//!   into `created`, `renamed` (from and to), `modified` and `deleted` changes, dropping
+//!   hidden and ignored entries (see `ignore_rules`; only the root ignore files apply)
+//! - `TreeDelta::take_batch`: the coalesced changes since the last batch, for one
+//!   `workspace-changed` event
+//! - `TreeDelta::take_watch_changes`: the directories to start and stop watching, so
+//!   the watcher only watches directories that aren't ignored, without recursing
+//!   into `.git`, `node_modules` and the like
+//!
+//! Coalescing keeps one change per path: created then deleted cancels out, deleted then
+//! created is a modification, a rename of a created file is a create at the new path,
+//! and entries inside a deleted directory are folded into the directory's delete.
+//!
+//! The delta knows which entries exist (scanned at start, then kept up to date from the
+//! events), so a hidden or ignored temp file renamed over an existing file - an atomic
+//! save - is a modification rather than a create.
+
+use crate::ignore_rules::{IgnoreRules, IGNORE_FILES};
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+/// One change of the workspace tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceChange {
+    pub kind: ChangeKind,
+    pub path: String,
+    /// Previous path of a renamed entry
+    pub from: Option<String>,
+    /// Best effort for deleted entries, which can't be inspected anymore
+    #[serde(rename = "isDirectory")]
+    pub is_directory: bool,
+}
+
+/// Payload of the `workspace-changed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceChangeBatch {
+    pub root: String,
+    pub changes: Vec<WorkspaceChange>,
+    /// Events were lost (e.g. a kernel queue overflow) - reload the whole tree
+    pub rescan: bool,
+    /// Timestamp of the batch (milliseconds since epoch)
+    #[serde(rename = "detectedAt")]
+    pub detected_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    kind: ChangeKind,
+    path: PathBuf,
+    from: Option<PathBuf>,
+    is_dir: bool,
+}
+
+/// Pending changes of a watched workspace
+pub struct TreeDelta {
+    root: PathBuf,
+    rules: IgnoreRules,
+    changes: Vec<Change>,
+    /// First half of a rename, waiting for its `To` event
+    pending_from: Option<(Option<usize>, PathBuf)>,
+    /// Rename trackers already reported, so the backend's combined event is skipped
+    paired: HashSet<usize>,
+    /// Entries of the tree that aren't ignored
+    known: HashSet<PathBuf>,
+    /// Directories that aren't ignored (the root included), to be watched
+    watched: HashSet<PathBuf>,
+    /// Directories added to and removed from `watched` since `take_watch_changes`
+    watch_queue: Vec<PathBuf>,
+    unwatch_queue: Vec<PathBuf>,
+    rescan: bool,
+}
+
+impl TreeDelta {
+    pub fn new(root: PathBuf) -> Self {
+        let rules = IgnoreRules::load(&root);
+        let mut delta = Self {
+            root,
+            rules,
+            changes: Vec::new(),
+            pending_from: None,
+            paired: HashSet::new(),
+            known: HashSet::new(),
+            watched: HashSet::new(),
+            watch_queue: Vec::new(),
+            unwatch_queue: Vec::new(),
+            rescan: false,
+        };
+        delta.scan(&delta.root.clone());
+        delta
+    }
+
+    /// Record the entries below the directory `dir` as known, and its directories as
+    /// watched
+    fn scan(&mut self, dir: &Path) {
+        let mut stack = vec![dir.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            if self.watched.insert(dir.clone()) {
+                self.watch_queue.push(dir.clone());
+            }
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let path = entry.path();
+                if self.is_ignored(&path, file_type.is_dir()) {
+                    continue;
+                }
+                // Symlinked directories aren't followed, so the scan can't loop
+                if file_type.is_dir() {
+                    stack.push(path.clone());
+                }
+                self.known.insert(path);
+            }
+        }
+    }
+
+    /// Drop `path` and, for a directory, its entries from the known entries
+    fn forget(&mut self, path: &Path) {
+        if !self.known.remove(path) {
+            return;
+        }
+        self.known.retain(|known| !known.starts_with(path));
+        let gone: Vec<PathBuf> = self.watched.iter().filter(|dir| dir.starts_with(path)).cloned().collect();
+        for dir in gone {
+            self.watched.remove(&dir);
+            self.unwatch_queue.push(dir);
+        }
+    }
+
+    /// Directories to start watching and to stop watching since the last call
+    pub fn take_watch_changes(&mut self) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        (std::mem::take(&mut self.watch_queue), std::mem::take(&mut self.unwatch_queue))
+    }
+
+    /// Every directory to watch
+    pub fn watched_dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.watched.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.pending_from.is_none() && !self.rescan
+    }
+
+    /// Add a raw watcher event
+    pub fn push_event(&mut self, event: &Event) {
+        if event.need_rescan() {
+            self.rescan = true;
+        }
+        let ignore_files: Vec<PathBuf> = IGNORE_FILES.iter().map(|file| self.root.join(file)).collect();
+        if event.paths.iter().any(|path| ignore_files.contains(path)) {
+            self.rules = IgnoreRules::load(&self.root);
+            let before = std::mem::take(&mut self.watched);
+            self.known.clear();
+            self.scan(&self.root.clone());
+            self.watch_queue.retain(|dir| !before.contains(dir));
+            let unwatched = before.into_iter().filter(|dir| !self.watched.contains(dir));
+            self.unwatch_queue.extend(unwatched);
+        }
+
+        let Some(path) = event.paths.first().cloned() else {
+            return;
+        };
+        match event.kind {
+            EventKind::Create(kind) => {
+                let is_dir = kind == CreateKind::Folder || path.is_dir();
+                self.created(path, is_dir);
+            }
+            EventKind::Remove(kind) => self.deleted(path, kind == RemoveKind::Folder),
+            EventKind::Modify(ModifyKind::Name(mode)) => self.push_rename(event, mode, path),
+            EventKind::Modify(ModifyKind::Metadata(_)) => {}
+            EventKind::Modify(_) => self.modified(path),
+            _ => {}
+        }
+    }
+
+    fn push_rename(&mut self, event: &Event, mode: RenameMode, path: PathBuf) {
+        let tracker = event.tracker();
+        match mode {
+            RenameMode::Both => {
+                if tracker.is_some_and(|tracker| self.paired.remove(&tracker)) {
+                    return;
+                }
+                if let Some(to) = event.paths.get(1).cloned() {
+                    self.renamed(path, to);
+                }
+            }
+            RenameMode::From => {
+                self.flush_pending_from();
+                self.pending_from = Some((tracker, path));
+            }
+            RenameMode::To => match self.pending_from.take() {
+                Some((from_tracker, from)) if from_tracker == tracker => {
+                    if let Some(tracker) = tracker {
+                        self.paired.insert(tracker);
+                    }
+                    self.renamed(from, path);
+                }
+                pending => {
+                    self.pending_from = pending;
+                    let is_dir = path.is_dir();
+                    self.created(path, is_dir);
+                }
+            },
+            // Backends that report each side alone: tell them apart by existence
+            _ => {
+                if path.exists() {
+                    let is_dir = path.is_dir();
+                    self.created(path, is_dir);
+                } else {
+                    self.deleted(path, false);
+                }
+            }
+        }
+    }
+
+    /// A `From` without a matching `To` moved out of the workspace
+    fn flush_pending_from(&mut self) {
+        if let Some((_, from)) = self.pending_from.take() {
+            self.deleted(from, false);
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => relative.as_os_str().is_empty() || self.rules.is_ignored(relative, is_dir),
+            Err(_) => true,
+        }
+    }
+
+    fn position(&self, path: &Path) -> Option<usize> {
+        self.changes.iter().position(|change| change.path == path)
+    }
+
+    fn created(&mut self, path: PathBuf, is_dir: bool) {
+        if self.is_ignored(&path, is_dir) {
+            return;
+        }
+        self.known.insert(path.clone());
+        if is_dir {
+            self.scan(&path);
+        }
+        match self.position(&path) {
+            Some(i) if self.changes[i].kind == ChangeKind::Deleted => {
+                self.changes[i] = Change { kind: ChangeKind::Modified, path, from: None, is_dir };
+            }
+            Some(_) => {}
+            None => self.changes.push(Change { kind: ChangeKind::Created, path, from: None, is_dir }),
+        }
+    }
+
+    fn modified(&mut self, path: PathBuf) {
+        // Directory modifications are their entries changing, reported on their own
+        if path.is_dir() || self.is_ignored(&path, false) || self.position(&path).is_some() {
+            return;
+        }
+        self.changes.push(Change { kind: ChangeKind::Modified, path, from: None, is_dir: false });
+    }
+
+    fn deleted(&mut self, path: PathBuf, is_dir: bool) {
+        if self.is_ignored(&path, is_dir) {
+            return;
+        }
+        self.forget(&path);
+        match self.position(&path) {
+            Some(i) => {
+                let change = self.changes.remove(i);
+                match change.kind {
+                    ChangeKind::Created => {}
+                    ChangeKind::Renamed => self.deleted(change.from.unwrap_or(path), change.is_dir),
+                    _ => self.changes.push(Change { kind: ChangeKind::Deleted, path, from: None, is_dir }),
+                }
+            }
+            None => self.changes.push(Change { kind: ChangeKind::Deleted, path, from: None, is_dir }),
+        }
+    }
+
+    fn renamed(&mut self, from: PathBuf, to: PathBuf) {
+        let is_dir = to.is_dir();
+        match (self.is_ignored(&from, is_dir), self.is_ignored(&to, is_dir)) {
+            (true, true) => return,
+            // Replacing an existing file is an atomic save
+            (true, false) if !is_dir && self.known.contains(&to) => return self.modified(to),
+            (true, false) => return self.created(to, is_dir),
+            (false, true) => return self.deleted(from, is_dir),
+            (false, false) => {}
+        }
+        self.forget(&from);
+        self.known.insert(to.clone());
+        if is_dir {
+            self.scan(&to);
+        }
+
+        let earlier = self.position(&from).map(|i| self.changes.remove(i));
+        let change = match earlier {
+            Some(Change { kind: ChangeKind::Created, .. }) => {
+                Change { kind: ChangeKind::Created, path: to, from: None, is_dir }
+            }
+            Some(Change { kind: ChangeKind::Renamed, from: Some(original), .. }) if original == to => {
+                return;
+            }
+            Some(Change { kind: ChangeKind::Renamed, from: Some(original), .. }) => {
+                Change { kind: ChangeKind::Renamed, path: to, from: Some(original), is_dir }
+            }
+            _ => Change { kind: ChangeKind::Renamed, path: to, from: Some(from), is_dir },
+        };
+        if let Some(i) = self.position(&change.path) {
+            self.changes.remove(i);
+        }
+        self.changes.push(change);
+    }
+
+    /// The coalesced changes since the last batch, or None if there are none
+    pub fn take_batch(&mut self, detected_at: u64) -> Option<WorkspaceChangeBatch> {
+        self.flush_pending_from();
+        self.paired.clear();
+        let changes = std::mem::take(&mut self.changes);
+        let rescan = std::mem::take(&mut self.rescan);
+        if changes.is_empty() && !rescan {
+            return None;
+        }
+
+        let deleted_dirs: Vec<PathBuf> = changes
+            .iter()
+            .filter(|change| change.kind == ChangeKind::Deleted)
+            .map(|change| change.path.clone())
+            .collect();
+        let changes = changes
+            .into_iter()
+            .filter(|change| {
+                !deleted_dirs
+                    .iter()
+                    .any(|dir| change.path != *dir && change.path.starts_with(dir))
+            })
+            .map(|change| WorkspaceChange {
+                kind: change.kind,
+                path: change.path.to_string_lossy().to_string(),
+                from: change.from.map(|from| from.to_string_lossy().to_string()),
+                is_directory: change.is_dir,
+            })
+            .collect();
+
+        Some(WorkspaceChangeBatch {
+            root: self.root.to_string_lossy().to_string(),
+            changes,
+            rescan,
+            detected_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: EventKind, paths: &[&Path], tracker: Option<usize>) -> Event {
+        let mut event = Event::new(kind);
+        for path in paths {
+            event = event.add_path(path.to_path_buf());
+        }
+        match tracker {
+            Some(tracker) => event.set_tracker(tracker),
+            None => event,
+        }
+    }
+
+    fn kinds(batch: &WorkspaceChangeBatch) -> Vec<(ChangeKind, String, Option<String>)> {
+        batch
+            .changes
+            .iter()
+            .map(|change| (change.kind, change.path.clone(), change.from.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tree_delta_coalesces_changes() {
+        let root = std::env::temp_dir().join(format!("tree-delta-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.u"), "").unwrap();
+        let p = |name: &str| root.join(name);
+        let s = |name: &str| root.join(name).to_string_lossy().to_string();
+        let mut delta = TreeDelta::new(root.clone());
+
+        let create = EventKind::Create(CreateKind::File);
+        let remove = EventKind::Remove(RemoveKind::Any);
+        let write = EventKind::Modify(ModifyKind::Data(notify::event::DataChange::Any));
+        let name = |mode| EventKind::Modify(ModifyKind::Name(mode));
+
+        // Created then written, created then deleted, hidden and ignored entries
+        delta.push_event(&event(create, &[&p("a.u")], None));
+        delta.push_event(&event(write, &[&p("a.u")], None));
+        delta.push_event(&event(create, &[&p("tmp.u")], None));
+        delta.push_event(&event(remove, &[&p("tmp.u")], None));
+        delta.push_event(&event(create, &[&p(".git/index")], None));
+        delta.push_event(&event(create, &[&p("node_modules/x.js")], None));
+        // Rename reported as From, To and Both (inotify)
+        delta.push_event(&event(name(RenameMode::From), &[&p("b.u")], Some(7)));
+        delta.push_event(&event(name(RenameMode::To), &[&p("c.u")], Some(7)));
+        delta.push_event(&event(name(RenameMode::Both), &[&p("b.u"), &p("c.u")], Some(7)));
+        // Deleted then recreated (atomic save)
+        delta.push_event(&event(remove, &[&p("d.u")], None));
+        delta.push_event(&event(create, &[&p("d.u")], None));
+        // Entries of a deleted directory fold into it
+        delta.push_event(&event(remove, &[&p("lib/x.u")], None));
+        delta.push_event(&event(EventKind::Remove(RemoveKind::Folder), &[&p("lib")], None));
+        // A temp file renamed over an existing file (atomic save) or onto a new one
+        delta.push_event(&event(name(RenameMode::Both), &[&p(".main.u.1.0.tmp"), &p("main.u")], None));
+        delta.push_event(&event(name(RenameMode::Both), &[&p(".new.u.1.1.tmp"), &p("new.u")], None));
+        // Moved out of the workspace
+        delta.push_event(&event(name(RenameMode::From), &[&p("e.u")], Some(9)));
+
+        let batch = delta.take_batch(1).unwrap();
+        assert_eq!(
+            kinds(&batch),
+            vec![
+                (ChangeKind::Created, s("a.u"), None),
+                (ChangeKind::Renamed, s("c.u"), Some(s("b.u"))),
+                (ChangeKind::Modified, s("d.u"), None),
+                (ChangeKind::Deleted, s("lib"), None),
+                (ChangeKind::Modified, s("main.u"), None),
+                (ChangeKind::Created, s("new.u"), None),
+                (ChangeKind::Deleted, s("e.u"), None),
+            ]
+        );
+        assert!(delta.is_empty());
+        assert!(delta.take_batch(2).is_none());
+
+        // Renaming a renamed file keeps the original path; renaming it back cancels out
+        delta.push_event(&event(name(RenameMode::Both), &[&p("f.u"), &p("g.u")], None));
+        delta.push_event(&event(name(RenameMode::Both), &[&p("g.u"), &p("h.u")], None));
+        delta.push_event(&event(name(RenameMode::Both), &[&p("x.u"), &p("y.u")], None));
+        delta.push_event(&event(name(RenameMode::Both), &[&p("y.u"), &p("x.u")], None));
+        let batch = delta.take_batch(3).unwrap();
+        assert_eq!(kinds(&batch), vec![(ChangeKind::Renamed, s("h.u"), Some(s("f.u")))]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_tree_delta_tracks_watched_directories() {
+        let root = std::env::temp_dir().join(format!("tree-delta-dirs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in ["lib/util", ".git/objects", "node_modules/x", "out"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let p = |name: &str| root.join(name);
+        let sorted = |mut dirs: Vec<PathBuf>| {
+            dirs.sort();
+            dirs
+        };
+
+        let mut delta = TreeDelta::new(root.clone());
+        let (watch, unwatch) = delta.take_watch_changes();
+        assert_eq!(sorted(watch), vec![root.clone(), p("lib"), p("lib/util"), p("out")]);
+        assert!(unwatch.is_empty());
+
+        // New and renamed directories are watched, deleted ones dropped
+        std::fs::create_dir_all(root.join("src/deep")).unwrap();
+        delta.push_event(&event(EventKind::Create(CreateKind::Folder), &[&p("src")], None));
+        std::fs::rename(root.join("lib"), root.join("pkg")).unwrap();
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        delta.push_event(&event(rename, &[&p("lib"), &p("pkg")], None));
+        let (watch, unwatch) = delta.take_watch_changes();
+        assert_eq!(sorted(watch), vec![p("pkg"), p("pkg/util"), p("src"), p("src/deep")]);
+        assert_eq!(sorted(unwatch), vec![p("lib"), p("lib/util")]);
+
+        // Ignoring a directory stops watching it
+        std::fs::write(root.join(".gitignore"), "/out/\n").unwrap();
+        delta.push_event(&event(EventKind::Create(CreateKind::File), &[&p(".gitignore")], None));
+        assert_eq!(delta.take_watch_changes(), (Vec::new(), vec![p("out")]));
+        assert!(!delta.watched_dirs().any(|dir| dir.starts_with(p("out"))));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
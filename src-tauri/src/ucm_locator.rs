@@ -0,0 +1,375 @@
+//! UCM Locator - Finds the `ucm` binary and checks its version
+//!
+//! This module provides:
+//! - `locate_ucm`: resolves `ucm` from the configured path, the `UNISON_UCM_PATH`
+//!   environment variable, the known install locations or `PATH` (in that order)
+//! - `ucm_search_path`: the `PATH` given to spawned UCM processes, since packaged
+//!   macOS apps don't inherit the shell's `PATH`
+//! - `with_ucm_dirs`: an inherited `PATH` with the install locations it lacks
+//!   prepended, for terminals that must keep the user's own order
+//! - `get_ucm_info`: runs `ucm version` and compares it against the supported range,
+//!   so the frontend can show exactly what was found and where
+
+use serde::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable that points the editor at a specific `ucm` build
+pub const UCM_PATH_ENV: &str = "UNISON_UCM_PATH";
+
+/// Oldest UCM release the editor supports (inclusive) - needs `ucm mcp`
+pub const MIN_UCM_VERSION: UCMVersion = UCMVersion::new(0, 5, 40);
+
+/// First UCM release the editor doesn't know about yet (exclusive)
+pub const MAX_UCM_VERSION: UCMVersion = UCMVersion::new(2, 0, 0);
+
+/// How long `ucm version` may take before the binary is considered broken
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Directories UCM is commonly installed into, searched before `PATH`
+fn known_ucm_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".local/bin")); // Linux/macOS user install
+        dirs.push(home.join("bin")); // User bin
+        dirs.push(home.join(".cargo/bin")); // Cargo install
+    }
+    dirs.extend(
+        [
+            "/opt/homebrew/bin", // Homebrew on Apple Silicon
+            "/usr/local/bin",    // Homebrew/manual install
+            "/usr/bin",          // System install
+            "/bin",
+            "/usr/sbin",
+            "/sbin",
+        ]
+        .iter()
+        .map(PathBuf::from),
+    );
+    dirs
+}
+
+/// `PATH` for spawned UCM processes: the known install locations followed by
+/// the inherited `PATH`
+pub fn ucm_search_path() -> String {
+    let mut entries: Vec<PathBuf> = known_ucm_dirs();
+    if let Some(existing) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&existing) {
+            if !entries.contains(&dir) {
+                entries.push(dir);
+            }
+        }
+    }
+    std::env::join_paths(entries)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// `path` with the known install locations it lacks prepended. Its own entries keep
+/// their order, so a shell finds the same programs it would elsewhere, and `ucm`.
+pub fn with_ucm_dirs(path: Option<&OsStr>) -> OsString {
+    let inherited: Vec<PathBuf> = path.map(|path| std::env::split_paths(path).collect()).unwrap_or_default();
+    let entries = known_ucm_dirs()
+        .into_iter()
+        .filter(|dir| !inherited.contains(dir))
+        .chain(inherited.iter().cloned());
+    std::env::join_paths(entries).unwrap_or_else(|_| path.map(OsStr::to_os_string).unwrap_or_default())
+}
+
+/// Where a `ucm` binary was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UCMSource {
+    /// The path configured in the editor settings
+    Settings,
+    /// The `UNISON_UCM_PATH` environment variable
+    Environment,
+    /// One of the known install locations or `PATH`
+    SearchPath,
+}
+
+/// A resolved `ucm` binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UCMLocation {
+    pub path: PathBuf,
+    pub source: UCMSource,
+}
+
+fn ucm_binary_name() -> &'static str {
+    if cfg!(windows) {
+        "ucm.exe"
+    } else {
+        "ucm"
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        true
+    }
+}
+
+/// Check an explicitly configured path. A directory is searched for the binary.
+fn check_configured(path: &str, source: UCMSource) -> Result<UCMLocation, String> {
+    let mut candidate = PathBuf::from(path);
+    if candidate.is_dir() {
+        candidate.push(ucm_binary_name());
+    }
+    if is_executable(&candidate) {
+        Ok(UCMLocation { path: candidate, source })
+    } else {
+        let origin = match source {
+            UCMSource::Settings => "configured UCM path".to_string(),
+            _ => UCM_PATH_ENV.to_string(),
+        };
+        Err(format!(
+            "UCM not found: {} does not point to an executable ({})",
+            origin,
+            candidate.display()
+        ))
+    }
+}
+
+/// Resolve the `ucm` binary.
+///
+/// An explicitly configured path (settings, then `UNISON_UCM_PATH`) must exist;
+/// it is an error rather than silently falling back to another UCM.
+pub fn locate_ucm(configured: Option<&str>) -> Result<UCMLocation, String> {
+    if let Some(path) = configured.map(str::trim).filter(|p| !p.is_empty()) {
+        return check_configured(path, UCMSource::Settings);
+    }
+
+    if let Ok(path) = std::env::var(UCM_PATH_ENV) {
+        if !path.trim().is_empty() {
+            return check_configured(path.trim(), UCMSource::Environment);
+        }
+    }
+
+    let search_path = ucm_search_path();
+    std::env::split_paths(&search_path)
+        .map(|dir| dir.join(ucm_binary_name()))
+        .find(|candidate| is_executable(candidate))
+        .map(|path| UCMLocation {
+            path,
+            source: UCMSource::SearchPath,
+        })
+        .ok_or_else(|| "UCM not found in any known location or PATH".to_string())
+}
+
+/// A UCM release version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct UCMVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl UCMVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+}
+
+impl std::fmt::Display for UCMVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Parse the release version from `ucm version` output, e.g.
+/// `ucm version: release/0.5.41 (built on 2025-06-10)`.
+/// Development builds report a commit hash and yield `None`.
+pub fn parse_ucm_version(output: &str) -> Option<UCMVersion> {
+    output
+        .split(|c: char| c.is_whitespace() || c == '/' || c == '(' || c == ')')
+        .map(|token| token.trim_start_matches('v'))
+        .find_map(|token| {
+            let mut parts = token.split('.');
+            let major = parts.next()?.parse().ok()?;
+            let minor = parts.next()?.parse().ok()?;
+            let patch = parts.next()?.parse().ok()?;
+            Some(UCMVersion::new(major, minor, patch))
+        })
+}
+
+/// How a UCM version relates to the supported range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UCMSupport {
+    Supported,
+    TooOld,
+    TooNew,
+    /// The version couldn't be parsed (e.g. a development build)
+    Unknown,
+}
+
+pub fn check_ucm_version(version: Option<UCMVersion>) -> UCMSupport {
+    match version {
+        None => UCMSupport::Unknown,
+        Some(v) if v < MIN_UCM_VERSION => UCMSupport::TooOld,
+        Some(v) if v >= MAX_UCM_VERSION => UCMSupport::TooNew,
+        Some(_) => UCMSupport::Supported,
+    }
+}
+
+/// Everything known about the UCM the editor would use, for `get_ucm_info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UCMInfo {
+    pub found: bool,
+    /// Resolved binary path
+    pub path: Option<String>,
+    pub source: Option<UCMSource>,
+    /// Raw `ucm version` output
+    #[serde(rename = "versionOutput")]
+    pub version_output: Option<String>,
+    pub version: Option<String>,
+    pub support: Option<UCMSupport>,
+    #[serde(rename = "minVersion")]
+    pub min_version: String,
+    #[serde(rename = "maxVersion")]
+    pub max_version: String,
+    /// Directories that were searched when no path was configured
+    #[serde(rename = "searchedLocations")]
+    pub searched_locations: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Run `ucm version` on a resolved binary
+async fn run_ucm_version(path: &Path) -> Result<String, String> {
+    let output = tokio::time::timeout(
+        VERSION_TIMEOUT,
+        tokio::process::Command::new(path)
+            .arg("version")
+            .env("PATH", ucm_search_path())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| format!("`{} version` timed out", path.display()))?
+    .map_err(|e| format!("Failed to run `{} version`: {}", path.display(), e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!(
+            "`{} version` failed ({}): {}",
+            path.display(),
+            output.status,
+            if stderr.is_empty() { stdout } else { stderr }
+        ));
+    }
+    Ok(stdout)
+}
+
+/// Locate UCM, run `ucm version` and check it against the supported range
+pub async fn get_ucm_info(configured: Option<&str>) -> UCMInfo {
+    let mut info = UCMInfo {
+        found: false,
+        path: None,
+        source: None,
+        version_output: None,
+        version: None,
+        support: None,
+        min_version: MIN_UCM_VERSION.to_string(),
+        max_version: MAX_UCM_VERSION.to_string(),
+        searched_locations: std::env::split_paths(&ucm_search_path())
+            .map(|dir| dir.display().to_string())
+            .collect(),
+        error: None,
+    };
+
+    let location = match locate_ucm(configured) {
+        Ok(location) => location,
+        Err(e) => {
+            info.error = Some(e);
+            return info;
+        }
+    };
+    info.found = true;
+    info.path = Some(location.path.display().to_string());
+    info.source = Some(location.source);
+
+    match run_ucm_version(&location.path).await {
+        Ok(output) => {
+            let version = parse_ucm_version(&output);
+            info.version = version.map(|v| v.to_string());
+            info.support = Some(check_ucm_version(version));
+            info.version_output = Some(output);
+        }
+        Err(e) => info.error = Some(e),
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ucm_version() {
+        assert_eq!(
+            parse_ucm_version("ucm version: release/0.5.41 (built on 2025-06-10)"),
+            Some(UCMVersion::new(0, 5, 41))
+        );
+        assert_eq!(
+            parse_ucm_version("ucm version: 1.0.2 (built on 2025-12-01)"),
+            Some(UCMVersion::new(1, 0, 2))
+        );
+        assert_eq!(parse_ucm_version("v0.5.27"), Some(UCMVersion::new(0, 5, 27)));
+        assert_eq!(
+            parse_ucm_version("ucm version: 6b3d2a1 (built on 2025-06-10)"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_ucm_version() {
+        assert_eq!(check_ucm_version(Some(MIN_UCM_VERSION)), UCMSupport::Supported);
+        assert_eq!(check_ucm_version(Some(UCMVersion::new(0, 5, 2))), UCMSupport::TooOld);
+        assert_eq!(check_ucm_version(Some(MAX_UCM_VERSION)), UCMSupport::TooNew);
+        assert_eq!(check_ucm_version(None), UCMSupport::Unknown);
+    }
+
+    #[test]
+    fn test_search_path_starts_with_known_dirs() {
+        let path = ucm_search_path();
+        let entries: Vec<PathBuf> = std::env::split_paths(&path).collect();
+        assert!(entries.contains(&PathBuf::from("/usr/local/bin")));
+        let unique: std::collections::HashSet<&PathBuf> = entries.iter().collect();
+        assert_eq!(unique.len(), entries.len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_with_ucm_dirs_keeps_inherited_order() {
+        let path = with_ucm_dirs(Some(OsStr::new("/custom/bin:/usr/bin:/usr/local/bin")));
+        let entries: Vec<PathBuf> = std::env::split_paths(&path).collect();
+        // The inherited entries come last, in their own order
+        assert_eq!(
+            entries[entries.len() - 3..],
+            [PathBuf::from("/custom/bin"), PathBuf::from("/usr/bin"), PathBuf::from("/usr/local/bin")]
+        );
+        assert!(entries.contains(&PathBuf::from("/opt/homebrew/bin")));
+        let unique: std::collections::HashSet<&PathBuf> = entries.iter().collect();
+        assert_eq!(unique.len(), entries.len());
+    }
+
+    #[test]
+    fn test_configured_path_must_exist() {
+        let err = locate_ucm(Some("/definitely/not/here/ucm")).unwrap_err();
+        assert!(err.contains("configured UCM path"));
+    }
+}
@@ -1,28 +1,73 @@
 //! UCM PTY Manager - Spawns and manages UCM with pseudo-terminal for interactive use
 //!
 //! This module provides:
-//! - PTY-based UCM spawning for full terminal emulation
+//! - PTY-based UCM spawning for full terminal emulation (on `pty_session::PtyProcess`)
 //! - Non-blocking communication via channels (no hanging!)
-//! - Context detection by parsing UCM prompt
+//! - Context detection by parsing UCM prompt (project, branch and namespace path)
+//! - Prompt state tracking (idle, busy, confirmations and numbered choices),
+//!   emitted as `ucm-prompt-state` events
 //! - Event emission for output and context changes
-//! - Dynamic port allocation for API and LSP servers
+//! - A byte-accurate scrollback ring buffer, so a remounted terminal can reattach
+//! - Request/response command execution (`exec`) that waits for the next prompt,
+//!   serialized with user typing
+//! - Process control: graceful exit with SIGTERM/SIGKILL escalation, interrupts,
+//!   and exit status reporting
+//! - Dynamic port allocation for API and LSP servers, with LSP startup verification
+//! - Optional codebase selection (`--codebase` / `--codebase-create`)
+//! - Codebase lock detection, reporting the process holding the lock (see `codebase_lock`)
+//! - All events are tagged with the owning session id (see `ucm_session`)
 
-use crate::port_utils::find_available_port;
+use crate::codebase_lock::{codebase_root, find_lock_holder, LockHolder, EDITOR_PID_ENV};
+use crate::lsp_proxy::LspProxy;
+use crate::port_utils::find_available_port_excluding;
+use crate::pty_session::{
+    apply_terminal_env, PtyAttachSnapshot, PtyExitStatus, PtyOutput, PtyProcess, INITIAL_COLS,
+    INITIAL_ROWS,
+};
+use crate::ucm_session::{emit_session_event, UCMCodebase};
 use parking_lot::Mutex;
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use portable_pty::CommandBuilder;
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
 use std::sync::Arc;
-use std::thread;
 use std::time::Duration;
-use tauri::{AppHandle, Emitter};
-use tokio::sync::mpsc;
+use tauri::AppHandle;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{oneshot, Mutex as TokioMutex, Notify};
 
-/// Current UCM context (project and branch)
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Current UCM context (project, branch and namespace path)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UCMContext {
     pub project: Option<String>,
     pub branch: Option<String>,
+    /// Namespace path within the branch (`lib.utils` in `proj/main:lib.utils>`),
+    /// None at the branch root
+    pub path: Option<String>,
+}
+
+/// What UCM is doing, as far as its terminal output shows (`ucm-prompt-state` payload)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum UCMPromptState {
+    /// Starting up or running a command
+    Busy,
+    /// Waiting for a command at the main prompt
+    Idle { context: UCMContext },
+    /// Asking a yes/no question in the middle of a command
+    Confirmation { question: String },
+    /// Asking to pick one entry of a numbered list
+    Choice {
+        question: String,
+        options: Vec<PromptChoice>,
+    },
+}
+
+/// One entry of a numbered sub-prompt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptChoice {
+    pub number: u32,
+    pub label: String,
 }
 
 /// Ports allocated for UCM services
@@ -32,16 +77,186 @@ pub struct UCMPorts {
     pub lsp_port: u16,
 }
 
+/// Environment variable UCM reads to choose its LSP port (defaults to 5757)
+const UCM_LSP_PORT_ENV: &str = "UNISON_LSP_PORT";
+
+/// How long to wait for UCM to start listening on its LSP port
+const LSP_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the LSP server gets to answer the `initialize` probe
+const LSP_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long UCM gets to honour `exit` before being sent SIGTERM
+const GRACEFUL_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The reader keeps the last `LINE_BUFFER_KEEP` bytes of decoded output once it
+/// grows past `LINE_BUFFER_MAX`, for prompt and error detection
+const LINE_BUFFER_MAX: usize = 4096;
+const LINE_BUFFER_KEEP: usize = 2048;
+
+/// Only the tail of a command's output is checked for the closing prompt
+const PROMPT_SCAN_BYTES: usize = 1024;
+
+/// Glyphs UCM puts on a line of their own to open a failure message block
+const UCM_FAILURE_GLYPHS: &[&str] = &["⚠", "❗", "❓", "🚫"];
+
+/// Glyphs UCM puts on a line of their own to open a success message block
+const UCM_SUCCESS_GLYPHS: &[&str] = &["✅"];
+
+/// Event payload for `ucm-lsp-port-error`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LspPortError {
+    pub port: u16,
+    pub message: String,
+}
+
+/// Event payload for `ucm-lsp-ready`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LspReady {
+    pub port: u16,
+}
+
+/// Result of a command run with `exec`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UCMExecResult {
+    pub command: String,
+    /// Output between the echoed command and the next prompt, without ANSI escapes
+    pub output: String,
+    /// Outcome read from UCM's message blocks (see `exec_status`)
+    pub status: UCMExecStatus,
+    /// Context shown by the prompt that ended the command
+    pub context: Option<UCMContext>,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: u64,
+}
+
+/// Outcome of an `exec`. UCM has no exit codes, so it is read from the glyph that
+/// opens its first message block; output without one is `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UCMExecStatus {
+    Succeeded,
+    Failed,
+    Unknown,
+}
+
+/// An in-flight `exec`, fed by the reader thread until the next prompt appears
+struct ExecCapture {
+    output: Vec<u8>,
+    done: Option<oneshot::Sender<()>>,
+}
+
+/// Keeps UCM's prompt state and emits `ucm-prompt-state` whenever it changes
+struct PromptTracker {
+    state: Mutex<UCMPromptState>,
+    /// Woken on every state change
+    changed: Notify,
+    app_handle: AppHandle,
+    session_id: String,
+}
+
+impl PromptTracker {
+    fn update(&self, state: UCMPromptState) {
+        {
+            let mut current = self.state.lock();
+            if *current == state {
+                return;
+            }
+            *current = state.clone();
+        }
+        self.changed.notify_waiters();
+        emit_session_event(&self.app_handle, &self.session_id, "ucm-prompt-state", state);
+    }
+}
+
+/// Wait until UCM is at its main prompt. A sub-prompt belongs to the command that
+/// asked it, so it fails right away instead of being answered by the next command.
+async fn wait_until_idle(
+    state: &Mutex<UCMPromptState>,
+    changed: &Notify,
+    deadline: tokio::time::Instant,
+) -> Result<(), String> {
+    loop {
+        // Registered before the check, so a change in between isn't missed
+        let notified = changed.notified();
+        match &*state.lock() {
+            UCMPromptState::Idle { .. } => return Ok(()),
+            UCMPromptState::Confirmation { question } | UCMPromptState::Choice { question, .. } => {
+                return Err(format!("UCM is waiting for an answer: {}", question))
+            }
+            UCMPromptState::Busy => {}
+        }
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            return Err("UCM is busy running another command".to_string());
+        }
+    }
+}
+
+/// Incremental UTF-8 decoder that carries sequences split across reads over to the next one
+#[derive(Default)]
+struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        let mut rest: &[u8] = &self.pending;
+
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        // Invalid bytes: replace them and keep going
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &invalid[len..];
+                        }
+                        // Incomplete sequence at the end: wait for the next read
+                        None => {
+                            rest = invalid;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        let remaining = rest.to_vec();
+        self.pending = remaining;
+        text
+    }
+}
+
+/// Event payload for `ucm-file-lock-error`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLockError {
+    pub message: String,
+    /// The process holding the lock, if it could be found
+    pub holder: Option<LockHolder>,
+}
+
 /// UCM PTY Manager - manages a UCM process with PTY using channels for non-blocking I/O
 pub struct UCMPtyManager {
-    /// Channel to send input to PTY writer thread
-    write_tx: mpsc::Sender<Vec<u8>>,
-    /// Channel to send resize commands
-    resize_tx: mpsc::Sender<(u16, u16)>,
+    /// UCM running in the PTY (writer task, reader thread and scrollback)
+    process: PtyProcess,
     /// Current detected context
     current_context: Arc<Mutex<UCMContext>>,
-    /// Flag to signal threads to stop
-    running: Arc<Mutex<bool>>,
+    /// The line the user is typing (sent, but not yet submitted with Enter).
+    /// Held for the whole of an `exec`, which queues user input behind the command.
+    input_line: TokioMutex<String>,
+    /// Output capture of the running `exec`, if any
+    exec_capture: Arc<Mutex<Option<ExecCapture>>>,
+    /// Idle/busy/sub-prompt state, updated from output and submitted input
+    prompt: Arc<PromptTracker>,
     /// Allocated ports for this UCM instance
     #[allow(dead_code)]
     ports: UCMPorts,
@@ -52,78 +267,53 @@ impl UCMPtyManager {
     ///
     /// # Arguments
     /// * `app_handle` - Tauri app handle for emitting events
+    /// * `session_id` - Session the events emitted by this UCM are tagged with
+    /// * `ucm_path` - The `ucm` binary, resolved by `ucm_locator::locate_ucm`
+    /// * `codebase` - Codebase to open instead of UCM's default one
     /// * `cwd` - Optional working directory for UCM (for file loading)
+    /// * `reserved_ports` - Ports allocated to other sessions that must not be reused
     ///
     /// # Returns
     /// A tuple of (UCMPtyManager, UCMPorts) with the manager and allocated ports
-    pub async fn spawn(app_handle: AppHandle, cwd: Option<String>) -> Result<(Self, UCMPorts), String> {
-        log::info!("UCM PTY spawn starting...");
+    pub async fn spawn(
+        app_handle: AppHandle,
+        session_id: String,
+        ucm_path: &Path,
+        codebase: Option<&UCMCodebase>,
+        cwd: Option<String>,
+        reserved_ports: &[u16],
+    ) -> Result<(Self, UCMPorts), String> {
+        log::info!("UCM PTY spawn starting for session {}...", session_id);
 
         // Find available port for API server
-        let api_port = find_available_port(5858)
+        let api_port = find_available_port_excluding(5858, reserved_ports)
             .ok_or("Could not find available port for UCM API server")?;
 
-        // LSP port is hardcoded in UCM at 5757
-        let lsp_port: u16 = 5757;
+        // Prefer UCM's default LSP port, moving on if another UCM (or anything else) holds it
+        let mut excluded = reserved_ports.to_vec();
+        excluded.push(api_port);
+        let lsp_port = find_available_port_excluding(5757, &excluded)
+            .ok_or("Could not find available port for UCM LSP server")?;
+        if lsp_port != 5757 {
+            log::warn!("Default UCM LSP port 5757 is in use, using {} instead", lsp_port);
+        }
 
-        log::info!("Allocating UCM ports - API: {}, LSP: {} (hardcoded)", api_port, lsp_port);
+        log::info!("Allocating UCM ports - API: {}, LSP: {}", api_port, lsp_port);
 
         let ports = UCMPorts { api_port, lsp_port };
 
-        // Create PTY system
-        let pty_system = native_pty_system();
-
-        // Configure PTY size
-        let pair = pty_system
-            .openpty(PtySize {
-                rows: 24,
-                cols: 80,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| format!("Failed to open PTY: {}", e))?;
-
-        log::info!("PTY created successfully");
-
         // Build command for UCM
-        let mut cmd = CommandBuilder::new("ucm");
+        let mut cmd = CommandBuilder::new(ucm_path);
+        if let Some(codebase) = codebase {
+            log::info!("Opening codebase {} (create: {})", codebase.path, codebase.create);
+            cmd.args(codebase.args());
+        }
         cmd.arg("--port");
         cmd.arg(api_port.to_string());
+        cmd.env(UCM_LSP_PORT_ENV, lsp_port.to_string());
+        cmd.env(EDITOR_PID_ENV, std::process::id().to_string());
 
-        // Set PATH for GUI apps on macOS
-        let path_additions = vec![
-            "/opt/homebrew/bin",
-            "/usr/local/bin",
-            "/usr/bin",
-            "/bin",
-            "/usr/sbin",
-            "/sbin",
-        ];
-
-        let mut all_paths = path_additions.iter().map(|s| s.to_string()).collect::<Vec<_>>();
-        if let Some(home) = dirs::home_dir() {
-            all_paths.insert(0, format!("{}/.local/bin", home.display()));
-            all_paths.insert(0, format!("{}/bin", home.display()));
-        }
-
-        let existing_path = std::env::var("PATH").unwrap_or_default();
-        let new_path = if existing_path.is_empty() {
-            all_paths.join(":")
-        } else {
-            format!("{}:{}", all_paths.join(":"), existing_path)
-        };
-
-        cmd.env("PATH", new_path);
-        cmd.env("TERM", "xterm-256color");
-        cmd.env("LANG", "en_US.UTF-8");
-        cmd.env("LC_ALL", "en_US.UTF-8");
-        cmd.env("COLORTERM", "truecolor");
-        cmd.env("TERMINFO_DIRS", "/usr/share/terminfo:/lib/terminfo:/etc/terminfo");
-
-        if let Some(home) = dirs::home_dir() {
-            cmd.env("HOME", home.to_string_lossy().to_string());
-        }
-
+        apply_terminal_env(&mut cmd);
         cmd.env("CLICOLOR", "1");
         cmd.env("CLICOLOR_FORCE", "1");
         cmd.env("FORCE_COLOR", "1");
@@ -138,163 +328,159 @@ impl UCMPtyManager {
             cmd.cwd(home);
         }
 
-        // Spawn UCM in the PTY
-        let _child = pair
-            .slave
-            .spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn UCM: {}", e))?;
-
-        log::info!("UCM process spawned successfully");
-
-        let master = pair.master;
-        let writer = master
-            .take_writer()
-            .map_err(|e| format!("Failed to get PTY writer: {}", e))?;
-        let mut reader = master
-            .try_clone_reader()
-            .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
-
         let current_context = Arc::new(Mutex::new(UCMContext::default()));
-        let running = Arc::new(Mutex::new(true));
-        let master = Arc::new(Mutex::new(master));
+        let exec_capture: Arc<Mutex<Option<ExecCapture>>> = Arc::new(Mutex::new(None));
+        let prompt = Arc::new(PromptTracker {
+            state: Mutex::new(UCMPromptState::Busy),
+            changed: Notify::new(),
+            app_handle: app_handle.clone(),
+            session_id: session_id.clone(),
+        });
 
-        // Create channels for non-blocking communication
-        let (write_tx, mut write_rx) = mpsc::channel::<Vec<u8>>(100);
-        let (resize_tx, mut resize_rx) = mpsc::channel::<(u16, u16)>(10);
+        // Output handler - runs on the reader thread for every read
+        let context_clone = current_context.clone();
+        let app_handle_clone = app_handle.clone();
+        let session_id_clone = session_id.clone();
+        let exec_capture_clone = exec_capture.clone();
+        let prompt_clone = prompt.clone();
+        let mut line_buffer = String::new();
+        let mut utf8_decoder = Utf8Decoder::default();
+        let mut reads_since_parse = 0u32;
+        let mut lsp_port_error_reported = false;
+        let lock_root = codebase_root(codebase);
 
-        // Writer thread - handles writes and resizes via channels
-        let writer = Arc::new(Mutex::new(writer));
-        let writer_clone = writer.clone();
-        let master_clone = master.clone();
-        let running_writer = running.clone();
+        let on_output = move |output: &[u8], offset: u64| {
+            // Emit output event immediately - don't block on parsing
+            emit_session_event(
+                &app_handle_clone,
+                &session_id_clone,
+                "ucm-pty-output",
+                PtyOutput {
+                    data: output.to_vec(),
+                    offset,
+                },
+            );
 
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    Some(data) = write_rx.recv() => {
-                        let mut w = writer_clone.lock();
-                        if let Err(e) = w.write_all(&data) {
-                            log::error!("PTY write error: {}", e);
-                        }
-                        let _ = w.flush();
-                    }
-                    Some((rows, cols)) = resize_rx.recv() => {
-                        let m = master_clone.lock();
-                        if let Err(e) = m.resize(PtySize {
-                            rows,
-                            cols,
-                            pixel_width: 0,
-                            pixel_height: 0,
-                        }) {
-                            log::error!("PTY resize error: {}", e);
-                        } else {
-                            log::debug!("PTY resized to {}x{}", cols, rows);
-                        }
-                    }
-                    else => {
-                        if !*running_writer.lock() {
-                            break;
-                        }
-                        tokio::time::sleep(Duration::from_millis(10)).await;
+            // Feed a running exec and wake it once UCM is back at its prompt
+            if let Some(capture) = exec_capture_clone.lock().as_mut() {
+                capture.output.extend_from_slice(output);
+                let tail = &capture.output[capture.output.len().saturating_sub(PROMPT_SCAN_BYTES)..];
+                if ends_with_prompt(&strip_ansi(&String::from_utf8_lossy(tail))) {
+                    if let Some(done) = capture.done.take() {
+                        let _ = done.send(());
                     }
                 }
             }
-            log::info!("UCM PTY writer task exiting");
-        });
 
-        // Reader thread - reads output and emits events
-        let context_clone = current_context.clone();
-        let running_clone = running.clone();
-        let app_handle_clone = app_handle.clone();
+            // Decode every read, so characters split across reads aren't lost
+            line_buffer.push_str(&utf8_decoder.decode(output));
 
-        thread::spawn(move || {
-            // Larger buffer for better throughput during heavy output (e.g., run commands)
-            let mut buffer = [0u8; 32768];
-            let mut line_buffer = String::new();
-            let mut reads_since_parse = 0u32;
+            // The last line of output tells whether UCM is waiting for input
+            prompt_clone.update(detect_prompt_state(&strip_ansi(&line_buffer)));
 
-            loop {
-                if !*running_clone.lock() {
-                    break;
-                }
+            // Only parse occasionally to reduce overhead during heavy output
+            reads_since_parse += 1;
+            let should_parse = reads_since_parse >= 5 || output.len() < 1000;
 
-                match reader.read(&mut buffer) {
-                    Ok(0) => {
-                        // EOF - UCM exited (user typed 'exit' or process terminated)
-                        log::info!("UCM PTY EOF - process exited");
-                        *running_clone.lock() = false;
-                        // Notify frontend that UCM has exited
-                        let _ = app_handle_clone.emit("ucm-process-exited", ());
-                        break;
-                    }
-                    Ok(n) => {
-                        let output = &buffer[..n];
+            if should_parse {
+                reads_since_parse = 0;
 
-                        // Emit output event immediately - don't block on parsing
-                        if let Err(e) = app_handle_clone.emit("ucm-pty-output", output.to_vec()) {
-                            log::error!("Failed to emit ucm-pty-output: {}", e);
-                        }
+                // Parse for context changes and errors
 
-                        // Only parse occasionally to reduce overhead during heavy output
-                        reads_since_parse += 1;
-                        let should_parse = reads_since_parse >= 5 || n < 1000;
-
-                        if should_parse {
-                            reads_since_parse = 0;
-
-                            // Parse for context changes and errors
-                            if let Ok(text) = std::str::from_utf8(output) {
-                                line_buffer.push_str(text);
-
-                                // Check for file lock error
-                                if line_buffer.contains("Failed to obtain a file lock") {
-                                    log::warn!("UCM file lock error detected");
-                                    *running_clone.lock() = false;
-                                    let _ = app_handle_clone.emit("ucm-file-lock-error", ());
-                                    break;
-                                }
-
-                                // Check for context changes (only when we see a prompt indicator)
-                                if line_buffer.contains('>') {
-                                    if let Some(new_context) = parse_ucm_prompt(&line_buffer) {
-                                        let mut ctx = context_clone.lock();
-                                        if ctx.project != new_context.project || ctx.branch != new_context.branch {
-                                            *ctx = new_context.clone();
-                                            let _ = app_handle_clone.emit("ucm-context-changed", new_context);
-                                        }
-                                    }
-                                }
-
-                                // Trim buffer
-                                if line_buffer.len() > 1024 {
-                                    line_buffer = line_buffer[line_buffer.len() - 512..].to_string();
-                                }
-                            }
-                        }
+                // Check for file lock error
+                if line_buffer.contains("Failed to obtain a file lock") {
+                    log::warn!("UCM file lock error detected");
+                    let holder = lock_root.as_deref().and_then(find_lock_holder);
+                    if let Some(holder) = &holder {
+                        log::warn!("Codebase lock held by {}: {}", holder.pid, holder.command_line);
                     }
-                    Err(e) => {
-                        // Check if it's a would-block error (expected for non-blocking)
-                        if e.kind() == std::io::ErrorKind::WouldBlock {
-                            thread::sleep(Duration::from_millis(10));
-                            continue;
+                    emit_session_event(
+                        &app_handle_clone,
+                        &session_id_clone,
+                        "ucm-file-lock-error",
+                        FileLockError {
+                            message: "Failed to obtain a file lock on the codebase".to_string(),
+                            holder,
+                        },
+                    );
+                    return ControlFlow::Break(());
+                }
+
+                // Check for UCM failing to bind its LSP port (GHC reports
+                // "bind: resource busy (Address already in use)")
+                if !lsp_port_error_reported && line_buffer.contains("Address already in use") {
+                    lsp_port_error_reported = true;
+                    log::error!("UCM could not bind LSP port {}: already in use", lsp_port);
+                    emit_session_event(
+                        &app_handle_clone,
+                        &session_id_clone,
+                        "ucm-lsp-port-error",
+                        LspPortError {
+                            port: lsp_port,
+                            message: format!(
+                                "LSP port {} is already in use by another process",
+                                lsp_port
+                            ),
+                        },
+                    );
+                }
+
+                // Check for context changes (only when we see a prompt indicator)
+                if line_buffer.contains('>') {
+                    if let Some(new_context) = parse_ucm_prompt(&strip_ansi(&line_buffer)) {
+                        let mut ctx = context_clone.lock();
+                        if *ctx != new_context {
+                            *ctx = new_context.clone();
+                            emit_session_event(
+                                &app_handle_clone,
+                                &session_id_clone,
+                                "ucm-context-changed",
+                                new_context,
+                            );
                         }
-                        // Other errors - UCM likely crashed or was killed
-                        log::error!("PTY read error: {}", e);
-                        *running_clone.lock() = false;
-                        let _ = app_handle_clone.emit("ucm-process-exited", ());
-                        break;
                     }
                 }
             }
 
-            log::info!("UCM PTY reader thread exiting");
-        });
+            // Trim buffer, keeping enough lines to see a numbered sub-prompt
+            if line_buffer.len() > LINE_BUFFER_MAX {
+                let mut cut = line_buffer.len() - LINE_BUFFER_KEEP;
+                while !line_buffer.is_char_boundary(cut) {
+                    cut += 1;
+                }
+                line_buffer.drain(..cut);
+            }
+            ControlFlow::Continue(())
+        };
+
+        // UCM exited (user typed 'exit' or process terminated)
+        let exit_app_handle = app_handle.clone();
+        let exit_session_id = session_id.clone();
+        let exit_capture = exec_capture.clone();
+        let on_exit = move |status: PtyExitStatus| {
+            // Fail a running exec instead of letting it time out
+            exit_capture.lock().take();
+            // Notify frontend that UCM has exited, with its exit status
+            emit_session_event(&exit_app_handle, &exit_session_id, "ucm-process-exited", status);
+        };
+
+        // Spawn UCM in the PTY, with the writer task and reader thread
+        let process = PtyProcess::spawn("UCM", cmd, INITIAL_ROWS, INITIAL_COLS, on_output, on_exit)?;
+
+        // Verify UCM actually starts listening on the LSP port we gave it
+        tokio::spawn(verify_lsp_listening(
+            app_handle.clone(),
+            session_id.clone(),
+            lsp_port,
+            process.running_handle(),
+        ));
 
         let manager = Self {
-            write_tx,
-            resize_tx,
+            process,
             current_context,
-            running,
+            input_line: TokioMutex::new(String::new()),
+            exec_capture,
+            prompt,
             ports: ports.clone(),
         };
 
@@ -302,12 +488,101 @@ impl UCMPtyManager {
         Ok((manager, ports))
     }
 
-    /// Write data to UCM's stdin (async, via channel)
+    /// Write user input to UCM's stdin (async, via channel).
+    /// Waits while an `exec` is running, so keystrokes never land inside its command.
     pub async fn write(&self, data: &[u8]) -> Result<(), String> {
-        self.write_tx
-            .send(data.to_vec())
-            .await
-            .map_err(|e| format!("Failed to send write to PTY: {}", e))
+        let mut input_line = self.input_line.lock().await;
+        track_input_line(&mut input_line, data);
+        self.send(data).await
+    }
+
+    /// Send raw bytes to the PTY writer task
+    async fn send(&self, data: &[u8]) -> Result<(), String> {
+        // Submitting a line (command or sub-prompt answer) keeps UCM busy until the next prompt
+        if data.contains(&b'\r') || data.contains(&b'\n') {
+            self.prompt.update(UCMPromptState::Busy);
+        }
+        self.process.send(data).await
+    }
+
+    /// Run a UCM command and wait for the next prompt.
+    ///
+    /// The command is echoed in the terminal like typed input. A line the user has
+    /// half-typed is cleared first and typed again afterwards, and user input sent
+    /// while the command runs is queued until it finishes.
+    ///
+    /// While UCM is busy with another command, the exec waits for the prompt (within
+    /// `timeout`); at a sub-prompt it fails, so the command never lands in another one.
+    pub async fn exec(&self, command: &str, timeout: Duration) -> Result<UCMExecResult, String> {
+        let command = command.trim();
+        if command.is_empty() || command.contains(['\n', '\r']) {
+            return Err("UCM command must be a single non-empty line".to_string());
+        }
+        if !self.is_running() {
+            return Err("UCM is not running".to_string());
+        }
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        wait_until_idle(&self.prompt.state, &self.prompt.changed, deadline).await?;
+        let input_line = self.input_line.lock().await;
+        if !self.is_running() {
+            return Err("UCM is not running".to_string());
+        }
+        // Typed input (or another exec) may have started a command while this one waited
+        if !matches!(self.prompt_state(), UCMPromptState::Idle { .. }) {
+            return Err("UCM is busy running another command".to_string());
+        }
+
+        let (done_tx, done_rx) = oneshot::channel();
+        *self.exec_capture.lock() = Some(ExecCapture {
+            output: Vec::new(),
+            done: Some(done_tx),
+        });
+
+        let started = std::time::Instant::now();
+        let mut sent = Ok(());
+        if !input_line.is_empty() {
+            // Ctrl-U clears the half-typed line so it isn't prefixed to the command
+            sent = self.send(&[0x15]).await;
+        }
+        if sent.is_ok() {
+            sent = self.send(format!("{}\n", command).as_bytes()).await;
+        }
+        let finished = match sent {
+            Ok(()) => tokio::time::timeout_at(deadline, done_rx).await,
+            Err(e) => {
+                self.exec_capture.lock().take();
+                return Err(e);
+            }
+        };
+        let capture = self.exec_capture.lock().take();
+
+        if !input_line.is_empty() {
+            self.send(input_line.as_bytes()).await?;
+        }
+        drop(input_line);
+
+        match finished {
+            Err(_) => Err(format!(
+                "UCM command `{}` did not finish within {} seconds",
+                command,
+                timeout.as_secs()
+            )),
+            Ok(Err(_)) => Err(format!("UCM exited while running `{}`", command)),
+            Ok(Ok(())) => {
+                let raw = capture.map(|c| c.output).unwrap_or_default();
+                let (output, context) = extract_exec_output(&raw);
+                let status = exec_status(&output);
+                log::info!("UCM exec `{}` finished ({:?})", command, status);
+                Ok(UCMExecResult {
+                    command: command.to_string(),
+                    output,
+                    status,
+                    context,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                })
+            }
+        }
     }
 
     /// Send a switch command to UCM
@@ -321,75 +596,458 @@ impl UCMPtyManager {
         self.current_context.lock().clone()
     }
 
+    /// Current prompt state (idle, busy or waiting at a sub-prompt)
+    pub fn prompt_state(&self) -> UCMPromptState {
+        self.prompt.state.lock().clone()
+    }
+
+    /// Shared handle to the detected context, kept up to date by the reader thread
+    pub fn context_handle(&self) -> Arc<Mutex<UCMContext>> {
+        self.current_context.clone()
+    }
+
     /// Resize the PTY (async, via channel)
     pub async fn resize(&self, rows: u16, cols: u16) -> Result<(), String> {
-        self.resize_tx
-            .send((rows, cols))
-            .await
-            .map_err(|e| format!("Failed to send resize to PTY: {}", e))
+        self.process.resize(rows, cols).await
+    }
+
+    /// Buffered output and current size, for a terminal (re)attaching mid-session
+    pub fn attach(&self) -> PtyAttachSnapshot {
+        self.process.attach()
+    }
+
+    /// Send Ctrl-C to UCM (interrupts the running command, like in a real terminal).
+    /// Bypasses the input queue, so it can also interrupt a running `exec`.
+    pub async fn interrupt(&self) -> Result<(), String> {
+        if let Ok(mut input_line) = self.input_line.try_lock() {
+            input_line.clear();
+        }
+        self.send(&[0x03]).await
+    }
+
+    /// OS process id of UCM, if known
+    pub fn process_id(&self) -> Option<u32> {
+        self.process.process_id()
     }
 
     /// Stop the PTY manager
     pub fn stop(&self) {
-        *self.running.lock() = false;
+        self.process.stop();
     }
 
     /// Check if the PTY is still running
     pub fn is_running(&self) -> bool {
-        *self.running.lock()
+        self.process.is_running()
     }
-}
 
-impl Drop for UCMPtyManager {
-    fn drop(&mut self) {
-        self.stop();
+    /// Terminate UCM and wait for it to exit, releasing its codebase lock.
+    ///
+    /// Sends `exit` first so UCM can shut down cleanly, then escalates to SIGTERM
+    /// and finally SIGKILL if it does not exit in time.
+    pub async fn shutdown(&self) -> Result<(), String> {
+        if self.process.has_exited() {
+            self.stop();
+            return Ok(());
+        }
+
+        log::info!("Stopping UCM (pid {:?}) with `exit`", self.process_id());
+        self.process.set_terminated_by("exit");
+        if self.write(b"exit\n").await.is_ok() && self.process.wait_for_exit(GRACEFUL_EXIT_TIMEOUT).await {
+            self.stop();
+            return Ok(());
+        }
+
+        self.process.terminate().await
     }
 }
 
-/// Parse UCM prompt to extract project and branch
-fn parse_ucm_prompt(output: &str) -> Option<UCMContext> {
-    let lines: Vec<&str> = output.lines().collect();
+/// Wait for UCM's LSP server to answer on `lsp_port`. Accepting a connection isn't
+/// enough, as another process may hold the port: the server must answer an LSP
+/// `initialize` request (see `probe_lsp`).
+/// Emits `ucm-lsp-ready` with the port on success, or `ucm-lsp-port-error` on timeout.
+async fn verify_lsp_listening(
+    app_handle: AppHandle,
+    session_id: String,
+    lsp_port: u16,
+    running: Arc<Mutex<bool>>,
+) {
+    let started = std::time::Instant::now();
+    let mut last_error = "nothing is listening".to_string();
 
-    for line in lines.iter().rev() {
-        let trimmed = line.trim();
+    while started.elapsed() < LSP_STARTUP_TIMEOUT {
+        if !*running.lock() {
+            return;
+        }
 
-        if trimmed.is_empty() {
-            continue;
+        match probe_lsp(lsp_port).await {
+            Ok(()) => {
+                log::info!("UCM LSP server is answering on port {}", lsp_port);
+                emit_session_event(&app_handle, &session_id, "ucm-lsp-ready", LspReady { port: lsp_port });
+                return;
+            }
+            Err(e) => last_error = e,
         }
 
-        if let Some(prompt_end) = trimmed.rfind('>') {
-            let prompt_part = &trimmed[..prompt_end];
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    log::error!(
+        "UCM LSP server did not start on port {} within {:?}: {}",
+        lsp_port,
+        LSP_STARTUP_TIMEOUT,
+        last_error
+    );
+    emit_session_event(
+        &app_handle,
+        &session_id,
+        "ucm-lsp-port-error",
+        LspPortError {
+            port: lsp_port,
+            message: format!(
+                "UCM did not start its LSP server on port {} within {} seconds ({})",
+                lsp_port,
+                LSP_STARTUP_TIMEOUT.as_secs(),
+                last_error
+            ),
+        },
+    );
+}
+
+/// Send an LSP `initialize` request to `lsp_port` and wait for its response, which
+/// must carry the server's capabilities. The probe connection is then dropped; UCM
+/// serves every connection as a separate LSP session.
+async fn probe_lsp(lsp_port: u16) -> Result<(), String> {
+    let stream = tokio::net::TcpStream::connect(("127.0.0.1", lsp_port))
+        .await
+        .map_err(|_| "nothing is listening".to_string())?;
+    let (mut read, mut write) = stream.into_split();
+
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": { "processId": null, "rootUri": null, "capabilities": {} },
+    })
+    .to_string();
+    write
+        .write_all(format!("Content-Length: {}\r\n\r\n{}", request.len(), request).as_bytes())
+        .await
+        .map_err(|e| format!("failed to send initialize: {}", e))?;
 
-            let context_part = if let Some(colon_idx) = prompt_part.find(':') {
-                &prompt_part[..colon_idx]
-            } else {
-                prompt_part
+    let response = async {
+        loop {
+            // Notifications (log messages) may come first
+            let message = LspProxy::read_lsp_message(&mut read).await.map_err(|e| e.to_string())?;
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(&message) else {
+                continue;
             };
+            if message.get("id") == Some(&serde_json::Value::from(1)) && message.get("method").is_none() {
+                return Ok::<_, String>(message);
+            }
+        }
+    };
+    let response = tokio::time::timeout(LSP_PROBE_TIMEOUT, response)
+        .await
+        .map_err(|_| "the port is taken by a process that doesn't speak LSP".to_string())?
+        .map_err(|e| format!("the port is taken by a process that doesn't speak LSP ({})", e))?;
+
+    if response["result"]["capabilities"].is_object() {
+        Ok(())
+    } else {
+        Err(format!("unexpected initialize response: {}", response))
+    }
+}
+
+/// Status of an `exec` from its output: the first line holding nothing but a glyph
+/// opens UCM's message block (`  ⚠️` followed by the explanation, `  ✅` ...).
+/// Text is not matched, as wording like "not found" also shows up in normal output.
+fn exec_status(output: &str) -> UCMExecStatus {
+    let glyph = output
+        .lines()
+        .map(|line| line.trim().trim_end_matches('\u{fe0f}'))
+        .find(|line| UCM_FAILURE_GLYPHS.contains(line) || UCM_SUCCESS_GLYPHS.contains(line));
+    match glyph {
+        Some(glyph) if UCM_FAILURE_GLYPHS.contains(&glyph) => UCMExecStatus::Failed,
+        Some(_) => UCMExecStatus::Succeeded,
+        None => UCMExecStatus::Unknown,
+    }
+}
+
+/// Keep track of the line the user is typing from the raw input they send
+fn track_input_line(line: &mut String, data: &[u8]) {
+    // Escape sequences (arrow keys, history) move the cursor in ways we can't follow
+    if data.first() == Some(&0x1b) {
+        return;
+    }
+    for ch in String::from_utf8_lossy(data).chars() {
+        match ch {
+            '\r' | '\n' | '\x03' | '\x15' => line.clear(),
+            '\x7f' | '\x08' => {
+                line.pop();
+            }
+            c if c.is_control() => {}
+            c => line.push(c),
+        }
+    }
+}
 
-            if let Some(slash_idx) = context_part.rfind('/') {
-                let project = context_part[..slash_idx].trim();
-                let branch = context_part[slash_idx + 1..].trim();
-
-                if !project.is_empty()
-                    && !branch.is_empty()
-                    && !project.contains(' ')
-                    && !branch.contains(' ')
-                {
-                    return Some(UCMContext {
-                        project: Some(project.to_string()),
-                        branch: Some(branch.to_string()),
-                    });
+/// Remove ANSI escape sequences (CSI, OSC and two-byte escapes) from terminal output
+pub fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters, then a final byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
                 }
             }
+            // OSC: terminated by BEL or ESC '\'
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
-    None
+    result
+}
+
+/// Apply carriage returns the way a terminal would: the last segment of a line wins
+fn resolve_carriage_returns(line: &str) -> &str {
+    line.rsplit('\r')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or("")
+}
+
+/// Whether ANSI-stripped output ends at a fresh UCM prompt (after at least one line break)
+fn ends_with_prompt(text: &str) -> bool {
+    let Some(newline) = text.rfind('\n') else {
+        return false;
+    };
+    let last_line = resolve_carriage_returns(&text[newline + 1..]).trim_end();
+    last_line.ends_with('>') && parse_ucm_prompt(last_line).is_some()
+}
+
+/// Turn the raw output of an `exec` into clean text plus the context of the closing prompt.
+/// Drops the echoed command line and the closing prompt line.
+fn extract_exec_output(raw: &[u8]) -> (String, Option<UCMContext>) {
+    let text = strip_ansi(&String::from_utf8_lossy(raw));
+    let mut lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| resolve_carriage_returns(line).trim_end())
+        .collect();
+
+    let context = lines.pop().and_then(parse_ucm_prompt);
+    if !lines.is_empty() {
+        lines.remove(0);
+    }
+
+    while lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    (lines.join("\n"), context)
+}
+
+/// Parse UCM prompt to extract project, branch and namespace path
+fn parse_ucm_prompt(output: &str) -> Option<UCMContext> {
+    output
+        .lines()
+        .rev()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .find_map(|line| parse_prompt_line(line).map(|(context, _)| context))
+}
+
+/// Parse a line starting with a UCM prompt (`project/branch>` or `project/branch:path>`).
+/// Returns the context and whatever follows the prompt (input being typed).
+fn parse_prompt_line(line: &str) -> Option<(UCMContext, &str)> {
+    let trimmed = line.trim_start();
+    let prompt_end = trimmed.find('>')?;
+    let prompt_part = &trimmed[..prompt_end];
+
+    let (context_part, path) = match prompt_part.find(':') {
+        Some(colon_idx) => (&prompt_part[..colon_idx], prompt_part[colon_idx + 1..].trim()),
+        None => (prompt_part, ""),
+    };
+
+    let slash_idx = context_part.rfind('/')?;
+    let project = context_part[..slash_idx].trim();
+    let branch = context_part[slash_idx + 1..].trim();
+
+    if project.is_empty()
+        || branch.is_empty()
+        || project.contains(' ')
+        || branch.contains(' ')
+        || path.contains(' ')
+    {
+        return None;
+    }
+
+    let context = UCMContext {
+        project: Some(project.to_string()),
+        branch: Some(branch.to_string()),
+        path: (!path.is_empty()).then(|| path.trim_start_matches('.').to_string()),
+    };
+    Some((context, &trimmed[prompt_end + 1..]))
+}
+
+/// Work out the prompt state from ANSI-stripped output (only its last lines matter)
+fn detect_prompt_state(text: &str) -> UCMPromptState {
+    let mut lines = text.split('\n').map(resolve_carriage_returns);
+    let last_line = lines.next_back().unwrap_or("");
+
+    if let Some((context, _input)) = parse_prompt_line(last_line) {
+        return UCMPromptState::Idle { context };
+    }
+
+    let question = last_line.trim();
+    if question.is_empty() {
+        return UCMPromptState::Busy;
+    }
+
+    let lower = question.to_lowercase();
+    if ["(y/n)", "[y/n]", "(yes/no)", "[yes/no]"]
+        .iter()
+        .any(|marker| lower.contains(marker))
+    {
+        return UCMPromptState::Confirmation {
+            question: question.to_string(),
+        };
+    }
+
+    if question.ends_with(['?', ':', '>']) {
+        // Numbered options directly above the question, e.g. "  1. lib.foo"
+        let mut options: Vec<PromptChoice> = lines
+            .rev()
+            .map(str::trim)
+            .skip_while(|line| line.is_empty())
+            .map_while(parse_choice_line)
+            .collect();
+        if !options.is_empty() {
+            options.reverse();
+            return UCMPromptState::Choice {
+                question: question.to_string(),
+                options,
+            };
+        }
+    }
+
+    UCMPromptState::Busy
+}
+
+/// Parse a numbered list entry like `1. foo` or `2) bar`
+fn parse_choice_line(line: &str) -> Option<PromptChoice> {
+    let digits = line.find(|c: char| !c.is_ascii_digit())?;
+    let number = line[..digits].parse().ok()?;
+    let label = line[digits..].strip_prefix(['.', ')'])?.trim();
+    if label.is_empty() {
+        return None;
+    }
+    Some(PromptChoice {
+        number,
+        label: label.to_string(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::io::AsyncReadExt;
+
+    /// Serve one connection on an ephemeral port, answering with `reply` once the
+    /// client has sent something
+    async fn serve_once(reply: String) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).await;
+            let _ = stream.write_all(reply.as_bytes()).await;
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_idle() {
+        let state = Arc::new(Mutex::new(UCMPromptState::Busy));
+        let changed = Arc::new(Notify::new());
+        let soon = || tokio::time::Instant::now() + Duration::from_millis(50);
+
+        // Busy for longer than the timeout
+        let busy = wait_until_idle(&state, &changed, soon()).await;
+        assert!(busy.unwrap_err().contains("busy"));
+
+        // Busy until the running command returns to the prompt
+        let (waiter_state, waiter_changed) = (state.clone(), changed.clone());
+        let waiter = tokio::spawn(async move {
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+            wait_until_idle(&waiter_state, &waiter_changed, deadline).await
+        });
+        tokio::task::yield_now().await;
+        *state.lock() = UCMPromptState::Idle { context: UCMContext::default() };
+        changed.notify_waiters();
+        assert_eq!(waiter.await.unwrap(), Ok(()));
+
+        // A sub-prompt fails at once instead of being answered
+        *state.lock() = UCMPromptState::Confirmation { question: "Really delete tour? (y/n)".to_string() };
+        let answered = wait_until_idle(&state, &changed, soon()).await;
+        assert!(answered.unwrap_err().contains("Really delete tour?"));
+        *state.lock() = UCMPromptState::Choice { question: "Pick a number:".to_string(), options: Vec::new() };
+        assert!(wait_until_idle(&state, &changed, soon()).await.is_err());
+    }
+
+    #[test]
+    fn test_exec_status_reads_message_blocks() {
+        let failed = "\n  ⚠️\n\n  I couldn't find a term named foo.\n";
+        assert_eq!(exec_status(failed), UCMExecStatus::Failed);
+        assert_eq!(exec_status("  ❓\n\n  I couldn't resolve bar\n"), UCMExecStatus::Failed);
+        assert_eq!(exec_status("  ✅\n\n  Done.\n"), UCMExecStatus::Succeeded);
+        // Ordinary output mentioning errors is not a failure
+        let listing = "  1. notFound : Text\n  2. Sorry.message : Text\n";
+        assert_eq!(exec_status(listing), UCMExecStatus::Unknown);
+        assert_eq!(exec_status("  Warning ⚠️ inline\n"), UCMExecStatus::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_probe_lsp_requires_initialize_response() {
+        let log = r#"{"jsonrpc":"2.0","method":"window/logMessage","params":{}}"#;
+        let result = r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":{}}}"#;
+        let reply = format!(
+            "Content-Length: {}\r\n\r\n{}Content-Length: {}\r\n\r\n{}",
+            log.len(),
+            log,
+            result.len(),
+            result
+        );
+        let lsp = serve_once(reply).await;
+        assert_eq!(probe_lsp(lsp).await, Ok(()));
+
+        // Another server holding the port
+        let http = serve_once("HTTP/1.1 400 Bad Request\r\n\r\n".to_string()).await;
+        assert!(probe_lsp(http).await.unwrap_err().contains("doesn't speak LSP"));
+    }
 
     #[test]
     fn test_parse_ucm_prompt_basic() {
@@ -415,6 +1073,116 @@ mod tests {
         assert_eq!(ctx.branch, Some("main".to_string()));
     }
 
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1m\x1b[32mDone.\x1b[0m"), "Done.");
+        assert_eq!(strip_ansi("\x1b]0;ucm\x07tour/main> "), "tour/main> ");
+        assert_eq!(strip_ansi("a\x1b]8;;x\x1b\\b"), "ab");
+        assert_eq!(strip_ansi("plain ⧩ text"), "plain ⧩ text");
+    }
+
+    #[test]
+    fn test_ends_with_prompt() {
+        assert!(!ends_with_prompt("tour/main> "));
+        assert!(!ends_with_prompt("move.term a b\r\n  Working..."));
+        assert!(ends_with_prompt("move.term a b\r\n\r\n  Done.\r\n\r\ntour/main> "));
+    }
+
+    #[test]
+    fn test_extract_exec_output() {
+        let raw = b"move.term foo bar\r\n\r\n  \x1b[32mDone.\x1b[0m\r\n\r\ntour/main> ";
+        let (output, context) = extract_exec_output(raw);
+        assert_eq!(output, "  Done.");
+        assert_eq!(context.unwrap().branch, Some("main".to_string()));
+    }
+
+    #[test]
+    fn test_track_input_line() {
+        let mut line = String::new();
+        track_input_line(&mut line, b"vie");
+        track_input_line(&mut line, b"x\x7f");
+        assert_eq!(line, "vie");
+        track_input_line(&mut line, b"\x1b[A");
+        assert_eq!(line, "vie");
+        track_input_line(&mut line, b"w foo\r");
+        assert_eq!(line, "");
+    }
+
+    #[test]
+    fn test_parse_ucm_prompt_path() {
+        let ctx = parse_ucm_prompt("myproject/feature:lib.utils> ").unwrap();
+        assert_eq!(ctx.path, Some("lib.utils".to_string()));
+        let ctx = parse_ucm_prompt("myproject/feature> ").unwrap();
+        assert_eq!(ctx.path, None);
+    }
+
+    #[test]
+    fn test_detect_prompt_state_idle_and_busy() {
+        let idle = detect_prompt_state("Done.\n\ntour/main:lib> vie");
+        match idle {
+            UCMPromptState::Idle { context } => assert_eq!(context.path, Some("lib".to_string())),
+            other => panic!("expected idle, got {:?}", other),
+        }
+        assert_eq!(detect_prompt_state("tour/main> update\n"), UCMPromptState::Busy);
+        assert_eq!(detect_prompt_state("  Typechecking..."), UCMPromptState::Busy);
+    }
+
+    #[test]
+    fn test_detect_prompt_state_sub_prompts() {
+        assert_eq!(
+            detect_prompt_state("tour/main> delete.project tour\nReally delete tour? (y/n) "),
+            UCMPromptState::Confirmation {
+                question: "Really delete tour? (y/n)".to_string()
+            }
+        );
+
+        let state = detect_prompt_state("Which one?\n\n  1. lib.foo\n  2. lib.bar\n\nPick a number:");
+        assert_eq!(
+            state,
+            UCMPromptState::Choice {
+                question: "Pick a number:".to_string(),
+                options: vec![
+                    PromptChoice { number: 1, label: "lib.foo".to_string() },
+                    PromptChoice { number: 2, label: "lib.bar".to_string() },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_prompt_state_event_payload() {
+        use crate::ucm_session::SessionEvent;
+
+        let event = SessionEvent {
+            session_id: "ws-1".to_string(),
+            payload: UCMPromptState::Busy,
+        };
+        assert_eq!(
+            serde_json::to_value(event).unwrap(),
+            serde_json::json!({ "sessionId": "ws-1", "state": "busy" })
+        );
+
+        let event = SessionEvent {
+            session_id: "ws-1".to_string(),
+            payload: UCMPromptState::Confirmation {
+                question: "Sure? (y/n)".to_string(),
+            },
+        };
+        let json = serde_json::to_value(event).unwrap();
+        assert_eq!(json["state"], "confirmation");
+        assert_eq!(json["question"], "Sure? (y/n)");
+    }
+
+    #[test]
+    fn test_utf8_decoder_carries_split_sequences() {
+        let bytes = "⧩ ok".as_bytes();
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(&bytes[..1]), "");
+        assert_eq!(decoder.decode(&bytes[1..2]), "");
+        assert_eq!(decoder.decode(&bytes[2..]), "⧩ ok");
+        assert_eq!(decoder.decode(b"a\xffb"), "a\u{fffd}b");
+    }
+
     #[test]
     fn test_parse_ucm_prompt_no_match() {
         let output = "Just some text without a prompt";
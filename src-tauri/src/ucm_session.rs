@@ -0,0 +1,451 @@
+//! UCM Sessions - Registry of concurrently running UCM instances
+//!
+//! This module provides:
+//! - `UCMSession`: everything that belongs to one UCM instance (PTY, API client,
+//!   MCP client, ports, LSP proxy, inspector and problems), so workspaces backed by
+//!   different codebases can be open side by side
+//! - `SessionRegistry`: sessions keyed by a frontend-chosen session id
+//! - `SessionEvent`: event payload wrapper that tags events with their session id
+//! - `UCMCodebase`: the codebase a session's PTY and MCP processes open
+//! - `SessionMode`: whether a session owns its UCM, borrows another UCM's API
+//!   read-only, or runs without UCM because the codebase is locked
+
+use crate::lsp_inspector::LspInspector;
+use crate::mcp_client::MCPClient;
+use crate::problems::ProblemsStore;
+use crate::ucm_api::UCMApiClient;
+use crate::ucm_pty::UCMPtyManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex as TokioMutex, MutexGuard as TokioMutexGuard};
+
+/// Session used by commands that don't specify one (single-workspace frontends)
+pub const DEFAULT_SESSION_ID: &str = "default";
+
+/// Resolve the optional `sessionId` command argument
+pub fn resolve_session_id(session_id: Option<String>) -> String {
+    session_id
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| DEFAULT_SESSION_ID.to_string())
+}
+
+/// Event payload tagged with the session it belongs to.
+/// The payload's fields are flattened next to `sessionId`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEvent<T: Serialize> {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(flatten)]
+    pub payload: T,
+}
+
+/// Emit `event` with `payload` tagged with `session_id`
+pub fn emit_session_event<T: Serialize + Clone>(
+    app_handle: &AppHandle,
+    session_id: &str,
+    event: &str,
+    payload: T,
+) {
+    let tagged = SessionEvent {
+        session_id: session_id.to_string(),
+        payload,
+    };
+    if let Err(e) = app_handle.emit(event, tagged) {
+        log::error!("Failed to emit {} for session {}: {}", event, session_id, e);
+    }
+}
+
+/// Ports allocated to a session's UCM and LSP proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionPorts {
+    #[serde(rename = "apiPort")]
+    pub api_port: u16,
+    #[serde(rename = "lspPort")]
+    pub lsp_port: u16,
+    #[serde(rename = "lspProxyPort")]
+    pub lsp_proxy_port: u16,
+}
+
+/// Codebase a session opens instead of UCM's default `~/.unison`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UCMCodebase {
+    /// Codebase directory, relative paths are resolved against the workspace
+    pub path: String,
+    /// Create the codebase if it doesn't exist (`--codebase-create`)
+    #[serde(default)]
+    pub create: bool,
+}
+
+impl UCMCodebase {
+    /// Make the path absolute (relative to `base`) and check that it exists unless
+    /// it may be created
+    pub fn resolve(&self, base: Option<&str>) -> Result<UCMCodebase, String> {
+        let path = self.path.trim();
+        if path.is_empty() {
+            return Err("Codebase path is empty".to_string());
+        }
+
+        let mut resolved = Path::new(path).to_path_buf();
+        if let Some(rest) = path.strip_prefix("~/") {
+            if let Some(home) = dirs::home_dir() {
+                resolved = home.join(rest);
+            }
+        } else if resolved.is_relative() {
+            if let Some(base) = base {
+                resolved = Path::new(base).join(resolved);
+            }
+        }
+
+        if resolved.exists() && !resolved.is_dir() {
+            return Err(format!("Codebase path is not a directory: {}", resolved.display()));
+        }
+        if !self.create && !resolved.is_dir() {
+            return Err(format!(
+                "Codebase not found: {} (enable codebase creation to create it)",
+                resolved.display()
+            ));
+        }
+
+        Ok(UCMCodebase {
+            path: resolved.to_string_lossy().to_string(),
+            create: self.create,
+        })
+    }
+
+    /// UCM command-line arguments selecting this codebase (before any subcommand)
+    pub fn args(&self) -> [String; 2] {
+        let flag = if self.create { "--codebase-create" } else { "--codebase" };
+        [flag.to_string(), self.path.clone()]
+    }
+}
+
+/// How a session reaches its codebase
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SessionMode {
+    /// The session runs its own UCM
+    #[default]
+    Full,
+    /// Connected to the HTTP API of the UCM holding the codebase lock; nothing is
+    /// written to the codebase
+    ReadOnly {
+        #[serde(rename = "holderPid")]
+        holder_pid: u32,
+        #[serde(rename = "apiPort")]
+        api_port: u16,
+    },
+    /// No UCM: files can be edited, codebase features are unavailable
+    Degraded,
+}
+
+/// Summary of a session for `list_ucm_sessions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UCMSessionInfo {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    /// Whether the session's UCM PTY is running
+    pub running: bool,
+    /// Allocated ports, once UCM has been spawned
+    pub ports: Option<SessionPorts>,
+    /// Codebase opened by the session (None = UCM's default codebase)
+    pub codebase: Option<UCMCodebase>,
+    pub mode: SessionMode,
+}
+
+/// One UCM instance and the services attached to it
+pub struct UCMSession {
+    pub id: String,
+    pub ucm_client: Mutex<Option<UCMApiClient>>,
+    pub mcp_client: Mutex<Option<MCPClient>>,
+    /// UCM PTY manager - uses tokio Mutex for async access. Shared so long-running
+    /// calls (`exec`) don't hold the lock and block kill/resize.
+    pub ucm_pty: TokioMutex<Option<Arc<UCMPtyManager>>>,
+    /// Ports allocated when the PTY was last spawned
+    pub ports: Mutex<Option<SessionPorts>>,
+    /// Codebase the PTY was spawned with; the MCP client opens the same one
+    pub codebase: Mutex<Option<UCMCodebase>>,
+    pub mode: Mutex<SessionMode>,
+    /// LSP WebSocket proxy task, aborted when UCM is respawned or the session closes
+    pub lsp_proxy_task: Mutex<Option<JoinHandle<()>>>,
+    /// Opt-in capture of this session's LSP proxy traffic
+    pub lsp_inspector: Arc<LspInspector>,
+    /// Workspace-wide diagnostics collected from this session's LSP
+    pub problems: Arc<ProblemsStore>,
+}
+
+impl UCMSession {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            // UCM client will be initialized when UCM is spawned with the actual port
+            ucm_client: Mutex::new(None),
+            mcp_client: Mutex::new(None),
+            ucm_pty: TokioMutex::new(None),
+            ports: Mutex::new(None),
+            codebase: Mutex::new(None),
+            mode: Mutex::new(SessionMode::Full),
+            lsp_proxy_task: Mutex::new(None),
+            lsp_inspector: Arc::new(LspInspector::new()),
+            problems: Arc::new(ProblemsStore::new()),
+        }
+    }
+
+    /// Clone of the session's UCM API client
+    pub fn api_client(&self) -> Result<UCMApiClient, String> {
+        let client_guard = self.ucm_client.lock().unwrap();
+        client_guard
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| "UCM client not initialized".to_string())
+    }
+
+    pub fn ports(&self) -> Option<SessionPorts> {
+        *self.ports.lock().unwrap()
+    }
+
+    pub fn codebase(&self) -> Option<UCMCodebase> {
+        self.codebase.lock().unwrap().clone()
+    }
+
+    pub fn mode(&self) -> SessionMode {
+        *self.mode.lock().unwrap()
+    }
+
+    /// Fail unless the session may write to its codebase or open it with another UCM
+    /// (the MCP client), which the lock held by a foreign UCM rules out
+    pub fn ensure_writable(&self) -> Result<(), String> {
+        match self.mode() {
+            SessionMode::Full => Ok(()),
+            SessionMode::ReadOnly { holder_pid, .. } => Err(format!(
+                "The codebase is locked by another UCM (pid {}); this session is read-only",
+                holder_pid
+            )),
+            SessionMode::Degraded => {
+                Err("The codebase is locked by another UCM; UCM features are unavailable".to_string())
+            }
+        }
+    }
+
+    /// Abort the session's LSP WebSocket proxy, freeing its port
+    pub fn stop_lsp_proxy(&self) {
+        if let Some(task) = self.lsp_proxy_task.lock().unwrap().take() {
+            log::info!("Stopping LSP proxy for session {}", self.id);
+            task.abort();
+        }
+    }
+
+    /// Stop UCM, the LSP proxy and the MCP client of this session
+    pub async fn shutdown(&self) -> Result<(), String> {
+        self.stop_lsp_proxy();
+        self.mcp_client.lock().unwrap().take();
+
+        let mut pty_guard = self.ucm_pty.lock().await;
+        if let Some(manager) = pty_guard.take() {
+            manager.shutdown().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn info(&self) -> UCMSessionInfo {
+        let running = self
+            .ucm_pty
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|manager| manager.is_running());
+        UCMSessionInfo {
+            session_id: self.id.clone(),
+            running,
+            ports: self.ports(),
+            codebase: self.codebase(),
+            mode: self.mode(),
+        }
+    }
+}
+
+/// Session Registry - all UCM sessions keyed by session id
+pub struct SessionRegistry {
+    sessions: Mutex<HashMap<String, Arc<UCMSession>>>,
+    /// Serializes port allocation across sessions, so two spawns can't pick the same ports
+    spawn_lock: TokioMutex<()>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            spawn_lock: TokioMutex::new(()),
+        }
+    }
+
+    /// Get an existing session
+    pub fn get(&self, session_id: &str) -> Result<Arc<UCMSession>, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown UCM session: {}", session_id))
+    }
+
+    /// Get a session, creating an empty one if it doesn't exist yet. Only spawning UCM
+    /// creates sessions; other commands use `get`, so a mistyped id is an error.
+    pub fn get_or_create(&self, session_id: &str) -> Arc<UCMSession> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .entry(session_id.to_string())
+            .or_insert_with(|| {
+                log::info!("Creating UCM session {}", session_id);
+                Arc::new(UCMSession::new(session_id))
+            })
+            .clone()
+    }
+
+    /// Remove a session from the registry (the caller shuts it down)
+    pub fn remove(&self, session_id: &str) -> Option<Arc<UCMSession>> {
+        self.sessions.lock().unwrap().remove(session_id)
+    }
+
+    /// All sessions, sorted by id
+    pub fn sessions(&self) -> Vec<Arc<UCMSession>> {
+        let mut sessions: Vec<Arc<UCMSession>> =
+            self.sessions.lock().unwrap().values().cloned().collect();
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        sessions
+    }
+
+    /// Ports allocated to sessions other than `session_id`, which a new spawn must avoid
+    pub fn reserved_ports(&self, session_id: &str) -> Vec<u16> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| session.id != session_id)
+            .filter_map(|session| session.ports())
+            .flat_map(|ports| [ports.api_port, ports.lsp_port, ports.lsp_proxy_port])
+            .collect()
+    }
+
+    /// Hold while allocating ports for a session and recording them
+    pub async fn lock_spawn(&self) -> TokioMutexGuard<'_, ()> {
+        self.spawn_lock.lock().await
+    }
+}
+
+impl Default for SessionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(base: u16) -> SessionPorts {
+        SessionPorts {
+            api_port: base,
+            lsp_port: base + 1,
+            lsp_proxy_port: base + 2,
+        }
+    }
+
+    #[test]
+    fn test_resolve_session_id() {
+        assert_eq!(resolve_session_id(None), DEFAULT_SESSION_ID);
+        assert_eq!(resolve_session_id(Some(String::new())), DEFAULT_SESSION_ID);
+        assert_eq!(resolve_session_id(Some("ws-1".to_string())), "ws-1");
+    }
+
+    #[test]
+    fn test_get_or_create_reuses_sessions() {
+        let registry = SessionRegistry::new();
+        assert!(registry.get("a").is_err());
+
+        let first = registry.get_or_create("a");
+        let second = registry.get_or_create("a");
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&first, &registry.get("a").unwrap()));
+
+        registry.get_or_create("b");
+        let ids: Vec<String> = registry.sessions().iter().map(|s| s.id.clone()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        assert!(registry.remove("a").is_some());
+        assert!(registry.get("a").is_err());
+    }
+
+    #[test]
+    fn test_reserved_ports_excludes_own_session() {
+        let registry = SessionRegistry::new();
+        *registry.get_or_create("a").ports.lock().unwrap() = Some(ports(6000));
+        *registry.get_or_create("b").ports.lock().unwrap() = Some(ports(7000));
+        registry.get_or_create("c");
+
+        let mut reserved = registry.reserved_ports("a");
+        reserved.sort();
+        assert_eq!(reserved, vec![7000, 7001, 7002]);
+        assert_eq!(registry.reserved_ports("c").len(), 6);
+    }
+
+    #[test]
+    fn test_codebase_resolve_and_args() {
+        let base = std::env::temp_dir();
+        let base_str = base.to_string_lossy().to_string();
+
+        let missing = UCMCodebase {
+            path: "no-such-codebase-dir".to_string(),
+            create: false,
+        };
+        assert!(missing.resolve(Some(&base_str)).is_err());
+
+        let created = UCMCodebase {
+            create: true,
+            ..missing
+        }
+        .resolve(Some(&base_str))
+        .unwrap();
+        assert_eq!(Path::new(&created.path), base.join("no-such-codebase-dir"));
+        assert_eq!(created.args()[0], "--codebase-create");
+
+        let existing = UCMCodebase {
+            path: base_str.clone(),
+            create: false,
+        };
+        assert_eq!(existing.resolve(None).unwrap().args(), ["--codebase".to_string(), base_str]);
+    }
+
+    #[test]
+    fn test_session_event_flattens_payload() {
+        let event = SessionEvent {
+            session_id: "ws-1".to_string(),
+            payload: ports(6000),
+        };
+        let json = serde_json::to_value(event).unwrap();
+        assert_eq!(json["sessionId"], "ws-1");
+        assert_eq!(json["apiPort"], 6000);
+        assert_eq!(json["lspProxyPort"], 6002);
+    }
+
+    #[test]
+    fn test_session_mode() {
+        let session = UCMSession::new("a");
+        assert!(session.ensure_writable().is_ok());
+
+        let read_only = SessionMode::ReadOnly {
+            holder_pid: 4242,
+            api_port: 5858,
+        };
+        *session.mode.lock().unwrap() = read_only;
+        assert!(session.ensure_writable().unwrap_err().contains("4242"));
+
+        let json = serde_json::to_value(read_only).unwrap();
+        assert_eq!(json["kind"], "readOnly");
+        assert_eq!(json["apiPort"], 5858);
+    }
+}
@@ -0,0 +1,671 @@
+//! Workspace Search - Find and replace across the files of a workspace
+//!
+//! This module provides:
+//! - `SearchQuery`: literal or regex text, case sensitivity, whole-word matching and
+//!   include/exclude globs relative to the workspace root
+//! - `search_workspace`: walks the workspace, skipping hidden and ignored entries
+//!   (see `ignore_rules`, including `.gitignore`/`.ignore` files), and reports matches file by file with 1-based line and column numbers, checking a
+//!   cancellation flag between files
+//! - `preview_replace` / `apply_replace`: replacements are computed for every file first,
+//!   then written through temp files and renames, restoring the originals if any write
+//!   fails. Files that changed since the preview are refused.
+//! - `SearchRegistry`: cancellation flags of running searches, keyed by search id
+//!
+//! Matching is line by line, so a pattern can't span lines. Without include globs only
+//! Unison (`.u`) files are searched.
+
+use crate::file_io::{content_version, sync_parent_dir, temp_path, write_atomic};
+use crate::ignore_rules::IgnoreRules;
+use glob::{MatchOptions, Pattern};
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Files searched when no include globs are given
+const DEFAULT_INCLUDE: &str = "**/*.u";
+
+/// Larger files are skipped (generated or binary data, not source)
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Search stops reporting matches after this many, unless the query sets a limit
+const DEFAULT_MAX_MATCHES: usize = 10_000;
+
+/// What to search for, and where
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub query: String,
+    /// Treat `query` as a regular expression
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default, rename = "caseSensitive")]
+    pub case_sensitive: bool,
+    #[serde(default, rename = "wholeWord")]
+    pub whole_word: bool,
+    /// Globs of files to search (default: `**/*.u`)
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of files and directories to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default, rename = "maxMatches")]
+    pub max_matches: Option<usize>,
+}
+
+impl SearchQuery {
+    fn matcher(&self) -> Result<Regex, String> {
+        if self.query.is_empty() {
+            return Err("Search query is empty".to_string());
+        }
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        let pattern = if self.whole_word {
+            format!(r"\b(?:{})\b", pattern)
+        } else {
+            pattern
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|e| format!("Invalid search pattern: {}", e))
+    }
+}
+
+/// Include/exclude globs, matched against paths relative to the workspace root
+pub struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+impl PathFilter {
+    fn new(query: &SearchQuery) -> Result<Self, String> {
+        let compile = |globs: &[String]| -> Result<Vec<Pattern>, String> {
+            globs
+                .iter()
+                .map(|glob| glob.trim())
+                .filter(|glob| !glob.is_empty())
+                .map(|glob| Pattern::new(glob).map_err(|e| format!("Invalid glob '{}': {}", glob, e)))
+                .collect()
+        };
+        let mut include = compile(&query.include)?;
+        if include.is_empty() {
+            include.push(Self::default_include());
+        }
+        Ok(Self {
+            include,
+            exclude: compile(&query.exclude)?,
+        })
+    }
+
+    fn default_include() -> Pattern {
+        Pattern::new(DEFAULT_INCLUDE).expect("default include glob is valid")
+    }
+
+    fn excludes(&self, relative: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches_path_with(relative, GLOB_OPTIONS))
+    }
+
+    fn includes(&self, relative: &Path) -> bool {
+        self.include
+            .iter()
+            .any(|pattern| pattern.matches_path_with(relative, GLOB_OPTIONS))
+    }
+}
+
+/// The Unison (`.u`) files, without excludes
+impl Default for PathFilter {
+    fn default() -> Self {
+        Self {
+            include: vec![Self::default_include()],
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Visit the files under `root` accepted by `filter`, in a stable (sorted) order.
+/// Hidden and ignored entries are skipped, with the ignore files of each directory
+/// applying below it.
+pub fn walk_files(
+    root: &Path,
+    filter: &PathFilter,
+    visit: &mut dyn FnMut(&Path) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut pending = vec![(root.to_path_buf(), IgnoreRules::load(root))];
+    while let Some((dir, rules)) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            // Skip symlinks, which could loop
+            if file_type.is_symlink() {
+                continue;
+            }
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if rules.is_ignored(relative, file_type.is_dir()) || filter.excludes(relative) {
+                continue;
+            }
+            if file_type.is_dir() {
+                let mut subdir_rules = rules.clone();
+                subdir_rules.add_directory(root, &path);
+                subdirs.push((path, subdir_rules));
+            } else if filter.includes(relative) {
+                visit(&path)?;
+            }
+        }
+        // Reversed so the stack pops them in sorted order
+        pending.extend(subdirs.into_iter().rev());
+    }
+    ControlFlow::Continue(())
+}
+
+/// Read a searchable file; unreadable, binary (non-UTF-8) and huge files are skipped
+fn read_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    fs::read_to_string(path).ok()
+}
+
+/// 1-based column of byte offset `byte` in `line`, in UTF-16 code units like the editor
+fn column(line: &str, byte: usize) -> usize {
+    line[..byte].encode_utf16().count() + 1
+}
+
+/// One match within a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column of the first matched character
+    pub column: usize,
+    /// 1-based column just past the match
+    #[serde(rename = "endColumn")]
+    pub end_column: usize,
+    #[serde(rename = "lineText")]
+    pub line_text: String,
+}
+
+/// All matches within one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Result of a finished (or cancelled) search
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchSummary {
+    #[serde(rename = "filesSearched")]
+    pub files_searched: usize,
+    /// Files with at least one match
+    #[serde(rename = "fileCount")]
+    pub file_count: usize,
+    #[serde(rename = "matchCount")]
+    pub match_count: usize,
+    /// The match limit was reached; more matches may exist
+    pub truncated: bool,
+    pub cancelled: bool,
+}
+
+/// Event payload tagged with the search it belongs to
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchEvent<T: Serialize> {
+    #[serde(rename = "searchId")]
+    pub search_id: String,
+    #[serde(flatten)]
+    pub payload: T,
+}
+
+fn find_matches(matcher: &Regex, content: &str, limit: usize) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        for found in matcher.find_iter(line) {
+            if found.is_empty() {
+                continue;
+            }
+            if matches.len() == limit {
+                return matches;
+            }
+            matches.push(SearchMatch {
+                line: index + 1,
+                column: column(line, found.start()),
+                end_column: column(line, found.end()),
+                line_text: line.to_string(),
+            });
+        }
+    }
+    matches
+}
+
+/// Search the files under `root`, calling `on_file` for every file with matches.
+/// Stops early when `cancel` is set or the match limit is reached.
+pub fn search_workspace(
+    root: &Path,
+    query: &SearchQuery,
+    cancel: &AtomicBool,
+    mut on_file: impl FnMut(FileMatches),
+) -> Result<SearchSummary, String> {
+    let matcher = query.matcher()?;
+    let filter = PathFilter::new(query)?;
+    let max_matches = query.max_matches.unwrap_or(DEFAULT_MAX_MATCHES);
+    let mut summary = SearchSummary::default();
+
+    let _ = walk_files(root, &filter, &mut |path| {
+        if cancel.load(Ordering::Relaxed) {
+            summary.cancelled = true;
+            return ControlFlow::Break(());
+        }
+        let Some(content) = read_text(path) else {
+            return ControlFlow::Continue(());
+        };
+        summary.files_searched += 1;
+
+        let remaining = max_matches - summary.match_count;
+        let matches = find_matches(&matcher, &content, remaining + 1);
+        if matches.is_empty() {
+            return ControlFlow::Continue(());
+        }
+        let truncated = matches.len() > remaining;
+        let matches: Vec<SearchMatch> = matches.into_iter().take(remaining).collect();
+
+        summary.file_count += 1;
+        summary.match_count += matches.len();
+        on_file(FileMatches {
+            path: path.to_string_lossy().to_string(),
+            matches,
+        });
+
+        if truncated || summary.match_count == max_matches {
+            summary.truncated = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+
+    Ok(summary)
+}
+
+/// A changed line in a replacement preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineChange {
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Replacements in one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileReplacement {
+    pub path: String,
+    pub replacements: usize,
+    pub changes: Vec<LineChange>,
+    /// Version of the file the preview was computed from
+    pub version: String,
+}
+
+/// Result of `preview_replace` and `apply_replace`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplaceResult {
+    pub files: Vec<FileReplacement>,
+    #[serde(rename = "totalReplacements")]
+    pub total_replacements: usize,
+    /// Whether the files were written (false for a preview)
+    pub applied: bool,
+}
+
+/// Replace every match in `content`, line by line like the search.
+/// Regex queries may refer to capture groups (`$1`, `${name}`) in `replacement`.
+fn replace_content(
+    matcher: &Regex,
+    content: &str,
+    replacement: &str,
+    expand: bool,
+) -> (String, usize, Vec<LineChange>) {
+    let mut updated = String::with_capacity(content.len());
+    let mut count = 0;
+    let mut changes = Vec::new();
+
+    for (index, raw_line) in content.split_inclusive('\n').enumerate() {
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+        let ending = &raw_line[line.len()..];
+        let found = matcher.find_iter(line).filter(|m| !m.is_empty()).count();
+        if found == 0 {
+            updated.push_str(raw_line);
+            continue;
+        }
+        let replaced = if expand {
+            matcher.replace_all(line, replacement)
+        } else {
+            matcher.replace_all(line, NoExpand(replacement))
+        };
+        count += found;
+        changes.push(LineChange {
+            line: index + 1,
+            before: line.to_string(),
+            after: replaced.to_string(),
+        });
+        updated.push_str(&replaced);
+        updated.push_str(ending);
+    }
+
+    (updated, count, changes)
+}
+
+struct PlannedReplacement {
+    path: PathBuf,
+    original: String,
+    updated: String,
+    summary: FileReplacement,
+}
+
+fn plan_replace(root: &Path, query: &SearchQuery, replacement: &str) -> Result<Vec<PlannedReplacement>, String> {
+    let matcher = query.matcher()?;
+    let filter = PathFilter::new(query)?;
+    let mut planned = Vec::new();
+
+    let _ = walk_files(root, &filter, &mut |path| {
+        if let Some(content) = read_text(path) {
+            let (updated, replacements, changes) = replace_content(&matcher, &content, replacement, query.regex);
+            if replacements > 0 && updated != content {
+                planned.push(PlannedReplacement {
+                    path: path.to_path_buf(),
+                    summary: FileReplacement {
+                        path: path.to_string_lossy().to_string(),
+                        replacements,
+                        changes,
+                        version: content_version(content.as_bytes()),
+                    },
+                    original: content,
+                    updated,
+                });
+            }
+        }
+        ControlFlow::Continue(())
+    });
+
+    Ok(planned)
+}
+
+fn result_of(planned: &[PlannedReplacement], applied: bool) -> ReplaceResult {
+    ReplaceResult {
+        files: planned.iter().map(|plan| plan.summary.clone()).collect(),
+        total_replacements: planned.iter().map(|plan| plan.summary.replacements).sum(),
+        applied,
+    }
+}
+
+/// Compute the replacements without writing anything
+pub fn preview_replace(root: &Path, query: &SearchQuery, replacement: &str) -> Result<ReplaceResult, String> {
+    Ok(result_of(&plan_replace(root, query, replacement)?, false))
+}
+
+/// Apply the replacements to the files in `versions` (path -> version from the
+/// preview), or to every matching file when `versions` is empty.
+///
+/// All new contents are written to temp files before any file is replaced; if a
+/// rename fails, the files already replaced are restored.
+pub fn apply_replace(
+    root: &Path,
+    query: &SearchQuery,
+    replacement: &str,
+    versions: &HashMap<String, String>,
+) -> Result<ReplaceResult, String> {
+    let mut planned = plan_replace(root, query, replacement)?;
+
+    if !versions.is_empty() {
+        planned.retain(|plan| versions.contains_key(&plan.summary.path));
+        for (path, version) in versions {
+            match planned.iter().find(|plan| &plan.summary.path == path) {
+                Some(plan) if &plan.summary.version == version => {}
+                _ => return Err(format!("File changed since the preview: {}", path)),
+            }
+        }
+    }
+
+    // Phase 1: write every new content next to its file, flushed to disk
+    let mut written: Vec<PathBuf> = Vec::new();
+    for plan in &planned {
+        let temp = temp_path(&plan.path);
+        let result = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .and_then(|mut file| {
+                written.push(temp.clone());
+                file.write_all(plan.updated.as_bytes())?;
+                file.sync_all()?;
+                let permissions = fs::metadata(&plan.path)?.permissions();
+                fs::set_permissions(&temp, permissions)
+            });
+        if let Err(e) = result {
+            for temp in &written {
+                let _ = fs::remove_file(temp);
+            }
+            return Err(format!("Failed to write '{}': {}", plan.path.display(), e));
+        }
+    }
+
+    // Phase 2: move them into place, undoing on failure
+    for (index, plan) in planned.iter().enumerate() {
+        if let Err(e) = fs::rename(&written[index], &plan.path) {
+            for temp in &written[index..] {
+                let _ = fs::remove_file(temp);
+            }
+            for done in &planned[..index] {
+                if let Err(e) = write_atomic(&done.path, done.original.as_bytes()) {
+                    log::error!("Failed to restore '{}': {}", done.path.display(), e);
+                }
+            }
+            return Err(format!("Failed to replace '{}': {}", plan.path.display(), e));
+        }
+        if let Err(e) = sync_parent_dir(&plan.path) {
+            log::warn!("Failed to sync the directory of '{}': {}", plan.path.display(), e);
+        }
+    }
+
+    log::info!(
+        "Replaced {} occurrences in {} files",
+        planned.iter().map(|plan| plan.summary.replacements).sum::<usize>(),
+        planned.len()
+    );
+    Ok(result_of(&planned, true))
+}
+
+/// Cancellation flags of running searches, keyed by the frontend-chosen search id
+#[derive(Default)]
+pub struct SearchRegistry {
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl SearchRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a search, cancelling an older one with the same id
+    pub fn start(&self, search_id: &str) -> Arc<AtomicBool> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        if let Some(previous) = self
+            .active
+            .lock()
+            .unwrap()
+            .insert(search_id.to_string(), cancel.clone())
+        {
+            previous.store(true, Ordering::Relaxed);
+        }
+        cancel
+    }
+
+    /// Forget a finished search (unless it was replaced by a newer one)
+    pub fn finish(&self, search_id: &str, cancel: &Arc<AtomicBool>) {
+        let mut active = self.active.lock().unwrap();
+        if active.get(search_id).is_some_and(|current| Arc::ptr_eq(current, cancel)) {
+            active.remove(search_id);
+        }
+    }
+
+    /// Cancel a running search. Returns false if no such search is running.
+    pub fn cancel(&self, search_id: &str) -> bool {
+        match self.active.lock().unwrap().remove(search_id) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("workspace-search-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::create_dir_all(root.join("node_modules")).unwrap();
+        fs::write(root.join("main.u"), "foo = 1\nbar = foo + foo\n").unwrap();
+        fs::write(root.join("lib/util.u"), "-- föo\nfood = 2\n").unwrap();
+        fs::write(root.join("notes.md"), "foo\n").unwrap();
+        fs::write(root.join(".hidden/x.u"), "foo\n").unwrap();
+        fs::write(root.join("node_modules/y.u"), "foo\n").unwrap();
+        fs::create_dir_all(root.join("out")).unwrap();
+        fs::write(root.join(".gitignore"), "/out/\n").unwrap();
+        fs::write(root.join("out/z.u"), "foo\n").unwrap();
+        fs::write(root.join("lib/.ignore"), "generated.u\n").unwrap();
+        fs::write(root.join("lib/generated.u"), "foo\n").unwrap();
+        root
+    }
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery {
+            query: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn search(root: &Path, query: &SearchQuery) -> (SearchSummary, Vec<FileMatches>) {
+        let mut files = Vec::new();
+        let summary = search_workspace(root, query, &AtomicBool::new(false), |f| files.push(f)).unwrap();
+        (summary, files)
+    }
+
+    #[test]
+    fn test_search_skips_hidden_ignored_and_non_unison_files() {
+        let root = workspace("skip");
+        let (summary, files) = search(&root, &query("foo"));
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("main.u") && paths[1].ends_with("util.u"));
+        assert_eq!(summary.match_count, 4);
+
+        let second = &files[0].matches[2];
+        assert_eq!((second.line, second.column, second.end_column), (2, 13, 16));
+
+        let (_, files) = search(&root, &SearchQuery { include: vec!["*.md".to_string()], ..query("foo") });
+        assert!(files[0].path.ends_with("notes.md"));
+
+        let (_, files) = search(&root, &SearchQuery { exclude: vec!["lib".to_string()], ..query("foo") });
+        assert_eq!(files.len(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_search_options_and_limits() {
+        let root = workspace("options");
+        let whole = SearchQuery { whole_word: true, ..query("foo") };
+        assert_eq!(search(&root, &whole).0.match_count, 3);
+
+        let case = SearchQuery { case_sensitive: true, ..query("FOO") };
+        assert_eq!(search(&root, &case).0.match_count, 0);
+
+        // Columns count UTF-16 units: "-- föo" has a match at column 4
+        let regex = SearchQuery { regex: true, ..query("f.o") };
+        let (_, files) = search(&root, &regex);
+        assert_eq!(files[1].matches[0].column, 4);
+
+        let limited = SearchQuery { max_matches: Some(2), ..query("foo") };
+        let (summary, _) = search(&root, &limited);
+        assert!(summary.truncated);
+        assert_eq!(summary.match_count, 2);
+
+        let cancelled = search_workspace(&root, &query("foo"), &AtomicBool::new(true), |_| {}).unwrap();
+        assert!(cancelled.cancelled);
+        assert!(query("(").matcher().is_ok());
+        assert!(SearchQuery { regex: true, ..query("(") }.matcher().is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_replace_preview_and_apply() {
+        let root = workspace("replace");
+        let regex = SearchQuery { regex: true, whole_word: true, ..query("(fo+)") };
+
+        let preview = preview_replace(&root, &regex, "${1}x").unwrap();
+        assert!(!preview.applied);
+        assert_eq!(preview.total_replacements, 3);
+        assert_eq!(preview.files[0].changes[1].after, "bar = foox + foox");
+        assert_eq!(fs::read_to_string(root.join("main.u")).unwrap(), "foo = 1\nbar = foo + foo\n");
+
+        // A file changed since the preview is refused
+        let versions: HashMap<String, String> = preview
+            .files
+            .iter()
+            .map(|f| (f.path.clone(), f.version.clone()))
+            .collect();
+        fs::write(root.join("main.u"), "foo = 3\n").unwrap();
+        assert!(apply_replace(&root, &regex, "${1}x", &versions).is_err());
+        assert_eq!(fs::read_to_string(root.join("main.u")).unwrap(), "foo = 3\n");
+
+        let applied = apply_replace(&root, &regex, "${1}x", &HashMap::new()).unwrap();
+        assert!(applied.applied);
+        assert_eq!(fs::read_to_string(root.join("main.u")).unwrap(), "foox = 3\n");
+        let leftovers = fs::read_dir(&root).unwrap().filter(|entry| {
+            entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp")
+        });
+        assert_eq!(leftovers.count(), 0);
+
+        // Literal replacements don't expand `$`
+        apply_replace(&root, &query("foox"), "$1", &HashMap::new()).unwrap();
+        assert_eq!(fs::read_to_string(root.join("main.u")).unwrap(), "$1 = 3\n");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_search_registry_cancel() {
+        let registry = SearchRegistry::new();
+        let first = registry.start("s");
+        let second = registry.start("s");
+        assert!(first.load(Ordering::Relaxed));
+        registry.finish("s", &first);
+        assert!(registry.cancel("s"));
+        assert!(second.load(Ordering::Relaxed));
+        assert!(!registry.cancel("s"));
+    }
+}
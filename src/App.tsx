@@ -32,7 +32,7 @@ import { themeService } from './theme/themeService';
 import { getLoggingService } from './services/loggingService';
 import { buildSingleWatchCode, buildSingleTestCode, buildAllWatchesCode, buildAllTestsCode, getTestName, detectTestExpressions, detectWatchExpressions } from './services/watchExpressionService';
 import { getWorkspaceConfigService, type WorkspaceEditorState, type PersistedTab, type WindowState } from './services/workspaceConfigService';
-import { getUCMLifecycleService } from './services/ucmLifecycle';
+import { getUCMLifecycleService, type LockHolder } from './services/ucmLifecycle';
 import './App.css';
 
 // Debounce helper
@@ -83,6 +83,7 @@ function App() {
 
   // State for UCM conflict modal (when another UCM is using the codebase)
   const [showConflictModal, setShowConflictModal] = useState(false);
+  const [lockHolder, setLockHolder] = useState<LockHolder | null>(null);
 
   const [selectedDefinition, setSelectedDefinition] = useState<{
     name: string;
@@ -138,6 +139,8 @@ function App() {
     fileName: string;
     filePath: string;
     newContent: string;
+    /** Version of `newContent` on disk, null if the file was deleted */
+    diskVersion: string | null;
   } | null>(null);
 
   const client = getUCMApiClient();
@@ -199,8 +202,9 @@ function App() {
 
     // Set up the listener
     (async () => {
-      unlisten = await listen('ucm-file-lock-error', () => {
-        console.log('[App] UCM file lock error received');
+      unlisten = await listen<{ holder: LockHolder | null }>('ucm-file-lock-error', (event) => {
+        console.log('[App] UCM file lock error received', event.payload.holder);
+        setLockHolder(event.payload.holder);
         setShowConflictModal(true);
       });
       console.log('[App] File lock error listener registered');
@@ -249,7 +253,10 @@ function App() {
         console.log(`[FileWatcher] App callback started, delay so far: ${callbackStartTime - event.detectedAt}ms, path: ${event.path}`);
 
         const { tabs: currentTabs, updateTab: storeUpdateTab } = useUnisonStore.getState();
-        const affectedTabs = currentTabs.filter((tab) => tab.filePath === event.path);
+        // Tabs already at the reported version (e.g. saved from another tab) need nothing
+        const affectedTabs = currentTabs.filter(
+          (tab) => tab.filePath === event.path && (event.version === null || tab.fileVersion !== event.version)
+        );
 
         if (affectedTabs.length === 0) {
           console.log(`[FileWatcher] No tabs found for path: ${event.path}`);
@@ -272,10 +279,14 @@ function App() {
         try {
           const readStartTime = Date.now();
           const { getFileSystemService } = await import('./services/fileSystem');
-          const newContent = await getFileSystemService().readFile(event.path);
+          const { content: newContent, version } = await getFileSystemService().readFileWithVersion(event.path);
           const readEndTime = Date.now();
 
           for (const tab of affectedTabs) {
+            if (tab.fileVersion === version) {
+              // Our own save, or a write that didn't change the content
+              continue;
+            }
             if (tab.isDirty) {
               // File has unsaved changes - show conflict modal
               setFileConflict({
@@ -283,10 +294,11 @@ function App() {
                 fileName: tab.title,
                 filePath: event.path,
                 newContent,
+                diskVersion: version,
               });
             } else {
               // File is clean - auto-reload
-              storeUpdateTab(tab.id, { content: newContent, isDirty: false });
+              storeUpdateTab(tab.id, { content: newContent, fileVersion: version, isDirty: false });
               const totalTime = Date.now() - event.detectedAt;
               console.log(`[FileWatcher] Tab updated - file read: ${readEndTime - readStartTime}ms, TOTAL: ${totalTime}ms`);
             }
@@ -482,10 +494,11 @@ function App() {
             for (const persistedTab of editorState.tabs) {
               try {
                 let content = persistedTab.content || '';
+                let fileVersion: string | undefined;
                 // If tab has a file path, load the content from disk (may have changed)
                 if (persistedTab.filePath) {
                   try {
-                    content = await fileSystemService.readFile(persistedTab.filePath);
+                    ({ content, version: fileVersion } = await fileSystemService.readFileWithVersion(persistedTab.filePath));
                   } catch (e) {
                     console.warn(`Failed to load file for tab ${persistedTab.title}:`, e);
                     continue; // Skip tabs with missing files
@@ -498,6 +511,7 @@ function App() {
                   language: persistedTab.language,
                   isDirty: false,
                   filePath: persistedTab.filePath,
+                  fileVersion,
                 };
                 addTab(tab);
               } catch (e) {
@@ -518,8 +532,9 @@ function App() {
         addRecentWorkspace(workspaceDirectory);
 
         // Spawn UCM PTY for this workspace if not already running
-        // (WelcomeScreen may have already spawned it)
-        if (!ucmLifecycle.isRunning()) {
+        // (WelcomeScreen may have already spawned it, or opened the workspace without UCM
+        // because another UCM holds the codebase lock)
+        if (!ucmLifecycle.isRunning() && ucmLifecycle.getSessionMode().kind === 'full') {
           // Wait for file lock error listener to be ready before spawning
           // This ensures we can show the modal if UCM fails due to file lock
           if (fileLockListenerReadyRef.current) {
@@ -929,6 +944,55 @@ function App() {
     }
   }
 
+  /**
+   * Handle running a Unison transcript (.md), showing each stanza as a test.
+   */
+  async function handleRunTranscript(path: string) {
+    const { setRunOutput } = useUnisonStore.getState();
+
+    setOutputPanelCollapsed(false);
+    setRunOutput({ type: 'info', message: `Running transcript ${path}...` });
+
+    // Stream UCM's output while the transcript runs
+    const lines: string[] = [];
+    const unlisten = await listen<{ path: string; line: string }>('transcript-output', (event) => {
+      if (event.payload.path !== path) return;
+      lines.push(event.payload.line);
+      setRunOutput({ type: 'info', message: `Running transcript ${path}...\n\n${lines.slice(-200).join('\n')}` });
+    });
+
+    try {
+      const result = await client.runTranscript(path);
+      const passed = result.testResults.filter(t => t.passed).length;
+      const failed = result.testResults.filter(t => !t.passed).length;
+      const skipped = result.blocks.filter(b => b.status === 'skipped').length;
+
+      const output = result.testResults
+        .map((t) => {
+          if (t.passed) {
+            return `✅ ${t.name}`;
+          }
+          return `🚫 ${t.name}\n   ${t.message.replace(/\n/g, '\n   ')}`;
+        })
+        .join('\n');
+      const errors = result.errors.length > 0 && failed === 0
+        ? `\n\n⚠️ Errors:\n${result.errors.join('\n')}`
+        : '';
+
+      setRunOutput({
+        type: result.success ? 'success' : 'error',
+        message: `${passed} passed, ${failed} failed, ${skipped} not run\n\n${output}${errors}`,
+      });
+    } catch (err) {
+      setRunOutput({
+        type: 'error',
+        message: `Failed to run transcript: ${err instanceof Error ? err.message : String(err)}`,
+      });
+    } finally {
+      unlisten();
+    }
+  }
+
   /**
    * Handle running all test expressions in the current file.
    */
@@ -940,6 +1004,12 @@ function App() {
       return;
     }
 
+    // Markdown files are transcripts: run them with UCM instead of typechecking
+    if (activeTab.filePath?.endsWith('.md')) {
+      await handleRunTranscript(activeTab.filePath);
+      return;
+    }
+
     if (!currentProject || !currentBranch) {
       setRunOutput({
         type: 'error',
@@ -1268,7 +1338,7 @@ function App() {
       // Load file content from disk
       const { getFileSystemService } = await import('./services/fileSystem');
       const fileSystemService = getFileSystemService();
-      const content = await fileSystemService.readFile(path);
+      const { content, version } = await fileSystemService.readFileWithVersion(path);
 
       // Create new tab for the file
       const newTab: EditorTab = {
@@ -1278,6 +1348,7 @@ function App() {
         language: 'unison',
         isDirty: false,
         filePath: path,
+        fileVersion: version,
       };
       addTab(newTab);
 
@@ -1290,6 +1361,21 @@ function App() {
     }
   }
 
+  /**
+   * Show the file conflict modal if a save failed because the file changed on disk
+   */
+  async function showSaveConflict(tab: EditorTab, err: unknown) {
+    const { FileConflictError } = await import('./services/fileSystem');
+    if (!(err instanceof FileConflictError) || !tab.filePath) return;
+    setFileConflict({
+      tabId: tab.id,
+      fileName: tab.title,
+      filePath: tab.filePath,
+      newContent: err.conflict.diskContent ?? '',
+      diskVersion: err.conflict.actualVersion,
+    });
+  }
+
   /**
    * Helper to trigger autosave for a tab after content is programmatically added
    */
@@ -1305,8 +1391,8 @@ function App() {
 
           const { getFileSystemService } = await import('./services/fileSystem');
           const fileSystemService = getFileSystemService();
-          await fileSystemService.writeFile(tab.filePath, tab.content);
-          storeUpdateTab(tabId, { isDirty: false, saveStatus: 'saved' });
+          const fileVersion = await fileSystemService.writeFile(tab.filePath, tab.content, undefined, tab.fileVersion);
+          storeUpdateTab(tabId, { isDirty: false, fileVersion, saveStatus: 'saved' });
           // Clear saved indicator after 3 seconds
           setTimeout(() => {
             useUnisonStore.getState().updateTab(tabId, { saveStatus: undefined });
@@ -1314,6 +1400,7 @@ function App() {
         } catch (err) {
           console.error('Auto-save failed:', err);
           useUnisonStore.getState().updateTab(tabId, { saveStatus: undefined });
+          await showSaveConflict(tab, err);
         }
       }
     }, 100);
@@ -1378,11 +1465,17 @@ function App() {
 
       const { getFileSystemService } = await import('./services/fileSystem');
       const fileSystemService = getFileSystemService();
-      await fileSystemService.writeFile(activeTab.filePath, activeTab.content);
+      const fileVersion = await fileSystemService.writeFile(
+        activeTab.filePath,
+        activeTab.content,
+        undefined,
+        activeTab.fileVersion
+      );
 
       // Mark as saved
       updateTab(activeTab.id, {
         isDirty: false,
+        fileVersion,
         saveStatus: 'saved',
       });
 
@@ -1393,6 +1486,7 @@ function App() {
     } catch (err) {
       console.error('Failed to save file:', err);
       updateTab(activeTab.id, { saveStatus: 'error' });
+      await showSaveConflict(activeTab, err);
 
       // Clear error after 3 seconds
       setTimeout(() => {
@@ -1763,17 +1857,44 @@ function App() {
     }
   }, [workspaceDirectory, setConnected]);
 
+  // Kill a UCM orphaned by a previous editor session, then spawn ours again
+  const handleKillLockHolder = useCallback(async (pid: number) => {
+    await getUCMLifecycleService().killLockHolder(pid);
+    await handleConflictRetry();
+  }, [handleConflictRetry]);
+
+  // Use the lock holder's API read-only instead of our own UCM
+  const handleConnectReadOnly = useCallback(async () => {
+    if (!workspaceDirectory) return;
+    await getUCMLifecycleService().connectToLockHolder(workspaceDirectory);
+    setShowConflictModal(false);
+    checkConnection();
+  }, [workspaceDirectory]);
+
+  // Keep the workspace open for file editing without UCM
+  const handleOpenDegraded = useCallback(async () => {
+    if (!workspaceDirectory) return;
+    await getUCMLifecycleService().openDegraded(workspaceDirectory);
+    setShowConflictModal(false);
+  }, [workspaceDirectory]);
+
   // Handle file conflict - reload from disk
   const handleFileConflictReload = useCallback(() => {
     if (!fileConflict) return;
-    updateTab(fileConflict.tabId, { content: fileConflict.newContent, isDirty: false });
+    updateTab(fileConflict.tabId, {
+      content: fileConflict.newContent,
+      fileVersion: fileConflict.diskVersion ?? undefined,
+      isDirty: false,
+    });
     setFileConflict(null);
   }, [fileConflict, updateTab]);
 
-  // Handle file conflict - keep local changes
+  // Handle file conflict - keep local changes (the next save overwrites the disk version)
   const handleFileConflictKeepLocal = useCallback(() => {
+    if (!fileConflict) return;
+    updateTab(fileConflict.tabId, { fileVersion: fileConflict.diskVersion ?? undefined });
     setFileConflict(null);
-  }, []);
+  }, [fileConflict, updateTab]);
 
   // Show welcome screen when no workspace is selected
   if (showWelcome) {
@@ -1987,13 +2108,18 @@ function App() {
 
       <UCMConflictModal
         isOpen={showConflictModal}
+        holder={lockHolder}
         onRetry={handleConflictRetry}
+        onKillHolder={handleKillLockHolder}
+        onConnectReadOnly={handleConnectReadOnly}
+        onOpenDegraded={handleOpenDegraded}
       />
 
       <FileConflictModal
         isOpen={fileConflict !== null}
         fileName={fileConflict?.fileName || ''}
         filePath={fileConflict?.filePath || ''}
+        deleted={fileConflict !== null && fileConflict.diskVersion === null}
         onReload={handleFileConflictReload}
         onKeepLocal={handleFileConflictKeepLocal}
       />
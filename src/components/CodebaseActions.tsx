@@ -83,6 +83,8 @@ export function CodebaseActions({ onSuccess, onTypecheckAll, onRunAllWatchExpres
         code: activeTab.content,
         projectName: currentProject.name,
         branchName: currentBranch.name,
+        // Lets the backend snapshot the code in the file's history first
+        filePath: activeTab.filePath ?? null,
       });
 
       if (result.success) {
@@ -107,7 +107,7 @@ export function ConnectionStatus() {
       const ports = ucmLifecycle.getPorts();
       const wsPort = ports?.lspProxyPort ?? 5758; // Fallback to default if not available
 
-      await lspClient.connect(wsPort);
+      await lspClient.connect(wsPort, ports?.lspProxyToken ?? '');
     } catch (error) {
       console.error('Failed to reconnect LSP:', error);
       setLspState('disconnected');
@@ -80,7 +80,7 @@ export function DeleteConfirmModal({
             </p>
           )}
           <p className="warning-text">
-            This action cannot be undone.
+            {isSingleItem ? 'It' : 'They'} will be moved to the trash (undo with Ctrl+Z).
           </p>
         </div>
 
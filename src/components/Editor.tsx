@@ -159,7 +159,7 @@ export function Editor({
         }
 
         // Connect to LSP via WebSocket proxy
-        await lspClient.connect(wsPort);
+        await lspClient.connect(wsPort, ports?.lspProxyToken ?? '');
         lastConnectedPortRef.current = wsPort;
         console.log(`[Editor] LSP connected on port ${wsPort} (diagnostics only)`);
       } catch (error) {
@@ -2,6 +2,8 @@ interface FileConflictModalProps {
   isOpen: boolean;
   fileName: string;
   filePath: string;
+  /** The file was deleted on disk (there is nothing to reload) */
+  deleted?: boolean;
   onReload: () => void;
   onKeepLocal: () => void;
 }
@@ -10,6 +12,7 @@ export function FileConflictModal({
   isOpen,
   fileName,
   filePath,
+  deleted = false,
   onReload,
   onKeepLocal,
 }: FileConflictModalProps) {
@@ -31,7 +34,7 @@ export function FileConflictModal({
 
         <div className="modal-body">
           <p>
-            The file <strong>{fileName}</strong> has been modified outside the editor.
+            The file <strong>{fileName}</strong> has been {deleted ? 'deleted' : 'modified'} outside the editor.
           </p>
           <p className="file-conflict-path">{filePath}</p>
           <p>You have unsaved changes. What would you like to do?</p>
@@ -41,9 +44,11 @@ export function FileConflictModal({
           <button type="button" className="btn-secondary" onClick={onKeepLocal}>
             Keep My Changes
           </button>
-          <button type="button" className="btn-primary btn-danger" onClick={onReload}>
-            Reload from Disk
-          </button>
+          {!deleted && (
+            <button type="button" className="btn-primary btn-danger" onClick={onReload}>
+              Reload from Disk
+            </button>
+          )}
         </div>
       </div>
     </div>
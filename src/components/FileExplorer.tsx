@@ -1,6 +1,7 @@
 import { useState, useEffect, useRef, type JSX } from 'react';
-import { getFileSystemService, type FileNode } from '../services/fileSystem';
+import { getFileSystemService, type FileNode, type FileOperation } from '../services/fileSystem';
 import { useUnisonStore } from '../store/unisonStore';
+import { getFileWatcherService } from '../services/fileWatcherService';
 import { ContextMenu, type ContextMenuItem } from './ContextMenu';
 import { RenameModal } from './RenameModal';
 import { DeleteConfirmModal } from './DeleteConfirmModal';
@@ -64,6 +65,27 @@ export function FileExplorer({ onFileClick, showOnlyUnison = false, refreshTrigg
     }
   }, [workspaceDirectory, refreshTrigger]);
 
+  // Latest loader for the workspace watcher callback, which outlives renders
+  const reloadRef = useRef<() => Promise<void>>(async () => {});
+  reloadRef.current = () => loadDirectoryWithExpandedDirs(expandedDirs, true);
+
+  // Reload when entries are created, renamed or deleted outside the explorer
+  // (UCM's `edit`, git, other editors)
+  useEffect(() => {
+    if (!workspaceDirectory) return;
+    const fileWatcherService = getFileWatcherService();
+    fileWatcherService.watchWorkspace(workspaceDirectory).catch((err) => {
+      console.error('[FileExplorer] Failed to watch workspace:', err);
+    });
+    const unsubscribe = fileWatcherService.onWorkspaceChange((batch) => {
+      const treeChanged = batch.rescan || batch.changes.some((change) => change.kind !== 'modified');
+      if (treeChanged) {
+        reloadRef.current();
+      }
+    });
+    return unsubscribe;
+  }, [workspaceDirectory]);
+
   // Auto-expand parent directories when focused file changes
   useEffect(() => {
     if (!focusedFilePath || !workspaceDirectory) return;
@@ -108,10 +130,11 @@ export function FileExplorer({ onFileClick, showOnlyUnison = false, refreshTrigg
     await loadDirectoryWithExpandedDirs(expandedDirs);
   }
 
-  async function loadDirectoryWithExpandedDirs(dirsToExpand: Set<string>) {
+  /** @param quiet - Keep showing the current tree while loading (background refresh) */
+  async function loadDirectoryWithExpandedDirs(dirsToExpand: Set<string>, quiet = false) {
     if (!workspaceDirectory) return;
 
-    setLoading(true);
+    if (!quiet) setLoading(true);
     setError(null);
 
     try {
@@ -337,6 +360,11 @@ export function FileExplorer({ onFileClick, showOnlyUnison = false, refreshTrigg
         icon: '✏️',
         onClick: () => setRenameModal(file),
       });
+      items.push({
+        label: 'Duplicate',
+        icon: '📑',
+        onClick: () => handleDuplicate(file),
+      });
     }
 
     // Delete supports multi-select
@@ -364,26 +392,32 @@ export function FileExplorer({ onFileClick, showOnlyUnison = false, refreshTrigg
     }
   }
 
-  async function handleDelete(filesToDelete: FileNode[]) {
+  async function handleDuplicate(file: FileNode) {
     try {
-      const { tabs, removeTab } = useUnisonStore.getState();
+      await fileSystemService.duplicatePath(file.path);
+      await loadDirectory();
+    } catch (err) {
+      console.error('Failed to duplicate:', err);
+      setError(err instanceof Error ? err.message : 'Failed to duplicate');
+    }
+  }
+
+  /** Close the tabs of a removed file, or of the files inside a removed directory */
+  function closeTabsOf(path: string, isDirectory: boolean) {
+    const { tabs, removeTab } = useUnisonStore.getState();
+    const tabsToClose = tabs.filter(tab =>
+      isDirectory ? tab.filePath?.startsWith(path + '/') : tab.filePath === path
+    );
+    for (const tab of tabsToClose) {
+      removeTab(tab.id);
+    }
+  }
 
+  async function handleDelete(filesToDelete: FileNode[]) {
+    try {
       for (const file of filesToDelete) {
         await fileSystemService.deleteFile(file.path);
-
-        // Close any tabs that have this file open
-        const tabsToClose = tabs.filter(tab => {
-          if (file.isDirectory) {
-            // For directories, close all tabs with files inside this directory
-            return tab.filePath?.startsWith(file.path + '/');
-          } else {
-            // For files, close the exact matching tab
-            return tab.filePath === file.path;
-          }
-        });
-        for (const tab of tabsToClose) {
-          removeTab(tab.id);
-        }
+        closeTabsOf(file.path, file.isDirectory);
       }
 
       // Clear selection after delete
@@ -398,6 +432,41 @@ export function FileExplorer({ onFileClick, showOnlyUnison = false, refreshTrigg
     }
   }
 
+  /** Undo (Ctrl/Cmd+Z) or redo (Ctrl/Cmd+Shift+Z, Ctrl+Y) the last file operation */
+  async function handleUndoRedo(redo: boolean) {
+    try {
+      const operation: FileOperation | null = redo
+        ? await fileSystemService.redoFileOperation()
+        : await fileSystemService.undoFileOperation();
+      if (!operation) return;
+
+      // Undoing a create and redoing a delete both remove the entry
+      const closeRemoved = (done: FileOperation) => {
+        if (done.kind === 'batch') {
+          done.operations.forEach(closeRemoved);
+        } else if ((done.kind === 'create' && !redo) || (done.kind === 'delete' && redo)) {
+          // A delete doesn't say whether the entry was a directory, so close both ways
+          closeTabsOf(done.path, false);
+          closeTabsOf(done.path, true);
+        }
+      };
+      closeRemoved(operation);
+      await loadDirectory();
+    } catch (err) {
+      console.error('Failed to undo/redo:', err);
+      setError(err instanceof Error ? err.message : 'Failed to undo');
+    }
+  }
+
+  function handleKeyDown(e: React.KeyboardEvent) {
+    if (!(e.metaKey || e.ctrlKey)) return;
+    const key = e.key.toLowerCase();
+    if (key === 'z' || key === 'y') {
+      e.preventDefault();
+      handleUndoRedo(key === 'y' || e.shiftKey);
+    }
+  }
+
   async function handleCreateFolder(parentPath: string, folderName: string) {
     try {
       const newPath = `${parentPath}/${folderName}`;
@@ -808,6 +877,8 @@ export function FileExplorer({ onFileClick, showOnlyUnison = false, refreshTrigg
       <div
         ref={containerRef}
         className="file-explorer-items"
+        tabIndex={-1}
+        onKeyDown={handleKeyDown}
         onDragOver={handleContainerDragOver}
         onDrop={handleContainerDrop}
       >
@@ -3,8 +3,13 @@ import { Terminal, type ITheme } from '@xterm/xterm';
 import { FitAddon } from '@xterm/addon-fit';
 import { WebLinksAddon } from '@xterm/addon-web-links';
 import { themeService } from '../theme/themeService';
+import { useUnisonStore } from '../store/unisonStore';
+import { ShellTerminalSession } from '../services/shellTerminal';
 import '@xterm/xterm/css/xterm.css';
 
+/** Backend PTY session of the terminal panel */
+const SHELL_SESSION_ID = 'general-terminal';
+
 interface GeneralTerminalProps {
   isCollapsed: boolean;
 }
@@ -14,8 +19,8 @@ export interface GeneralTerminalHandle {
 }
 
 /**
- * A general-purpose terminal component.
- * Currently displays a placeholder - could be extended to spawn a shell PTY.
+ * A general-purpose terminal component running the user's login shell
+ * in the workspace directory.
  * Exposes focus() via ref for keyboard shortcuts.
  */
 export const GeneralTerminal = forwardRef<GeneralTerminalHandle, GeneralTerminalProps>(function GeneralTerminal({ isCollapsed }, ref) {
@@ -23,6 +28,9 @@ export const GeneralTerminal = forwardRef<GeneralTerminalHandle, GeneralTerminal
   const xtermRef = useRef<Terminal | null>(null);
   const fitAddonRef = useRef<FitAddon | null>(null);
   const resizeObserverRef = useRef<ResizeObserver | null>(null);
+  const shellRef = useRef<ShellTerminalSession>(new ShellTerminalSession(SHELL_SESSION_ID));
+  const shellCwdRef = useRef<string | null | undefined>(undefined);
+  const workspaceDirectory = useUnisonStore((state) => state.workspaceDirectory);
 
   // Expose focus method via ref for keyboard shortcuts
   useImperativeHandle(ref, () => ({
@@ -69,11 +77,16 @@ export const GeneralTerminal = forwardRef<GeneralTerminalHandle, GeneralTerminal
     xtermRef.current = term;
     fitAddonRef.current = fitAddon;
 
-    // Show placeholder message
-    term.writeln('\x1b[90m--- General Terminal ---\x1b[0m');
-    term.writeln('\x1b[90mThis terminal is a placeholder.\x1b[0m');
-    term.writeln('\x1b[90mFuture: Connect to system shell (bash/zsh).\x1b[0m');
-    term.writeln('');
+    // Forward keystrokes and size changes to the shell
+    const shell = shellRef.current;
+    const dataDisposable = term.onData((data) => {
+      shell.write(data).catch(() => {
+        // Shell not running (exited) - input is dropped until it is restarted
+      });
+    });
+    const resizeDisposable = term.onResize(({ rows, cols }) => {
+      shell.resize(rows, cols).catch(() => {});
+    });
 
     // Set up resize observer for container
     const observer = new ResizeObserver(() => {
@@ -86,6 +99,8 @@ export const GeneralTerminal = forwardRef<GeneralTerminalHandle, GeneralTerminal
 
     // Cleanup
     return () => {
+      dataDisposable.dispose();
+      resizeDisposable.dispose();
       if (resizeObserverRef.current) {
         resizeObserverRef.current.disconnect();
       }
@@ -95,6 +110,44 @@ export const GeneralTerminal = forwardRef<GeneralTerminalHandle, GeneralTerminal
     };
   }, []);
 
+  // Connect to the shell, restarting it in the new directory when the workspace changes
+  useEffect(() => {
+    const term = xtermRef.current;
+    if (!term) return;
+    const shell = shellRef.current;
+    let cancelled = false;
+
+    const connect = async () => {
+      if (shellCwdRef.current !== undefined && shellCwdRef.current !== workspaceDirectory) {
+        await shell.kill();
+        term.reset();
+      }
+      shellCwdRef.current = workspaceDirectory;
+      if (cancelled) return;
+
+      await shell.connect(workspaceDirectory, term.rows, term.cols, {
+        onData: (data) => term.write(data),
+        onExit: (status) => {
+          const code = status.exitCode ?? '?';
+          term.writeln(`\r\n\x1b[90m[Process exited with code ${code}]\x1b[0m`);
+        },
+      });
+      // Unmounted while connecting - stop the listeners set up meanwhile
+      if (cancelled) {
+        shell.disconnect();
+      }
+    };
+
+    connect().catch((err) => {
+      term.writeln(`\x1b[31mFailed to start shell: ${err}\x1b[0m`);
+    });
+
+    return () => {
+      cancelled = true;
+      shell.disconnect();
+    };
+  }, [workspaceDirectory]);
+
   // Subscribe to theme changes
   useEffect(() => {
     const unsubscribe = themeService.onThemeChange((event) => {
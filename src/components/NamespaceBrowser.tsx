@@ -291,9 +291,7 @@ export function NamespaceBrowser({
               const newFQN = node.name;
               await moveItem(node.fullPath, newFQN, node.type);
             }
-            setTimeout(() => {
-              refreshNamespace();
-            }, 500);
+            refreshNamespace();
           } catch (err) {
             console.error('Failed to move items to root:', err);
           }
@@ -480,10 +478,7 @@ export function NamespaceBrowser({
         const newFQN = `${targetNode.fullPath}.${node.name}`;
         await moveItem(node.fullPath, newFQN, node.type);
       }
-      // Wait for UCM to process the commands before refreshing
-      setTimeout(() => {
-        refreshNamespace();
-      }, 500);
+      refreshNamespace();
     } catch (err) {
       console.error('Failed to move items:', err);
     }
@@ -550,9 +545,7 @@ export function NamespaceBrowser({
         const newFQN = node.name;
         await moveItem(node.fullPath, newFQN, node.type);
       }
-      setTimeout(() => {
-        refreshNamespace();
-      }, 500);
+      refreshNamespace();
     } catch (err) {
       console.error('Failed to move items to root:', err);
     }
@@ -40,11 +40,8 @@ export function NamespaceDeleteModal({
       for (const item of items) {
         await deleteItem(item.fullPath, item.type);
       }
-      // Wait for UCM to process the commands before refreshing
-      // UCM PTY commands are async, so we need a short delay
-      setTimeout(() => {
-        onComplete?.();
-      }, 500);
+      // Commands resolve once UCM has finished, so the namespace can be refreshed now
+      onComplete?.();
       onClose();
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to delete');
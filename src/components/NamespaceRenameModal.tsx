@@ -83,10 +83,8 @@ export function NamespaceRenameModal({
 
     try {
       await moveItem(currentFQN, newFQN, itemType);
-      // Wait for UCM to process the command before refreshing
-      setTimeout(() => {
-        onComplete?.();
-      }, 500);
+      // The command resolves once UCM has finished, so the namespace can be refreshed now
+      onComplete?.();
       onClose();
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to move/rename');
@@ -8,6 +8,8 @@ import { ucmContext } from '../services/ucmContext';
 interface UCMContext {
   project: string;
   branch: string;
+  /** Namespace path within the branch, null at the branch root */
+  path: string | null;
 }
 
 export function ProjectBranchSelector() {
@@ -1,14 +1,44 @@
+import { useState } from 'react';
+import type { LockHolder } from '../services/ucmLifecycle';
+
 interface UCMConflictModalProps {
   isOpen: boolean;
+  /** Process holding the codebase lock, if it could be found */
+  holder: LockHolder | null;
   onRetry: () => void;
+  /** Kill the holder (offered only for a UCM orphaned by a previous editor session) */
+  onKillHolder: (pid: number) => Promise<void>;
+  /** Use the holder's HTTP API read-only */
+  onConnectReadOnly: () => Promise<void>;
+  /** Open the workspace without UCM */
+  onOpenDegraded: () => Promise<void>;
 }
 
 export function UCMConflictModal({
   isOpen,
+  holder,
   onRetry,
+  onKillHolder,
+  onConnectReadOnly,
+  onOpenDegraded,
 }: UCMConflictModalProps) {
+  const [busy, setBusy] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
   if (!isOpen) return null;
 
+  const run = async (action: () => Promise<void>) => {
+    setBusy(true);
+    setError(null);
+    try {
+      await action();
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setBusy(false);
+    }
+  };
+
   return (
     <div className="ucm-conflict-overlay">
       <div className="ucm-conflict-modal">
@@ -24,12 +54,54 @@ export function UCMConflictModal({
           Another UCM process is using this codebase. Only one UCM instance can
           access a codebase at a time.
         </p>
-        <p className="ucm-conflict-hint">
-          Please close any existing UCM instances (terminal windows, other
-          editor instances) before continuing.
-        </p>
+        {holder ? (
+          <div className="ucm-conflict-holder">
+            <div>
+              <strong>PID {holder.pid}</strong>
+              {holder.orphaned && ' (left behind by a previous editor session)'}
+            </div>
+            <code>{holder.commandLine}</code>
+          </div>
+        ) : (
+          <p className="ucm-conflict-hint">
+            Please close any existing UCM instances (terminal windows, other
+            editor instances) before continuing.
+          </p>
+        )}
+        {error && <p className="ucm-conflict-error">{error}</p>}
         <div className="ucm-conflict-actions">
-          <button className="ucm-conflict-btn primary" onClick={onRetry}>
+          {holder?.orphaned && (
+            <button
+              className="ucm-conflict-btn primary"
+              disabled={busy}
+              onClick={() => run(() => onKillHolder(holder.pid))}
+            >
+              Stop It and Retry
+            </button>
+          )}
+          {holder?.apiPort != null && (
+            <button
+              className="ucm-conflict-btn secondary"
+              disabled={busy}
+              onClick={() => run(onConnectReadOnly)}
+              title={`Browse the codebase through its API on port ${holder.apiPort}`}
+            >
+              Connect Read-Only
+            </button>
+          )}
+          <button
+            className="ucm-conflict-btn secondary"
+            disabled={busy}
+            onClick={() => run(onOpenDegraded)}
+            title="Edit files without UCM"
+          >
+            Open Without UCM
+          </button>
+          <button
+            className={`ucm-conflict-btn ${holder?.orphaned ? 'secondary' : 'primary'}`}
+            disabled={busy}
+            onClick={onRetry}
+          >
             Retry
           </button>
         </div>
@@ -1,3 +1,37 @@
+import { useEffect, useState } from 'react';
+import {
+  getConfiguredUCMPath,
+  getUCMInfo,
+  setConfiguredUCMPath,
+  type UCMInfo,
+} from '../services/ucmLifecycle';
+
+const SOURCE_LABELS: Record<NonNullable<UCMInfo['source']>, string> = {
+  settings: 'configured path',
+  environment: 'UNISON_UCM_PATH',
+  searchPath: 'PATH search',
+};
+
+function describeSupport(info: UCMInfo): string | null {
+  switch (info.support) {
+    case 'tooOld':
+      return `This version is older than the oldest supported release (${info.minVersion}).`;
+    case 'tooNew':
+      return `This version is newer than the editor supports (below ${info.maxVersion}).`;
+    case 'unknown':
+      return 'The version could not be determined (development build?).';
+    default:
+      return null;
+  }
+}
+
+const codeStyle = {
+  background: 'var(--color-tab-background)',
+  padding: '2px 6px',
+  borderRadius: '4px',
+  fontSize: '12px',
+};
+
 interface UCMNotFoundModalProps {
   isOpen: boolean;
   onRetry: () => void;
@@ -9,8 +43,33 @@ export function UCMNotFoundModal({
   onRetry,
   onDismiss,
 }: UCMNotFoundModalProps) {
+  const [info, setInfo] = useState<UCMInfo | null>(null);
+  const [customPath, setCustomPath] = useState('');
+  const [pathError, setPathError] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!isOpen) return;
+    setCustomPath(getConfiguredUCMPath() ?? '');
+    setPathError(null);
+    getUCMInfo()
+      .then(setInfo)
+      .catch((err) => setPathError(String(err)));
+  }, [isOpen]);
+
   if (!isOpen) return null;
 
+  const handleUsePath = async () => {
+    try {
+      await setConfiguredUCMPath(customPath.trim() || null);
+      setPathError(null);
+      onRetry();
+    } catch (err) {
+      setPathError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  const supportMessage = info ? describeSupport(info) : null;
+
   return (
     <div className="ucm-conflict-overlay">
       <div className="ucm-conflict-modal">
@@ -36,18 +95,50 @@ export function UCMNotFoundModal({
           >
             unison-lang.org
           </a>{' '}
-          and ensure it's in your PATH (typically installed via Homebrew: <code style={{
-            background: 'var(--color-tab-background)',
-            padding: '2px 6px',
-            borderRadius: '4px',
-            fontSize: '12px'
-          }}>brew install unisonweb/unison/unison</code>).
+          and ensure it's in your PATH (typically installed via Homebrew: <code style={codeStyle}>brew install unisonweb/unison/unison</code>).
         </p>
+        {info && (
+          <div className="ucm-conflict-hint">
+            {info.found ? (
+              <p>
+                Found <code style={codeStyle}>{info.path}</code>
+                {info.source && <> via {SOURCE_LABELS[info.source]}</>}
+                {info.version && <>, version {info.version}</>}.
+              </p>
+            ) : (
+              <details>
+                <summary>Searched {info.searchedLocations.length} locations</summary>
+                <ul>
+                  {info.searchedLocations.map((location) => (
+                    <li key={location}>
+                      <code style={codeStyle}>{location}</code>
+                    </li>
+                  ))}
+                </ul>
+              </details>
+            )}
+            {supportMessage && <p>{supportMessage}</p>}
+            {info.error && <p>{info.error}</p>}
+          </div>
+        )}
+        <div className="ucm-conflict-hint">
+          <label>
+            Path to <code style={codeStyle}>ucm</code> (leave empty to auto-detect):
+            <input
+              type="text"
+              value={customPath}
+              onChange={(e) => setCustomPath(e.target.value)}
+              placeholder="/usr/local/bin/ucm"
+              style={{ width: '100%', marginTop: '4px' }}
+            />
+          </label>
+          {pathError && <p style={{ color: 'var(--color-status-error)' }}>{pathError}</p>}
+        </div>
         <div className="ucm-conflict-actions">
           <button className="ucm-conflict-btn secondary" onClick={onDismiss}>
             Dismiss
           </button>
-          <button className="ucm-conflict-btn primary" onClick={onRetry}>
+          <button className="ucm-conflict-btn primary" onClick={handleUsePath}>
             Retry
           </button>
         </div>
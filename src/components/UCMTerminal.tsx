@@ -157,7 +157,8 @@ export const UCMTerminal = forwardRef<UCMTerminalHandle, UCMTerminalProps>(funct
 
     // Subscribe to UCM output from the lifecycle service with write batching
     // This prevents xterm.js from being overwhelmed during high-volume output (e.g., run commands)
-    unsubscribeOutputRef.current = ucmService.onOutput((data) => {
+    // Attaching replays the backend scrollback first, so a remounted terminal keeps its history
+    unsubscribeOutputRef.current = ucmService.attachOutput((data) => {
       writeBufferRef.current.push(data);
 
       // Schedule a batched write if not already scheduled
@@ -181,6 +182,11 @@ export const UCMTerminal = forwardRef<UCMTerminalHandle, UCMTerminalProps>(funct
           term.write(combined);
         });
       }
+    }, (snapshot) => {
+      // The PTY may still have the size of a previous terminal - make UCM use ours
+      if (snapshot.rows !== term.rows || snapshot.cols !== term.cols) {
+        ucmService.resize(term.rows, term.cols);
+      }
     });
 
     // Send input to UCM via lifecycle service, but intercept 'exit' command
@@ -1,12 +1,12 @@
 import { useState, useCallback, useEffect, useRef } from 'react';
-import { open } from '@tauri-apps/plugin-dialog';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { useUnisonStore } from '../store/unisonStore';
 import { WorkspaceSetupDialog } from './WorkspaceSetupDialog';
 import { UCMConflictModal } from './UCMConflictModal';
 import { UCMNotFoundModal } from './UCMNotFoundModal';
-import { getUCMLifecycleService } from '../services/ucmLifecycle';
+import { getUCMLifecycleService, type LockHolder } from '../services/ucmLifecycle';
 import { getUCMApiClient } from '../services/ucmApi';
+import { getFileSystemService } from '../services/fileSystem';
 import appIcon from '../assets/app-icon.png';
 
 // Helper to detect if error is related to UCM not being found
@@ -36,6 +36,7 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
   const [isStartingUCM, setIsStartingUCM] = useState(false);
   const [startupError, setStartupError] = useState<string | null>(null);
   const [showConflictModal, setShowConflictModal] = useState(false);
+  const [lockHolder, setLockHolder] = useState<LockHolder | null>(null);
   const [showNotFoundModal, setShowNotFoundModal] = useState(false);
   const pendingFolderRef = useRef<string | null>(null);
   const pendingActionRef = useRef<'open' | 'recent' | null>(null);
@@ -46,9 +47,10 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
   useEffect(() => {
     let unlisten: UnlistenFn | null = null;
 
-    listen('ucm-file-lock-error', () => {
-      console.log('[WelcomeScreen] UCM file lock error received');
+    listen<{ holder: LockHolder | null }>('ucm-file-lock-error', (event) => {
+      console.log('[WelcomeScreen] UCM file lock error received', event.payload.holder);
       fileLockErrorRef.current = true; // Set flag so spawnUCMForFolder knows to abort
+      setLockHolder(event.payload.holder);
       setIsStartingUCM(false);
       setShowConflictModal(true);
     }).then((fn) => {
@@ -71,6 +73,9 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
     fileLockErrorRef.current = false; // Reset flag before spawn attempt
 
     try {
+      // The setup dialog writes the workspace config before the workspace is set
+      await getFileSystemService().openWorkspace(folder);
+
       // Spawn UCM early so projects are available in the setup dialog
       const ucmLifecycle = getUCMLifecycleService();
       await ucmLifecycle.spawn(folder);
@@ -132,14 +137,22 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
     }
   }, []);
 
+  // Publish the workspace once the backend has opened it
+  const finishOpening = useCallback(async (folder: string) => {
+    try {
+      await setWorkspaceDirectory(folder);
+    } catch (err) {
+      setStartupError(err instanceof Error ? err.message : String(err));
+      return;
+    }
+    addRecentWorkspace(folder);
+    onWorkspaceReady();
+  }, [setWorkspaceDirectory, addRecentWorkspace, onWorkspaceReady]);
+
   const handleOpenFolder = useCallback(async () => {
-    const selected = await open({
-      directory: true,
-      multiple: false,
-      title: 'Select Workspace Folder',
-    });
+    const selected = await getFileSystemService().pickWorkspaceFolder('Select Workspace Folder');
 
-    if (selected && typeof selected === 'string') {
+    if (selected) {
       const success = await spawnUCMForFolder(selected, 'open');
       if (success) {
         setShowSetupDialog(true);
@@ -151,12 +164,10 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
     async (path: string) => {
       const success = await spawnUCMForFolder(path, 'recent');
       if (success) {
-        setWorkspaceDirectory(path);
-        addRecentWorkspace(path);
-        onWorkspaceReady();
+        await finishOpening(path);
       }
     },
-    [spawnUCMForFolder, setWorkspaceDirectory, addRecentWorkspace, onWorkspaceReady]
+    [spawnUCMForFolder, finishOpening]
   );
 
   const handleConflictRetry = useCallback(async () => {
@@ -177,12 +188,38 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
       if (action === 'open') {
         setShowSetupDialog(true);
       } else {
-        setWorkspaceDirectory(folder);
-        addRecentWorkspace(folder);
-        onWorkspaceReady();
+        await finishOpening(folder);
       }
     }
-  }, [spawnUCMForFolder, setWorkspaceDirectory, addRecentWorkspace, onWorkspaceReady]);
+  }, [spawnUCMForFolder, finishOpening]);
+
+  const handleKillLockHolder = useCallback(async (pid: number) => {
+    await getUCMLifecycleService().killLockHolder(pid);
+    await handleConflictRetry();
+  }, [handleConflictRetry]);
+
+  // Continue opening the pending folder without our own UCM (read-only or degraded)
+  const continueWithoutUCM = useCallback(async (mode: 'readOnly' | 'degraded') => {
+    const folder = pendingFolderRef.current;
+    const action = pendingActionRef.current;
+    if (!folder || !action) {
+      return;
+    }
+
+    const ucmLifecycle = getUCMLifecycleService();
+    if (mode === 'readOnly') {
+      await ucmLifecycle.connectToLockHolder(folder);
+    } else {
+      await ucmLifecycle.openDegraded(folder);
+    }
+    setShowConflictModal(false);
+
+    if (action === 'open') {
+      setShowSetupDialog(true);
+    } else {
+      await finishOpening(folder);
+    }
+  }, [finishOpening]);
 
   const handleNotFoundRetry = useCallback(async () => {
     setShowNotFoundModal(false);
@@ -202,25 +239,21 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
       if (action === 'open') {
         setShowSetupDialog(true);
       } else {
-        setWorkspaceDirectory(folder);
-        addRecentWorkspace(folder);
-        onWorkspaceReady();
+        await finishOpening(folder);
       }
     }
-  }, [spawnUCMForFolder, setWorkspaceDirectory, addRecentWorkspace, onWorkspaceReady]);
+  }, [spawnUCMForFolder, finishOpening]);
 
   const handleNotFoundDismiss = useCallback(() => {
     setShowNotFoundModal(false);
   }, []);
 
-  const handleSetupComplete = useCallback(() => {
+  const handleSetupComplete = useCallback(async () => {
     if (selectedFolder) {
-      setWorkspaceDirectory(selectedFolder);
-      addRecentWorkspace(selectedFolder);
       setShowSetupDialog(false);
-      onWorkspaceReady();
+      await finishOpening(selectedFolder);
     }
-  }, [selectedFolder, setWorkspaceDirectory, addRecentWorkspace, onWorkspaceReady]);
+  }, [selectedFolder, finishOpening]);
 
   const handleSetupCancel = useCallback(() => {
     setShowSetupDialog(false);
@@ -310,7 +343,11 @@ export function WelcomeScreen({ onWorkspaceReady }: WelcomeScreenProps) {
 
       <UCMConflictModal
         isOpen={showConflictModal}
+        holder={lockHolder}
         onRetry={handleConflictRetry}
+        onKillHolder={handleKillLockHolder}
+        onConnectReadOnly={() => continueWithoutUCM('readOnly')}
+        onOpenDegraded={() => continueWithoutUCM('degraded')}
       />
 
       <UCMNotFoundModal
@@ -1,5 +1,4 @@
 import { useCallback, useEffect, useState } from 'react';
-import { open } from '@tauri-apps/plugin-dialog';
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { useUnisonStore } from '../store/unisonStore';
@@ -8,6 +7,7 @@ import { ucmContext } from '../services/ucmContext';
 import { WorkspaceSetupDialog } from './WorkspaceSetupDialog';
 import { getLSPService } from '../services/lspService';
 import { getUCMLifecycleService } from '../services/ucmLifecycle';
+import { getFileSystemService } from '../services/fileSystem';
 import {
   getWorkspaceConfigService,
   type WorkspaceConfig,
@@ -18,6 +18,8 @@ type ConnectionStatus = 'disconnected' | 'connecting' | 'connected';
 interface UCMContext {
   project: string;
   branch: string;
+  /** Namespace path within the branch, null at the branch root */
+  path: string | null;
 }
 
 export function WorkspaceProjectLinker() {
@@ -298,19 +300,20 @@ export function WorkspaceProjectLinker() {
 
   // Open an existing folder as a workspace (quick open, no setup dialog)
   const handleOpenWorkspace = useCallback(async () => {
-    const selected = await open({
-      directory: true,
-      multiple: false,
-      title: 'Open Workspace Folder',
-    });
+    const selected = await getFileSystemService().pickWorkspaceFolder('Open Workspace Folder');
 
-    if (selected && typeof selected === 'string') {
+    if (selected) {
       // Stop the current UCM process before switching workspaces
       // This ensures UCM restarts with the new workspace's cwd
       await ucmLifecycle.stop();
 
       clearWorkspaceState();
-      setWorkspaceDirectory(selected);
+      try {
+        await setWorkspaceDirectory(selected);
+      } catch (err) {
+        console.error('Failed to open workspace:', err);
+        return;
+      }
       addRecentWorkspace(selected);
       // App.tsx will handle workspace initialization (spawning UCM with new cwd)
     }
@@ -318,13 +321,11 @@ export function WorkspaceProjectLinker() {
 
   // Create a new workspace with setup dialog
   const handleNewWorkspace = useCallback(async () => {
-    const selected = await open({
-      directory: true,
-      multiple: false,
-      title: 'Select Folder for New Workspace',
-    });
+    const selected = await getFileSystemService().pickWorkspaceFolder('Select Folder for New Workspace');
 
-    if (selected && typeof selected === 'string') {
+    if (selected) {
+      // The setup dialog writes the workspace config, so the folder must be open
+      await getFileSystemService().openWorkspace(selected);
       setPendingFolderPath(selected);
       setShowSetupDialog(true);
     }
@@ -339,8 +340,12 @@ export function WorkspaceProjectLinker() {
       await ucmLifecycle.stop();
 
       clearWorkspaceState();
-      setWorkspaceDirectory(pendingFolderPath);
-      addRecentWorkspace(pendingFolderPath);
+      try {
+        await setWorkspaceDirectory(pendingFolderPath);
+        addRecentWorkspace(pendingFolderPath);
+      } catch (err) {
+        console.error('Failed to open workspace:', err);
+      }
       // App.tsx will handle workspace initialization (spawning UCM with new cwd)
     }
     setShowSetupDialog(false);
@@ -348,10 +353,16 @@ export function WorkspaceProjectLinker() {
   }, [pendingFolderPath, clearWorkspaceState, setWorkspaceDirectory, addRecentWorkspace, ucmLifecycle]);
 
   // Handle setup dialog cancellation
-  const handleSetupCancel = useCallback(() => {
+  const handleSetupCancel = useCallback(async () => {
     setShowSetupDialog(false);
     setPendingFolderPath(null);
-  }, []);
+    // Return file operations to the current workspace
+    try {
+      await getFileSystemService().openWorkspace(workspaceDirectory);
+    } catch (err) {
+      console.error('Failed to reopen workspace:', err);
+    }
+  }, [workspaceDirectory]);
 
   const getWorkspaceName = (path: string) => {
     const parts = path.split('/');
@@ -1,5 +1,6 @@
 import { useState } from 'react';
 import { useUnisonStore } from '../store/unisonStore';
+import { getFileSystemService } from '../services/fileSystem';
 
 export function WorkspaceSelector() {
   const { workspaceDirectory, setWorkspaceDirectory } = useUnisonStore();
@@ -8,16 +9,11 @@ export function WorkspaceSelector() {
   async function handleSelectDirectory() {
     setIsSelecting(true);
     try {
-      // Use Tauri dialog API to select directory
-      const { open } = await import('@tauri-apps/plugin-dialog');
-      const selected = await open({
-        directory: true,
-        multiple: false,
-        title: 'Select Workspace Directory',
-      });
+      // The backend shows the dialog, so only picked folders can be opened
+      const selected = await getFileSystemService().pickWorkspaceFolder('Select Workspace Directory');
 
-      if (selected && typeof selected === 'string') {
-        setWorkspaceDirectory(selected);
+      if (selected) {
+        await setWorkspaceDirectory(selected);
       }
     } catch (err) {
       console.error('Failed to select directory:', err);
@@ -26,8 +22,12 @@ export function WorkspaceSelector() {
     }
   }
 
-  function handleClearWorkspace() {
-    setWorkspaceDirectory(null);
+  async function handleClearWorkspace() {
+    try {
+      await setWorkspaceDirectory(null);
+    } catch (err) {
+      console.error('Failed to close workspace:', err);
+    }
   }
 
   return (
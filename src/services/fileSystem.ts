@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { logger } from './loggingService';
 
 export interface FileNode {
@@ -6,22 +7,168 @@ export interface FileNode {
   path: string;
   isDirectory: boolean;
   children?: FileNode[];
+  /** Size in bytes (files only) */
+  size?: number | null;
+  /** Milliseconds since the epoch */
+  modified?: number | null;
+  /** Whether a directory has listed entries, to expand it lazily */
+  hasChildren?: boolean | null;
+}
+
+/** Which dot-entries `listDirectory`/`listDirectoryPage` return */
+export type HiddenPolicy = 'hide' | 'showFiles' | 'show';
+
+export interface ListOptions {
+  hidden?: HiddenPolicy;
+  /** Leave out entries matched by `.gitignore`/`.ignore` files (default true) */
+  respectIgnore?: boolean;
+  offset?: number;
+  limit?: number | null;
+}
+
+/** One page of a directory, see `listDirectoryPage` */
+export interface DirectoryPage {
+  entries: FileNode[];
+  total: number;
+  /** Offset of the next page, null on the last page */
+  nextOffset: number | null;
+}
+
+/** Result of `read_file`: the content and the version to save against */
+export interface FileContent {
+  content: string;
+  version: string;
+}
+
+/** Error of `write_file` */
+export type WriteFileError =
+  | {
+      kind: 'conflict';
+      path: string;
+      expectedVersion: string;
+      /** null if the file was deleted */
+      actualVersion: string | null;
+      diskContent: string | null;
+    }
+  | { kind: 'failed'; message: string };
+
+/** A create, rename or delete of the file explorer, as returned by undo/redo */
+export type FileOperation =
+  | { kind: 'create'; path: string; isDirectory: boolean }
+  | { kind: 'rename'; oldPath: string; newPath: string }
+  | { kind: 'delete'; path: string }
+  | { kind: 'batch'; operations: FileOperation[] };
+
+/** What `copyPath`/`movePaths` do when a target already exists */
+export type ConflictPolicy = 'overwrite' | 'skip' | 'rename';
+
+/** Result of `copyPath`/`movePaths` */
+export interface TransferSummary {
+  /** Where the transferred entries ended up */
+  targets: string[];
+  /** Sources skipped because their target existed */
+  skipped: string[];
+}
+
+let transferCount = 0;
+
+/** A fresh id to tag the progress events of one copy or move */
+export function newTransferId(): string {
+  return `transfer-${++transferCount}`;
+}
+
+/** A `file-transfer-progress` event of the transfer `transferId` */
+export interface TransferProgress {
+  transferId: string;
+  /** Source entry being transferred */
+  current: string;
+  doneEntries: number;
+  totalEntries: number;
+  doneBytes: number;
+  totalBytes: number;
+}
+
+/** A version of a file recorded by `write_file` (see `listHistory`) */
+export interface HistoryEntry {
+  id: number;
+  /** Milliseconds since the epoch */
+  timestamp: number;
+  version: string;
+  size: number;
+  label: 'save' | 'beforeUpdate' | 'restore';
+}
+
+export interface DiffLine {
+  kind: 'equal' | 'added' | 'removed';
+  text: string;
+  oldLine: number | null;
+  newLine: number | null;
+}
+
+/** Thrown by `writeFile` when the file changed on disk since `expectedVersion` */
+export class FileConflictError extends Error {
+  constructor(readonly conflict: Extract<WriteFileError, { kind: 'conflict' }>) {
+    super(`File changed on disk: ${conflict.path}`);
+    this.name = 'FileConflictError';
+  }
 }
 
 /**
  * File system service for interacting with local files via Tauri
- * All operations support an optional workspace parameter for path validation
+ * Operations are confined to the workspace opened with `openWorkspace`; their optional
+ * workspace parameter can only narrow it
  */
 export class FileSystemService {
+  /**
+   * Show the native folder dialog for picking a workspace. Only picked folders (this
+   * launch or an earlier one) can be opened with `openWorkspace`.
+   * @returns The canonical folder, or null if the dialog was cancelled
+   */
+  async pickWorkspaceFolder(title: string): Promise<string | null> {
+    try {
+      return await invoke<string | null>('pick_workspace_folder', { title });
+    } catch (error) {
+      logger.error('file', 'Failed to pick workspace folder', error);
+      throw new Error(`Failed to pick workspace folder: ${error}`);
+    }
+  }
+
+  /**
+   * Confine file operations to a workspace, or close it with null. The backend refuses
+   * file operations while no workspace is open, and workspaces that weren't picked with
+   * `pickWorkspaceFolder`.
+   * @returns The canonical workspace root, or null when closing
+   */
+  async openWorkspace(path: string | null): Promise<string | null> {
+    try {
+      if (path === null) {
+        await invoke('close_workspace');
+        return null;
+      }
+      return await invoke<string>('open_workspace', { path });
+    } catch (error) {
+      logger.error('file', 'Failed to open workspace', error, { path });
+      throw new Error(`Failed to open workspace: ${error}`);
+    }
+  }
+
   /**
    * Read file contents
    * @param path - Absolute path to the file
    * @param workspace - Optional workspace root for path validation
    */
   async readFile(path: string, workspace?: string): Promise<string> {
+    return (await this.readFileWithVersion(path, workspace)).content;
+  }
+
+  /**
+   * Read file contents with the version to pass to `writeFile`
+   * @param path - Absolute path to the file
+   * @param workspace - Optional workspace root for path validation
+   */
+  async readFileWithVersion(path: string, workspace?: string): Promise<FileContent> {
     try {
-      const content = await invoke<string>('read_file', { path, workspace });
-      return content;
+      return await invoke<FileContent>('read_file', { path, workspace });
     } catch (error) {
       logger.error('file', 'Failed to read file', error, { path });
       throw new Error(`Failed to read file: ${error}`);
@@ -29,17 +176,58 @@ export class FileSystemService {
   }
 
   /**
-   * Write content to file
+   * Write content to file (atomically) and return its new version
    * @param path - Absolute path to the file
    * @param content - Content to write
    * @param workspace - Optional workspace root for path validation
+   * @param expectedVersion - Version the content is based on; throws `FileConflictError`
+   *   if the file changed on disk since
    */
-  async writeFile(path: string, content: string, workspace?: string): Promise<void> {
+  async writeFile(path: string, content: string, workspace?: string, expectedVersion?: string): Promise<string> {
     try {
-      await invoke('write_file', { path, content, workspace });
+      return await invoke<string>('write_file', { path, content, expectedVersion, workspace });
     } catch (error) {
+      const writeError = error as WriteFileError;
+      if (writeError?.kind === 'conflict') {
+        logger.warn('file', 'File changed on disk since it was read', { path });
+        throw new FileConflictError(writeError);
+      }
       logger.error('file', 'Failed to write file', error, { path });
-      throw new Error(`Failed to write file: ${error}`);
+      const message = writeError?.kind === 'failed' ? writeError.message : String(error);
+      throw new Error(`Failed to write file: ${message}`);
+    }
+  }
+
+  /**
+   * Versions of a file saved in the workspace history, newest first
+   * @param path - Absolute path to the file
+   * @param workspace - Optional workspace root for path validation
+   */
+  async listHistory(path: string, workspace?: string): Promise<HistoryEntry[]> {
+    return invoke<HistoryEntry[]>('list_file_history', { path, workspace });
+  }
+
+  /**
+   * Line diff between two versions of a file
+   * @param fromId - Older version, or null for the content on disk
+   * @param toId - Newer version, or null for the content on disk
+   */
+  async diffHistory(path: string, fromId: number | null, toId: number | null, workspace?: string): Promise<DiffLine[]> {
+    return invoke<DiffLine[]>('diff_file_history', { path, fromId, toId, workspace });
+  }
+
+  /**
+   * Restore a version of a file; returns the restored content and its new version
+   */
+  async restoreHistory(path: string, id: number, workspace?: string): Promise<FileContent> {
+    const op = logger.startOperation('file', 'Restore file version', { path, id });
+    try {
+      const result = await invoke<FileContent>('restore_file_history', { path, id, workspace });
+      op.complete();
+      return result;
+    } catch (error) {
+      op.fail(error);
+      throw new Error(`Failed to restore file: ${error}`);
     }
   }
 
@@ -48,10 +236,16 @@ export class FileSystemService {
    * @param path - Absolute path to the directory
    * @param recursive - Whether to list recursively
    * @param workspace - Optional workspace root for path validation
+   * @param options - Hidden-file policy and ignore-file handling
    */
-  async listDirectory(path: string, recursive: boolean = false, workspace?: string): Promise<FileNode[]> {
+  async listDirectory(
+    path: string,
+    recursive: boolean = false,
+    workspace?: string,
+    options?: ListOptions
+  ): Promise<FileNode[]> {
     try {
-      const result = await invoke<FileNode[]>('list_directory', { path, recursive, workspace });
+      const result = await invoke<FileNode[]>('list_directory', { path, recursive, workspace, options });
       return result;
     } catch (error) {
       logger.error('file', 'Failed to list directory', error, { path });
@@ -59,6 +253,21 @@ export class FileSystemService {
     }
   }
 
+  /**
+   * List one page of a directory, for directories too large to list at once
+   * @param path - Absolute path to the directory
+   * @param options - Page (`offset`/`limit`), hidden-file policy and ignore-file handling
+   * @param workspace - Optional workspace root for path validation
+   */
+  async listDirectoryPage(path: string, options?: ListOptions, workspace?: string): Promise<DirectoryPage> {
+    try {
+      return await invoke<DirectoryPage>('list_directory_page', { path, options, workspace });
+    } catch (error) {
+      logger.error('file', 'Failed to list directory page', error, { path });
+      throw new Error(`Failed to list directory: ${error}`);
+    }
+  }
+
   /**
    * Create a new file or directory
    * @param path - Absolute path to create
@@ -110,6 +319,126 @@ export class FileSystemService {
     }
   }
 
+  /**
+   * Copy a file or directory
+   * @param source - Path to copy
+   * @param destination - Path of the copy
+   * @param onConflict - What to do if `destination` exists
+   * @param transferId - Tag of the `file-transfer-progress` events (see `onTransferProgress`)
+   * @param workspace - Optional workspace root for path validation
+   */
+  async copyPath(
+    source: string,
+    destination: string,
+    onConflict: ConflictPolicy,
+    transferId: string = newTransferId(),
+    workspace?: string
+  ): Promise<TransferSummary> {
+    const op = logger.startOperation('file', 'Copy path', { source, destination });
+    try {
+      const result = await invoke<TransferSummary>('copy_path', {
+        source,
+        destination,
+        onConflict,
+        transferId,
+        workspace,
+      });
+      op.complete();
+      return result;
+    } catch (error) {
+      op.fail(error);
+      throw new Error(`Failed to copy: ${error}`);
+    }
+  }
+
+  /**
+   * Copy a file or directory next to itself as `name copy.ext`
+   * @returns The path of the copy
+   */
+  async duplicatePath(path: string, transferId: string = newTransferId(), workspace?: string): Promise<string> {
+    const op = logger.startOperation('file', 'Duplicate path', { path });
+    try {
+      const result = await invoke<string>('duplicate_path', { path, transferId, workspace });
+      op.complete();
+      return result;
+    } catch (error) {
+      op.fail(error);
+      throw new Error(`Failed to duplicate: ${error}`);
+    }
+  }
+
+  /**
+   * Move files and directories into a directory, undoable as one operation
+   * @param paths - Paths to move
+   * @param destination - Directory to move them into
+   * @param onConflict - What to do with entries whose name exists in `destination`
+   * @param transferId - Tag of the `file-transfer-progress` events (see `onTransferProgress`)
+   * @param workspace - Optional workspace root for path validation
+   */
+  async movePaths(
+    paths: string[],
+    destination: string,
+    onConflict: ConflictPolicy,
+    transferId: string = newTransferId(),
+    workspace?: string
+  ): Promise<TransferSummary> {
+    const op = logger.startOperation('file', 'Move paths', { count: paths.length, destination });
+    try {
+      const result = await invoke<TransferSummary>('move_paths', {
+        paths,
+        destination,
+        onConflict,
+        transferId,
+        workspace,
+      });
+      op.complete();
+      return result;
+    } catch (error) {
+      op.fail(error);
+      throw new Error(`Failed to move: ${error}`);
+    }
+  }
+
+  /**
+   * Subscribe to the progress of copies and moves
+   * @returns A function to unsubscribe
+   */
+  async onTransferProgress(callback: (progress: TransferProgress) => void): Promise<UnlistenFn> {
+    return listen<TransferProgress>('file-transfer-progress', (event) => callback(event.payload));
+  }
+
+  /**
+   * Undo the last create, rename or delete (deletes are restored from the trash)
+   * @returns The undone operation, or null if there is nothing to undo
+   */
+  async undoFileOperation(workspace?: string): Promise<FileOperation | null> {
+    const op = logger.startOperation('file', 'Undo file operation');
+    try {
+      const result = await invoke<FileOperation | null>('undo_file_operation', { workspace });
+      op.complete();
+      return result;
+    } catch (error) {
+      op.fail(error);
+      throw new Error(`Failed to undo: ${error}`);
+    }
+  }
+
+  /**
+   * Redo the last undone file operation
+   * @returns The redone operation, or null if there is nothing to redo
+   */
+  async redoFileOperation(workspace?: string): Promise<FileOperation | null> {
+    const op = logger.startOperation('file', 'Redo file operation');
+    try {
+      const result = await invoke<FileOperation | null>('redo_file_operation', { workspace });
+      op.complete();
+      return result;
+    } catch (error) {
+      op.fail(error);
+      throw new Error(`Failed to redo: ${error}`);
+    }
+  }
+
   /**
    * Check if a file or directory exists
    * @param path - Absolute path to check
@@ -7,10 +7,50 @@ export interface FileChangeEvent {
   changeType: 'modified' | 'deleted';
   /** Timestamp when the change was detected in the backend (milliseconds since epoch) */
   detectedAt: number;
+  /** Content hash of the file now (null if deleted). The editor's own saves are never reported. */
+  version: string | null;
 }
 
 export type FileChangeCallback = (event: FileChangeEvent) => void;
 
+/** Backend and cost of file watching (see `configure_file_watcher`) */
+export interface WatcherConfig {
+  /** `auto` uses native events, polling network filesystems and when native watching fails */
+  backend: 'auto' | 'native' | 'poll';
+  /** How often polled files are checked */
+  pollIntervalMs: number;
+  /** Polling compares contents, not just modification times (reads every file each interval) */
+  compareContents: boolean;
+}
+
+export const DEFAULT_WATCHER_CONFIG: WatcherConfig = {
+  backend: 'auto',
+  pollIntervalMs: 500,
+  compareContents: false,
+};
+
+const WATCHER_CONFIG_STORAGE_KEY = 'fileWatcherConfig';
+
+/** One change of the workspace tree */
+export interface WorkspaceChange {
+  kind: 'created' | 'modified' | 'deleted' | 'renamed';
+  path: string;
+  /** Previous path of a renamed entry */
+  from: string | null;
+  isDirectory: boolean;
+}
+
+/** Debounced batch of workspace tree changes (`workspace-changed` event) */
+export interface WorkspaceChangeBatch {
+  root: string;
+  changes: WorkspaceChange[];
+  /** Events were lost - reload the whole tree */
+  rescan: boolean;
+  detectedAt: number;
+}
+
+export type WorkspaceChangeCallback = (batch: WorkspaceChangeBatch) => void;
+
 /**
  * Service for watching files for external changes.
  * Uses the Rust file_watcher module via Tauri to detect when
@@ -21,6 +61,9 @@ class FileWatcherService {
   private initializing = false;
   private listeners: Set<FileChangeCallback> = new Set();
   private unlistenChange: UnlistenFn | null = null;
+  private unlistenWorkspace: UnlistenFn | null = null;
+  private workspaceListeners: Set<WorkspaceChangeCallback> = new Set();
+  private watchedWorkspace: string | null = null;
   private watchedPaths: Set<string> = new Set();
   // Track last processed event to deduplicate
   private lastProcessedEvent: { path: string; detectedAt: number } | null = null;
@@ -43,6 +86,7 @@ class FileWatcherService {
     try {
       // Initialize the Rust file watcher
       await invoke('init_file_watcher');
+      await invoke('configure_file_watcher', { config: this.getConfig() });
 
       // Listen for file change events from the backend
       this.unlistenChange = await listen<FileChangeEvent>('file-changed', (event) => {
@@ -73,6 +117,18 @@ class FileWatcherService {
         this.notifyListeners(event.payload);
       });
 
+      // Listen for workspace tree changes (see watchWorkspace)
+      this.unlistenWorkspace = await listen<WorkspaceChangeBatch>('workspace-changed', (event) => {
+        logger.debug('file', `[FileWatcher] Workspace changed: ${event.payload.changes.length} changes`);
+        this.workspaceListeners.forEach((callback) => {
+          try {
+            callback(event.payload);
+          } catch (error) {
+            logger.error('file', 'Error in workspace change callback', error);
+          }
+        });
+      });
+
       this.initialized = true;
       this.initializing = false;
       logger.info('file', 'File watcher service initialized');
@@ -83,6 +139,30 @@ class FileWatcherService {
     }
   }
 
+  /**
+   * The configured watcher backend and polling cost.
+   */
+  getConfig(): WatcherConfig {
+    try {
+      const stored = localStorage.getItem(WATCHER_CONFIG_STORAGE_KEY);
+      return stored ? { ...DEFAULT_WATCHER_CONFIG, ...JSON.parse(stored) } : DEFAULT_WATCHER_CONFIG;
+    } catch {
+      return DEFAULT_WATCHER_CONFIG;
+    }
+  }
+
+  /**
+   * Change the watcher backend and polling cost. Applies to watched files and the
+   * workspace immediately, and to later launches.
+   */
+  async configure(config: WatcherConfig): Promise<void> {
+    localStorage.setItem(WATCHER_CONFIG_STORAGE_KEY, JSON.stringify(config));
+    if (this.initialized) {
+      await invoke('configure_file_watcher', { config });
+    }
+    logger.info('file', 'File watcher configured', { ...config });
+  }
+
   /**
    * Start watching a file for external changes.
    * @param path - Absolute path to the file to watch
@@ -125,6 +205,56 @@ class FileWatcherService {
     }
   }
 
+  /**
+   * Watch the whole workspace for created, renamed, modified and deleted entries.
+   * Replaces the previous workspace watch.
+   * @param root - Absolute path to the workspace root
+   */
+  async watchWorkspace(root: string): Promise<void> {
+    if (!this.initialized) {
+      await this.initialize();
+    }
+    if (this.watchedWorkspace === root) {
+      return;
+    }
+
+    try {
+      await invoke('watch_workspace', { path: root });
+      this.watchedWorkspace = root;
+      logger.debug('file', 'Started watching workspace', { root });
+    } catch (error) {
+      logger.error('file', 'Failed to watch workspace', error, { root });
+      throw error;
+    }
+  }
+
+  /**
+   * Stop watching the workspace tree.
+   */
+  async unwatchWorkspace(): Promise<void> {
+    if (!this.watchedWorkspace) {
+      return;
+    }
+    try {
+      await invoke('unwatch_workspace');
+    } catch (error) {
+      logger.warn('file', 'Failed to unwatch workspace', { error });
+    } finally {
+      this.watchedWorkspace = null;
+    }
+  }
+
+  /**
+   * Register a callback for batches of workspace tree changes.
+   * @returns Unsubscribe function
+   */
+  onWorkspaceChange(callback: WorkspaceChangeCallback): () => void {
+    this.workspaceListeners.add(callback);
+    return () => {
+      this.workspaceListeners.delete(callback);
+    };
+  }
+
   /**
    * Register a callback to be notified when any watched file changes.
    * @param callback - Function to call when a file changes
@@ -193,6 +323,11 @@ class FileWatcherService {
       this.unlistenChange();
       this.unlistenChange = null;
     }
+    if (this.unlistenWorkspace) {
+      this.unlistenWorkspace();
+      this.unlistenWorkspace = null;
+    }
+    await this.unwatchWorkspace();
 
     // Unwatch all files
     for (const path of this.watchedPaths) {
@@ -205,6 +340,7 @@ class FileWatcherService {
 
     this.watchedPaths.clear();
     this.listeners.clear();
+    this.workspaceListeners.clear();
     this.initialized = false;
     logger.info('file', 'File watcher service disposed');
   }
@@ -42,6 +42,7 @@ export class MonacoLspClient {
   private baseReconnectDelay = 1000; // 1 second
   private reconnectTimeoutId: ReturnType<typeof setTimeout> | null = null;
   private wsPort: number = 5758;
+  private wsToken: string = '';
 
   // Bound event handlers for proper cleanup
   private boundOnOpen: (() => void) | null = null;
@@ -52,16 +53,18 @@ export class MonacoLspClient {
   /**
    * Connect to the LSP server via WebSocket proxy
    */
-  async connect(wsPort: number = 5758): Promise<void> {
+  async connect(wsPort: number = 5758, token: string = ''): Promise<void> {
     // Clean up existing connection first
     this.cleanup();
     this.wsPort = wsPort;
+    this.wsToken = token;
 
     try {
       const wsUrl = `ws://127.0.0.1:${wsPort}`;
       console.log(`Connecting to LSP WebSocket proxy at ${wsUrl}...`);
 
-      this.ws = new WebSocket(wsUrl);
+      // The proxy rejects connections without the per-launch token
+      this.ws = new WebSocket(`${wsUrl}/?token=${encodeURIComponent(token)}`);
 
       await new Promise<void>((resolve, reject) => {
         if (!this.ws) {
@@ -134,7 +137,7 @@ export class MonacoLspClient {
 
     this.reconnectTimeoutId = setTimeout(async () => {
       try {
-        await this.connect(this.wsPort);
+        await this.connect(this.wsPort, this.wsToken);
       } catch {
         // Error already logged in connect()
       }
@@ -0,0 +1,105 @@
+/**
+ * Shell Terminal Service
+ *
+ * Connects a terminal to a shell PTY session on the backend (`pty_*` commands).
+ * Sessions outlive the terminal component: reconnecting replays the session's
+ * scrollback, then streams `pty-output` events from where the replay ended.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { logger } from './loggingService';
+import type { SessionEvent, UCMAttachSnapshot, UCMExitStatus } from './ucmLifecycle';
+
+/** Result of `pty_spawn` / `pty_list` */
+export interface PtySessionInfo {
+  sessionId: string;
+  command: string;
+  pid: number | null;
+  running: boolean;
+}
+
+export interface ShellTerminalCallbacks {
+  onData: (data: Uint8Array) => void;
+  onExit?: (status: UCMExitStatus) => void;
+}
+
+export class ShellTerminalSession {
+  private unlisteners: UnlistenFn[] = [];
+
+  constructor(readonly sessionId: string) {}
+
+  /**
+   * Spawn the session's shell in `cwd` (or reuse the running one) and stream its output.
+   * Returns the session info; call `disconnect()` to stop streaming.
+   */
+  async connect(
+    cwd: string | null,
+    rows: number,
+    cols: number,
+    callbacks: ShellTerminalCallbacks
+  ): Promise<PtySessionInfo> {
+    this.disconnect();
+
+    // Queue output until the scrollback has been replayed, then skip what it contained
+    let pending: Array<{ data: Uint8Array; offset: number }> | null = [];
+    this.unlisteners.push(
+      await listen<SessionEvent<{ data: number[]; offset: number }>>('pty-output', (event) => {
+        if (event.payload.sessionId !== this.sessionId) return;
+        const data = new Uint8Array(event.payload.data);
+        if (pending) {
+          pending.push({ data, offset: event.payload.offset });
+        } else {
+          callbacks.onData(data);
+        }
+      })
+    );
+    this.unlisteners.push(
+      await listen<SessionEvent<UCMExitStatus>>('pty-exited', (event) => {
+        if (event.payload.sessionId !== this.sessionId) return;
+        callbacks.onExit?.(event.payload);
+      })
+    );
+
+    const info = await invoke<PtySessionInfo>('pty_spawn', {
+      sessionId: this.sessionId,
+      cwd,
+      rows,
+      cols,
+    });
+    logger.info('system', 'Shell terminal connected', { ...info });
+
+    const snapshot = await invoke<UCMAttachSnapshot>('pty_attach', { sessionId: this.sessionId });
+    if (snapshot.data.length > 0) {
+      callbacks.onData(new Uint8Array(snapshot.data));
+    }
+    for (const chunk of pending) {
+      const skip = snapshot.endOffset - chunk.offset;
+      if (skip < chunk.data.length) {
+        callbacks.onData(skip > 0 ? chunk.data.subarray(skip) : chunk.data);
+      }
+    }
+    pending = null;
+
+    return info;
+  }
+
+  /** Stop streaming output (the shell keeps running) */
+  disconnect() {
+    this.unlisteners.forEach((unlisten) => unlisten());
+    this.unlisteners = [];
+  }
+
+  write(data: string): Promise<void> {
+    return invoke('pty_write', { sessionId: this.sessionId, data });
+  }
+
+  resize(rows: number, cols: number): Promise<void> {
+    return invoke('pty_resize', { sessionId: this.sessionId, rows, cols });
+  }
+
+  /** Hang up the shell and forget the session */
+  kill(): Promise<void> {
+    return invoke('pty_kill', { sessionId: this.sessionId });
+  }
+}
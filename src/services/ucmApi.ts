@@ -43,6 +43,24 @@ export interface RunTestsResult {
   testResults: TestResult[];
 }
 
+/** Outcome of one transcript stanza (fenced block) */
+export interface TranscriptBlockResult {
+  index: number;
+  kind: string;
+  modifiers: string[];
+  startLine: number;
+  endLine: number;
+  status: 'passed' | 'failed' | 'skipped';
+  message: string | null;
+}
+
+/** Result of `run_transcript`, shaped like RunTestsResult */
+export interface TranscriptResult extends RunTestsResult {
+  outputFile: string | null;
+  blocks: TranscriptBlockResult[];
+  durationMs: number;
+}
+
 export interface RunFunctionResult {
   success: boolean;
   stdout: string;
@@ -294,6 +312,28 @@ export class UCMApiClient {
     }
   }
 
+  /**
+   * Run a Unison transcript (.md)
+   *
+   * Runs `ucm transcript` on a fresh codebase, or with `fork` `ucm transcript.fork`
+   * on a copy of the current codebase. Output is streamed as `transcript-output` events.
+   */
+  async runTranscript(path: string, fork = false): Promise<TranscriptResult> {
+    const op = logger.startOperation('run', 'Run transcript', { path, fork });
+    try {
+      const result = await invoke<TranscriptResult>('run_transcript', { path, fork });
+      op.complete({
+        success: result.success,
+        blockCount: result.blocks.length,
+        passed: result.blocks.filter(b => b.status === 'passed').length,
+      });
+      return result;
+    } catch (err) {
+      op.fail(err);
+      throw err;
+    }
+  }
+
   /**
    * Run an IO function
    *
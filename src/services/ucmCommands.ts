@@ -3,10 +3,11 @@
  *
  * Provides high-level functions for executing UCM commands via PTY.
  * These commands modify the Unison codebase (move, rename, delete).
- * Commands are sent to the UCM terminal so users can see output.
+ * Commands are sent to the UCM terminal so users can see output, and resolve
+ * once UCM is back at its prompt (rejecting with UCM's output if they failed).
  */
 
-import { getUCMLifecycleService } from './ucmLifecycle';
+import { getUCMLifecycleService, type UCMExecResult } from './ucmLifecycle';
 import { emit } from '@tauri-apps/api/event';
 import { logger } from './loggingService';
 
@@ -18,18 +19,24 @@ export async function showTerminal(): Promise<void> {
 }
 
 /**
- * Write a command to UCM PTY
+ * Run a command in the UCM PTY and wait for it to finish.
+ * Throws with UCM's output if the command failed.
  */
-async function writeCommand(command: string): Promise<void> {
+async function runCommand(command: string): Promise<UCMExecResult> {
   const ucm = getUCMLifecycleService();
 
   if (!ucm.isRunning()) {
     throw new Error('UCM is not running');
   }
 
-  // Ensure command ends with newline
-  const cmd = command.endsWith('\n') ? command : command + '\n';
-  await ucm.write(cmd);
+  const result = await ucm.exec(command.trim());
+  if (result.status === 'failed') {
+    logger.warn('ucm', 'UCM command failed', { command, output: result.output });
+    throw new Error(result.output || `UCM command failed: ${command}`);
+  }
+
+  logger.debug('ucm', 'UCM command completed', { command, durationMs: result.durationMs });
+  return result;
 }
 
 /**
@@ -41,7 +48,7 @@ async function writeCommand(command: string): Promise<void> {
 export async function moveTerm(oldFQN: string, newFQN: string): Promise<void> {
   logger.info('ucm', 'Moving term', { oldFQN, newFQN });
   await showTerminal();
-  await writeCommand(`move.term ${oldFQN} ${newFQN}`);
+  await runCommand(`move.term ${oldFQN} ${newFQN}`);
 }
 
 /**
@@ -53,7 +60,7 @@ export async function moveTerm(oldFQN: string, newFQN: string): Promise<void> {
 export async function moveType(oldFQN: string, newFQN: string): Promise<void> {
   logger.info('ucm', 'Moving type', { oldFQN, newFQN });
   await showTerminal();
-  await writeCommand(`move.type ${oldFQN} ${newFQN}`);
+  await runCommand(`move.type ${oldFQN} ${newFQN}`);
 }
 
 /**
@@ -65,7 +72,7 @@ export async function moveType(oldFQN: string, newFQN: string): Promise<void> {
 export async function moveNamespace(oldPath: string, newPath: string): Promise<void> {
   logger.info('ucm', 'Moving namespace', { oldPath, newPath });
   await showTerminal();
-  await writeCommand(`move.namespace ${oldPath} ${newPath}`);
+  await runCommand(`move.namespace ${oldPath} ${newPath}`);
 }
 
 /**
@@ -77,7 +84,7 @@ export async function deleteTerm(fqn: string): Promise<void> {
   logger.info('ucm', 'Deleting term', { fqn });
   await showTerminal();
   // Use delete.term.force to skip confirmation prompts
-  await writeCommand(`delete.term.force ${fqn}`);
+  await runCommand(`delete.term.force ${fqn}`);
 }
 
 /**
@@ -89,7 +96,7 @@ export async function deleteType(fqn: string): Promise<void> {
   logger.info('ucm', 'Deleting type', { fqn });
   await showTerminal();
   // Use delete.type.force to skip confirmation prompts
-  await writeCommand(`delete.type.force ${fqn}`);
+  await runCommand(`delete.type.force ${fqn}`);
 }
 
 /**
@@ -101,7 +108,7 @@ export async function deleteNamespace(path: string): Promise<void> {
   logger.info('ucm', 'Deleting namespace', { path });
   await showTerminal();
   // Use delete.namespace.force to skip confirmation prompts
-  await writeCommand(`delete.namespace.force ${path}`);
+  await runCommand(`delete.namespace.force ${path}`);
 }
 
 /**
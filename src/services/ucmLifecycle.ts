@@ -9,20 +9,137 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { logger } from './loggingService';
+import { getWorkspaceConfigService, type CodebaseConfig } from './workspaceConfigService';
 
 export type UCMStatus = 'idle' | 'spawning' | 'running' | 'error' | 'stopped';
 
+/** Session used when a UCM command doesn't name one */
+export const DEFAULT_UCM_SESSION_ID = 'default';
+
+/** UCM event payloads are tagged with the session they belong to */
+export type SessionEvent<T> = T & { sessionId: string };
+
 export interface ServicePorts {
   apiPort: number;
   lspPort: number;
   lspProxyPort: number;
+  /** Per-launch secret required by the LSP WebSocket proxy */
+  lspProxyToken: string;
+}
+
+/** Payload of the `ucm-process-exited` event */
+export type UCMExitStatus = {
+  exitCode: number | null;
+  success: boolean;
+  /** How the editor stopped UCM ("exit", "SIGTERM", "SIGKILL"), null if it exited on its own */
+  terminatedBy: string | null;
+};
+
+/** Result of `ucm_pty_attach`: buffered output plus the current PTY size */
+export interface UCMAttachSnapshot {
+  data: number[];
+  startOffset: number;
+  /** Output events with a lower offset are already part of `data` */
+  endOffset: number;
+  /** Older output was dropped from the backend scrollback */
+  truncated: boolean;
+  rows: number;
+  cols: number;
+}
+
+/** Project, branch and namespace path shown in UCM's prompt */
+export interface UCMPromptContext {
+  project: string | null;
+  branch: string | null;
+  path: string | null;
+}
+
+/** Payload of the `ucm-prompt-state` event: what UCM is waiting for */
+export type UCMPromptState =
+  | { state: 'busy' }
+  | { state: 'idle'; context: UCMPromptContext }
+  | { state: 'confirmation'; question: string }
+  | { state: 'choice'; question: string; options: Array<{ number: number; label: string }> };
+
+/** Result of `ucm_pty_exec` */
+export interface UCMExecResult {
+  command: string;
+  /** Output between the echoed command and the next prompt, without ANSI escapes */
+  output: string;
+  /** Read from the glyph opening UCM's first message block; 'unknown' without one */
+  status: 'succeeded' | 'failed' | 'unknown';
+  context: UCMPromptContext | null;
+  durationMs: number;
+}
+
+/** Result of `get_ucm_info`: which `ucm` binary would be used and its version */
+export interface UCMInfo {
+  found: boolean;
+  path: string | null;
+  source: 'settings' | 'environment' | 'searchPath' | null;
+  /** Raw `ucm version` output */
+  versionOutput: string | null;
+  version: string | null;
+  support: 'supported' | 'tooOld' | 'tooNew' | 'unknown' | null;
+  minVersion: string;
+  maxVersion: string;
+  searchedLocations: string[];
+  error: string | null;
+}
+
+/** The process holding the codebase lock (`ucm-file-lock-error`, `get_codebase_lock_holder`) */
+export interface LockHolder {
+  pid: number;
+  parentPid: number | null;
+  commandLine: string;
+  /** HTTP API port from the holder's `--port` argument */
+  apiPort: number | null;
+  /** Spawned by an editor that is no longer running - safe to kill */
+  orphaned: boolean;
+  source: 'lockFile' | 'processScan';
+  lockFile: string | null;
+}
+
+/** How a session reaches its codebase */
+export type SessionMode =
+  | { kind: 'full' }
+  /** Using the lock holder's HTTP API; nothing is written to the codebase */
+  | { kind: 'readOnly'; holderPid: number; apiPort: number }
+  /** No UCM: file editing only */
+  | { kind: 'degraded' };
+
+const UCM_PATH_STORAGE_KEY = 'ucmPath';
+
+/** `ucm` binary configured in the settings, or null to auto-detect */
+export function getConfiguredUCMPath(): string | null {
+  return localStorage.getItem(UCM_PATH_STORAGE_KEY) || null;
+}
+
+/** Configure the `ucm` binary (null restores auto-detection). Applies to the next spawn. */
+export async function setConfiguredUCMPath(path: string | null): Promise<void> {
+  await invoke('set_ucm_path', { path });
+  if (path) {
+    localStorage.setItem(UCM_PATH_STORAGE_KEY, path);
+  } else {
+    localStorage.removeItem(UCM_PATH_STORAGE_KEY);
+  }
 }
 
+/** Locate UCM and check its version. `ucmPath` checks a candidate without configuring it. */
+export function getUCMInfo(ucmPath?: string): Promise<UCMInfo> {
+  return invoke<UCMInfo>('get_ucm_info', { ucmPath: ucmPath ?? null });
+}
+
+type OutputCallback = (data: Uint8Array, offset: number) => void;
+
 export interface UCMLifecycleState {
   status: UCMStatus;
   error: string | null;
   workspaceDirectory: string | null;
   ports: ServicePorts | null;
+  mode: SessionMode;
+  /** Holder of the codebase lock from the last file lock error */
+  lockHolder: LockHolder | null;
 }
 
 type StatusChangeCallback = (status: UCMStatus, error?: string) => void;
@@ -33,12 +150,17 @@ class UCMLifecycleService {
     error: null,
     workspaceDirectory: null,
     ports: null,
+    mode: { kind: 'full' },
+    lockHolder: null,
   };
 
   private listeners: Set<StatusChangeCallback> = new Set();
-  private outputListeners: Set<(data: Uint8Array) => void> = new Set();
+  private outputListeners: Set<OutputCallback> = new Set();
+  private promptStateListeners: Set<(state: UCMPromptState) => void> = new Set();
+  private promptState: UCMPromptState = { state: 'busy' };
   private unlistenOutput: UnlistenFn | null = null;
   private initialized: boolean = false;
+  private sessionId: string = DEFAULT_UCM_SESSION_ID;
 
   /**
    * Initialize listeners that need to be set up early
@@ -51,15 +173,34 @@ class UCMLifecycleService {
     // This event can be emitted very quickly after spawn, so we need to listen before spawn
     // Note: We don't store the unlisten function because this listener lives for the
     // lifetime of the singleton service
-    await listen('ucm-file-lock-error', () => {
-      logger.warn('ucm', 'UCM file lock error - another process is using this codebase');
+    await listen<SessionEvent<{ message: string; holder: LockHolder | null }>>('ucm-file-lock-error', (event) => {
+      if (event.payload.sessionId !== this.sessionId) return;
+      logger.warn('ucm', 'UCM file lock error - another process is using this codebase', {
+        holder: event.payload.holder,
+      });
+      this.state.lockHolder = event.payload.holder;
       this.notifyStatusChange('error', 'Another UCM process is using this codebase');
       this.state.ports = null;
     });
 
-    // Listen for UCM process exit (user typed 'exit' or process crashed)
-    await listen('ucm-process-exited', () => {
-      logger.info('ucm', 'UCM process exited');
+    // UCM's LSP server could not use the port it was given (taken by another process)
+    await listen<SessionEvent<{ port: number; message: string }>>('ucm-lsp-port-error', (event) => {
+      if (event.payload.sessionId !== this.sessionId) return;
+      logger.error('ucm', event.payload.message);
+    });
+
+    // Track whether UCM is idle, busy or asking a question (confirmation, numbered choice)
+    await listen<SessionEvent<UCMPromptState>>('ucm-prompt-state', (event) => {
+      if (event.payload.sessionId !== this.sessionId) return;
+      const { sessionId: _sessionId, ...promptState } = event.payload;
+      this.promptState = promptState as UCMPromptState;
+      this.promptStateListeners.forEach((cb) => cb(this.promptState));
+    });
+
+    // Listen for UCM process exit (user typed 'exit', process crashed or was killed)
+    await listen<SessionEvent<UCMExitStatus>>('ucm-process-exited', (event) => {
+      if (event.payload.sessionId !== this.sessionId) return;
+      logger.info('ucm', 'UCM process exited', event.payload);
       this.notifyStatusChange('stopped');
       this.state.ports = null;
       // Clean up output listener since process is gone
@@ -108,22 +249,84 @@ class UCMLifecycleService {
     return () => this.listeners.delete(callback);
   }
 
+  /**
+   * Get UCM's last known prompt state
+   */
+  getPromptState(): UCMPromptState {
+    return this.promptState;
+  }
+
+  /**
+   * Subscribe to prompt state changes (idle, busy, confirmation, choice)
+   */
+  onPromptStateChange(callback: (state: UCMPromptState) => void): () => void {
+    this.promptStateListeners.add(callback);
+    return () => this.promptStateListeners.delete(callback);
+  }
+
   /**
    * Subscribe to PTY output
    */
-  onOutput(callback: (data: Uint8Array) => void): () => void {
+  onOutput(callback: OutputCallback): () => void {
     this.outputListeners.add(callback);
     return () => this.outputListeners.delete(callback);
   }
 
+  /**
+   * Subscribe to PTY output, first replaying the scrollback the backend has buffered.
+   * Lets a remounted terminal (or a reloaded webview) pick up a running UCM mid-session.
+   * Output arriving while the snapshot is fetched is queued and de-duplicated by offset.
+   */
+  attachOutput(
+    callback: (data: Uint8Array) => void,
+    onAttach?: (snapshot: UCMAttachSnapshot) => void
+  ): () => void {
+    let pending: Array<{ data: Uint8Array; offset: number }> | null = [];
+    const listener: OutputCallback = (data, offset) => {
+      if (pending) {
+        pending.push({ data, offset });
+      } else {
+        callback(data);
+      }
+    };
+    this.outputListeners.add(listener);
+
+    const flush = (endOffset: number) => {
+      for (const chunk of pending ?? []) {
+        // Skip bytes the snapshot already contained
+        const skip = endOffset - chunk.offset;
+        if (skip < chunk.data.length) {
+          callback(skip > 0 ? chunk.data.subarray(skip) : chunk.data);
+        }
+      }
+      pending = null;
+    };
+
+    invoke<UCMAttachSnapshot>('ucm_pty_attach', { sessionId: this.sessionId })
+      .then((snapshot) => {
+        onAttach?.(snapshot);
+        if (snapshot.data.length > 0) {
+          callback(new Uint8Array(snapshot.data));
+        }
+        flush(snapshot.endOffset);
+      })
+      .catch((err) => {
+        // UCM not spawned yet - nothing to replay
+        logger.debug('ucm', 'No UCM scrollback to attach to', { error: String(err) });
+        flush(0);
+      });
+
+    return () => this.outputListeners.delete(listener);
+  }
+
   private notifyStatusChange(status: UCMStatus, error?: string) {
     this.state.status = status;
     this.state.error = error || null;
     this.listeners.forEach((cb) => cb(status, error));
   }
 
-  private notifyOutput(data: Uint8Array) {
-    this.outputListeners.forEach((cb) => cb(data));
+  private notifyOutput(data: Uint8Array, offset: number) {
+    this.outputListeners.forEach((cb) => cb(data, offset));
   }
 
   /**
@@ -165,15 +368,33 @@ class UCMLifecycleService {
     try {
       // Set up output listener before spawning
       if (!this.unlistenOutput) {
-        this.unlistenOutput = await listen<number[]>('ucm-pty-output', (event) => {
-          const data = new Uint8Array(event.payload);
-          this.notifyOutput(data);
-        });
+        this.unlistenOutput = await listen<SessionEvent<{ data: number[]; offset: number }>>(
+          'ucm-pty-output',
+          (event) => {
+            if (event.payload.sessionId !== this.sessionId) return;
+            const data = new Uint8Array(event.payload.data);
+            this.notifyOutput(data, event.payload.offset);
+          }
+        );
       }
 
+      // Apply the configured UCM binary (fails if it no longer exists)
+      await invoke('set_ucm_path', { path: getConfiguredUCMPath() });
+
+      // Open the workspace's codebase (PTY, HTTP API and MCP all share it)
+      const configService = getWorkspaceConfigService();
+      const config = (await configService.hasConfig(workspaceDirectory))
+        ? await configService.loadConfig(workspaceDirectory)
+        : null;
+
       // Spawn UCM PTY - returns the allocated ports
-      const ports = await invoke<ServicePorts>('ucm_pty_spawn', { cwd: workspaceDirectory });
+      const ports = await invoke<ServicePorts>('ucm_pty_spawn', {
+        cwd: workspaceDirectory,
+        sessionId: this.sessionId,
+        codebase: config?.codebase ?? null,
+      });
       this.state.ports = ports;
+      this.state.mode = { kind: 'full' };
 
       spawnOp.complete({ ports });
       this.notifyStatusChange('running');
@@ -196,12 +417,28 @@ class UCMLifecycleService {
     }
 
     try {
-      await invoke('ucm_pty_write', { data });
+      await invoke('ucm_pty_write', { data, sessionId: this.sessionId });
     } catch (err) {
       logger.error('ucm', 'Failed to write to UCM PTY', err);
     }
   }
 
+  /**
+   * Run a UCM command in the PTY and wait for UCM to return to its prompt.
+   * The command is visible in the terminal; typing in the terminal is queued behind it.
+   */
+  async exec(command: string, timeoutMs?: number): Promise<UCMExecResult> {
+    if (this.state.status !== 'running') {
+      throw new Error('UCM is not running');
+    }
+
+    return invoke<UCMExecResult>('ucm_pty_exec', {
+      command,
+      timeoutMs,
+      sessionId: this.sessionId,
+    });
+  }
+
   /**
    * Resize the UCM PTY
    */
@@ -211,7 +448,7 @@ class UCMLifecycleService {
     }
 
     try {
-      await invoke('ucm_pty_resize', { rows, cols });
+      await invoke('ucm_pty_resize', { rows, cols, sessionId: this.sessionId });
     } catch (err) {
       logger.error('ucm', 'Failed to resize UCM PTY', err);
     }
@@ -250,6 +487,62 @@ class UCMLifecycleService {
     return this.spawn(workspaceDir);
   }
 
+  /**
+   * Switch the workspace to another codebase (null = UCM's default codebase).
+   * The choice is saved in the workspace config and UCM is restarted on it.
+   */
+  async switchCodebase(codebase: CodebaseConfig | null): Promise<boolean> {
+    const workspaceDirectory = this.state.workspaceDirectory;
+    if (!workspaceDirectory) {
+      throw new Error('No workspace is open');
+    }
+
+    await getWorkspaceConfigService().updateCodebase(workspaceDirectory, codebase);
+    logger.info('ucm', 'Switching codebase', { workspaceDirectory, codebase });
+
+    await this.stop();
+    return this.spawn(workspaceDirectory);
+  }
+
+  getLockHolder(): LockHolder | null {
+    return this.state.lockHolder;
+  }
+
+  getSessionMode(): SessionMode {
+    return this.state.mode;
+  }
+
+  /** Look up the current holder of the codebase lock again */
+  async refreshLockHolder(): Promise<LockHolder | null> {
+    this.state.lockHolder = await invoke<LockHolder | null>('get_codebase_lock_holder', {
+      sessionId: this.sessionId,
+    });
+    return this.state.lockHolder;
+  }
+
+  /** Kill the lock holder (only allowed for a UCM orphaned by a previous editor session) */
+  async killLockHolder(pid: number): Promise<void> {
+    await invoke('kill_codebase_lock_holder', { pid, sessionId: this.sessionId });
+    logger.info('ucm', 'Killed orphaned UCM holding the codebase lock', { pid });
+    this.state.lockHolder = null;
+  }
+
+  /** Use the lock holder's HTTP API read-only instead of our own UCM */
+  async connectToLockHolder(workspaceDir: string): Promise<SessionMode> {
+    this.state.mode = await invoke<SessionMode>('connect_to_lock_holder', { sessionId: this.sessionId });
+    this.state.workspaceDirectory = workspaceDir;
+    logger.info('ucm', 'Connected read-only to the UCM holding the codebase lock', { ...this.state.mode });
+    return this.state.mode;
+  }
+
+  /** Open the workspace without UCM (file editing only) */
+  async openDegraded(workspaceDir: string): Promise<SessionMode> {
+    this.state.mode = await invoke<SessionMode>('open_degraded_session', { sessionId: this.sessionId });
+    this.state.workspaceDirectory = workspaceDir;
+    logger.warn('ucm', 'Opened workspace without UCM (codebase locked)');
+    return this.state.mode;
+  }
+
   /**
    * Stop the UCM PTY
    */
@@ -270,7 +563,7 @@ class UCMLifecycleService {
       // and is set up once during initialization
 
       // Kill the UCM PTY process on the backend
-      await invoke('ucm_pty_kill');
+      await invoke('ucm_pty_kill', { sessionId: this.sessionId });
 
       this.notifyStatusChange('stopped');
       this.state.workspaceDirectory = null;
@@ -5,6 +5,16 @@ const CONFIG_DIR = '.unison-editor';
 const CONFIG_FILE = 'config.json';
 const EDITOR_STATE_FILE = 'editor-state.json';
 
+/**
+ * Codebase opened by UCM instead of the default ~/.unison
+ */
+export interface CodebaseConfig {
+  /** Codebase directory, relative paths are resolved against the workspace */
+  path: string;
+  /** Create the codebase if it doesn't exist (`--codebase-create`) */
+  create: boolean;
+}
+
 /**
  * Workspace configuration stored in .unison-editor/config.json
  */
@@ -12,6 +22,8 @@ export interface WorkspaceConfig {
   version: 1;
   linkedProject: string | null;
   defaultBranch: string | null;
+  /** Codebase for this workspace (missing/null = UCM's default codebase) */
+  codebase?: CodebaseConfig | null;
   createdAt: string;
   updatedAt: string;
 }
@@ -124,6 +136,7 @@ function getDefaultConfig(): WorkspaceConfig {
     version: 1,
     linkedProject: null,
     defaultBranch: null,
+    codebase: null,
     createdAt: now,
     updatedAt: now,
   };
@@ -203,7 +216,7 @@ export class WorkspaceConfigService {
   async loadConfig(workspacePath: string): Promise<WorkspaceConfig | null> {
     try {
       const configPath = this.getConfigFilePath(workspacePath);
-      const content = await invoke<string>('read_file', { path: configPath });
+      const { content } = await invoke<{ content: string }>('read_file', { path: configPath });
       const config = JSON.parse(content) as WorkspaceConfig;
       logger.info('system', 'Loaded workspace config', {
         workspacePath,
@@ -241,7 +254,7 @@ export class WorkspaceConfigService {
       if (!exists) {
         return null;
       }
-      const content = await invoke<string>('read_file', { path: statePath });
+      const { content } = await invoke<{ content: string }>('read_file', { path: statePath });
       const state = JSON.parse(content) as WorkspaceEditorState;
       logger.info('system', 'Loaded editor state', {
         workspacePath,
@@ -288,6 +301,21 @@ export class WorkspaceConfigService {
       await this.saveConfig(workspacePath, config);
     }
   }
+
+  /**
+   * Update the codebase UCM opens for this workspace
+   */
+  async updateCodebase(
+    workspacePath: string,
+    codebase: CodebaseConfig | null
+  ): Promise<void> {
+    const config = await this.loadConfig(workspacePath);
+    if (config) {
+      config.codebase = codebase;
+      config.updatedAt = new Date().toISOString();
+      await this.saveConfig(workspacePath, config);
+    }
+  }
 }
 
 // Singleton instance
@@ -0,0 +1,134 @@
+/**
+ * Workspace Search Service
+ *
+ * Find in files and replace across the workspace (`search_workspace`,
+ * `replace_in_workspace`). Matches stream in as `workspace-search-match` events
+ * while the search runs; replacements are previewed before they are applied.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+export interface SearchQuery {
+  query: string;
+  regex?: boolean;
+  caseSensitive?: boolean;
+  wholeWord?: boolean;
+  /** Globs relative to the workspace root (default: all `.u` files) */
+  include?: string[];
+  exclude?: string[];
+  maxMatches?: number;
+}
+
+export interface SearchMatch {
+  /** 1-based line and columns, as used by the editor */
+  line: number;
+  column: number;
+  endColumn: number;
+  lineText: string;
+}
+
+export interface FileMatches {
+  path: string;
+  matches: SearchMatch[];
+}
+
+export interface SearchSummary {
+  filesSearched: number;
+  fileCount: number;
+  matchCount: number;
+  /** The match limit was reached */
+  truncated: boolean;
+  cancelled: boolean;
+}
+
+export interface LineChange {
+  line: number;
+  before: string;
+  after: string;
+}
+
+export interface FileReplacement {
+  path: string;
+  replacements: number;
+  changes: LineChange[];
+  /** Version of the file the preview was computed from */
+  version: string;
+}
+
+export interface ReplaceResult {
+  files: FileReplacement[];
+  totalReplacements: number;
+  applied: boolean;
+}
+
+let nextSearchId = 0;
+
+/** A running search; `cancel()` stops it, `done` resolves with the summary */
+export interface WorkspaceSearch {
+  searchId: string;
+  done: Promise<SearchSummary>;
+  cancel: () => Promise<void>;
+}
+
+/** Search the workspace, calling `onMatches` for every file with matches */
+export async function searchWorkspace(
+  workspace: string,
+  query: SearchQuery,
+  onMatches: (matches: FileMatches) => void
+): Promise<WorkspaceSearch> {
+  const searchId = `search-${++nextSearchId}`;
+  const unlisten = await listen<FileMatches & { searchId: string }>('workspace-search-match', (event) => {
+    if (event.payload.searchId !== searchId) return;
+    const { searchId: _searchId, ...matches } = event.payload;
+    onMatches(matches);
+  });
+
+  const done = invoke<SearchSummary>('search_workspace', {
+    path: workspace,
+    query,
+    searchId,
+    workspace,
+  }).finally(unlisten);
+
+  return {
+    searchId,
+    done,
+    cancel: async () => {
+      await invoke('cancel_workspace_search', { searchId });
+    },
+  };
+}
+
+/** Compute the replacements without writing anything */
+export function previewReplace(workspace: string, query: SearchQuery, replacement: string): Promise<ReplaceResult> {
+  return invoke<ReplaceResult>('replace_in_workspace', {
+    path: workspace,
+    query,
+    replacement,
+    preview: true,
+    versions: null,
+    workspace,
+  });
+}
+
+/**
+ * Apply a previewed replacement to the files of the preview. Fails without writing
+ * anything if one of them changed since the preview.
+ */
+export function applyReplace(
+  workspace: string,
+  query: SearchQuery,
+  replacement: string,
+  preview: ReplaceResult
+): Promise<ReplaceResult> {
+  const versions = Object.fromEntries(preview.files.map((file) => [file.path, file.version]));
+  return invoke<ReplaceResult>('replace_in_workspace', {
+    path: workspace,
+    query,
+    replacement,
+    preview: false,
+    versions,
+    workspace,
+  });
+}
@@ -2,6 +2,7 @@ import { create } from 'zustand';
 import type { DefinitionSummary } from '../types/syntax';
 import type { ResolvedDefinition } from '../types/navigation';
 import { DEFAULT_LAYOUT, type LayoutState } from '../services/workspaceConfigService';
+import { getFileSystemService } from '../services/fileSystem';
 import type { LogEntry, LogFilter } from '../types/logging';
 import { DEFAULT_LOG_FILTER } from '../types/logging';
 
@@ -59,6 +60,7 @@ export interface EditorTab {
   language: string;
   isDirty: boolean;
   filePath?: string; // Optional: path to the file on disk
+  fileVersion?: string; // Version of the file on disk the content is based on (from read_file/write_file)
   saveStatus?: 'saved' | 'saving' | 'error'; // Save status indicator
 }
 
@@ -145,7 +147,8 @@ interface UnisonState {
   clearTabs: () => void;
 
   // File system actions
-  setWorkspaceDirectory: (directory: string | null) => void;
+  /** Opens the workspace in the backend, then publishes it; throws if it can't be opened */
+  setWorkspaceDirectory: (directory: string | null) => Promise<void>;
   addRecentFile: (filePath: string) => void;
 
   // Workspace configuration actions
@@ -214,7 +217,7 @@ export const useUnisonStore = create<UnisonState>((set, get) => ({
   tabs: [],
   activeTabId: null,
 
-  workspaceDirectory: localStorage.getItem('workspaceDirectory') || null,
+  workspaceDirectory: null,
   recentFiles: safeLocalStorageGetJson<string[]>('recentFiles', []),
 
   // Workspace configuration state
@@ -313,7 +316,10 @@ export const useUnisonStore = create<UnisonState>((set, get) => ({
   clearTabs: () => set({ tabs: [], activeTabId: null }),
 
   // File system actions
-  setWorkspaceDirectory: (directory) => {
+  setWorkspaceDirectory: async (directory) => {
+    // Awaited before the state update, so the backend knows the root by the time the
+    // workspace's files are requested
+    await getFileSystemService().openWorkspace(directory);
     if (directory) {
       localStorage.setItem('workspaceDirectory', directory);
     } else {
@@ -469,3 +475,20 @@ export const useUnisonStore = create<UnisonState>((set, get) => ({
   setShortcutsSettingsOpen: (open) => set({ shortcutsSettingsOpen: open }),
   setThemeSettingsOpen: (open) => set({ themeSettingsOpen: open }),
 }));
+
+/**
+ * Reopen the workspace of the last session. It is published only once the backend has
+ * opened it, so its files can be read as soon as it is set.
+ */
+async function restoreWorkspaceDirectory(): Promise<void> {
+  const directory = localStorage.getItem('workspaceDirectory');
+  if (!directory) return;
+  try {
+    await useUnisonStore.getState().setWorkspaceDirectory(directory);
+  } catch (error) {
+    console.error('[Store] Failed to restore workspace:', error);
+    localStorage.removeItem('workspaceDirectory');
+  }
+}
+
+restoreWorkspaceDirectory();